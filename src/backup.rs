@@ -0,0 +1,347 @@
+//! Writing generated files safely and keeping backups of them
+//!
+//! Env files often hold real secrets, so a crash or a full disk while writing
+//! must never leave one half-written. [`write_atomic`] writes to a temporary
+//! file next to the target, syncs it and renames it over the target, keeping
+//! the target's permissions. Before replacing a file, a copy can be kept as
+//! `.env.bak` or as a timestamped `.env.<time>.bak`, which [`restore`] puts
+//! back.
+
+use crate::stale::civil_date;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Extension of backup files
+const BACKUP_EXTENSION: &str = "bak";
+
+/// Unix mode of files only their owner may read and write
+pub const PRIVATE_MODE: u32 = 0o600;
+
+/// Whether to keep a copy of a file before replacing it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackupMode {
+    /// Keep no copy (the default)
+    #[default]
+    Off,
+    /// Keep the previous version as `.env.bak`, replacing an older backup
+    Single,
+    /// Keep every version as `.env.<YYYYMMDDTHHMMSSZ>.bak`
+    Timestamped,
+}
+
+/// Replace the file at `path` with `content` without ever exposing a partial file
+///
+/// An existing file keeps its permissions, unless `private` is set, e.g.
+/// because the file holds secrets: then a new file gets mode 0600 and an
+/// existing one loses any group and other permissions, keeping stricter ones.
+pub fn write_atomic(path: &Path, content: &[u8], private: bool) -> Result<()> {
+    let permissions = match fs::metadata(path) {
+        Ok(metadata) if private => Some(restrict(metadata.permissions())),
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            private.then(private_permissions).flatten()
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read file: {:?}", path));
+        }
+    };
+    write_atomic_with(path, content, permissions)
+}
+
+/// Permissions letting only the owner read and write a file, where supported
+fn private_permissions() -> Option<Permissions> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Some(Permissions::from_mode(PRIVATE_MODE))
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// `permissions` without any beyond read and write for the owner
+fn restrict(permissions: Permissions) -> Permissions {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Permissions::from_mode(permissions.mode() & PRIVATE_MODE)
+    }
+    #[cfg(not(unix))]
+    {
+        permissions
+    }
+}
+
+/// Write through a synced temporary file in the same directory, then rename it
+fn write_atomic_with(path: &Path, content: &[u8], permissions: Option<Permissions>) -> Result<()> {
+    let tmp_path = temp_path(path);
+    let result = (|| {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        // Never let the temporary file be more readable than the result
+        #[cfg(unix)]
+        if permissions.is_some() {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(PRIVATE_MODE);
+        }
+        let mut file = options.open(&tmp_path)?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if let Err(err) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err).with_context(|| format!("Failed to write file: {:?}", path));
+    }
+
+    // Make the rename itself durable; not all platforms can open directories
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(parent_dir(path)) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Directory containing `path`, `.` for a bare file name
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Hidden temporary file next to `path`, unique to this process
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Keep a copy of the file at `path` as `mode` asks before it is replaced
+///
+/// The copy is only readable by its owner.
+/// Returns the path of the backup, `None` if none was made because backups
+/// are off or the file doesn't exist.
+pub fn back_up(path: &Path, mode: BackupMode) -> Result<Option<PathBuf>> {
+    if mode == BackupMode::Off {
+        return Ok(None);
+    }
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read file: {:?}", path)),
+    };
+    let permissions = fs::metadata(path)
+        .with_context(|| format!("Failed to read file: {:?}", path))?
+        .permissions();
+
+    let backup = match mode {
+        BackupMode::Off => unreachable!(),
+        BackupMode::Single => with_suffix(path, BACKUP_EXTENSION),
+        BackupMode::Timestamped => {
+            let stamp = timestamp(SystemTime::now());
+            // Runs within the same second get a counter so no backup is overwritten
+            let mut backup = with_suffix(path, &format!("{}.{}", stamp, BACKUP_EXTENSION));
+            let mut counter = 1;
+            while backup.exists() {
+                backup = with_suffix(path, &format!("{}-{}.{}", stamp, counter, BACKUP_EXTENSION));
+                counter += 1;
+            }
+            backup
+        }
+    };
+    // Backups hold the same secrets, but nobody else needs to read them
+    write_atomic_with(&backup, &content, Some(restrict(permissions)))
+        .with_context(|| format!("Failed to back up {:?}", path))?;
+    Ok(Some(backup))
+}
+
+/// Backups of the file at `path`, newest first
+pub fn list_backups(path: &Path) -> Result<Vec<PathBuf>> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let dir = parent_dir(path);
+    let prefix = format!("{}.", name);
+    let suffix = format!(".{}", BACKUP_EXTENSION);
+
+    let mut backups = Vec::new();
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory: {:?}", dir))?
+    {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let Some(rest) = file_name.strip_prefix(&prefix) else {
+            continue;
+        };
+        // `.env.bak` or `.env.<timestamp>.bak`, but not `.env.production`
+        let is_backup =
+            rest == BACKUP_EXTENSION || rest.strip_suffix(&suffix).is_some_and(is_timestamp);
+        if is_backup {
+            let modified = entry.metadata()?.modified()?;
+            backups.push((modified, file_name, entry.path()));
+        }
+    }
+    backups.sort_by(|a, b| b.cmp(a));
+    Ok(backups.into_iter().map(|(_, _, path)| path).collect())
+}
+
+/// Put a backup of the file at `path` back, by default the newest one
+///
+/// Returns the backup that was restored. The backup itself is kept.
+pub fn restore(path: &Path, backup: Option<&Path>) -> Result<PathBuf> {
+    let backup = match backup {
+        Some(backup) => backup.to_path_buf(),
+        None => match list_backups(path)?.into_iter().next() {
+            Some(backup) => backup,
+            None => bail!("No backups of {:?} found", path),
+        },
+    };
+    let content =
+        fs::read(&backup).with_context(|| format!("Failed to read backup: {:?}", backup))?;
+    let permissions = fs::metadata(&backup)
+        .with_context(|| format!("Failed to read backup: {:?}", backup))?
+        .permissions();
+    write_atomic_with(path, &content, Some(permissions))?;
+    Ok(backup)
+}
+
+/// `path` with `.{suffix}` appended to its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}", name, suffix))
+}
+
+/// UTC time in the compact ISO 8601 form `YYYYMMDDTHHMMSSZ`
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let date = civil_date((secs / 86_400) as i64).replace('-', "");
+    let secs = secs % 86_400;
+    format!(
+        "{}T{:02}{:02}{:02}Z",
+        date,
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Whether `text` is a timestamp as written by [`timestamp`], with an optional counter
+fn is_timestamp(text: &str) -> bool {
+    let stamp = text.split_once('-').map_or(text, |(stamp, counter)| {
+        if counter.chars().all(|c| c.is_ascii_digit()) && !counter.is_empty() {
+            stamp
+        } else {
+            ""
+        }
+    });
+    stamp.len() == 16
+        && stamp.char_indices().all(|(i, c)| match i {
+            8 => c == 'T',
+            15 => c == 'Z',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_secs(20_744 * 86_400 + 3_600 + 2 * 60 + 3);
+        assert_eq!(timestamp(time), "20261018T010203Z");
+        assert!(is_timestamp("20261018T010203Z"));
+        assert!(is_timestamp("20261018T010203Z-2"));
+        assert!(!is_timestamp("production"));
+        assert!(!is_timestamp("20261018T010203Z-x"));
+    }
+
+    #[test]
+    fn test_write_atomic_keeps_permissions() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join(".env");
+        write_atomic(&path, b"A=1\n", false)?;
+        assert_eq!(fs::read_to_string(&path)?, "A=1\n");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, Permissions::from_mode(0o640))?;
+            write_atomic(&path, b"A=2\n", false)?;
+            assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o640);
+
+            // Private files lose group and other permissions but stay read-only
+            write_atomic(&path, b"A=2\n", true)?;
+            assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+            fs::set_permissions(&path, Permissions::from_mode(0o444))?;
+            write_atomic(&path, b"A=2\n", true)?;
+            assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o400);
+        }
+        assert_eq!(fs::read_to_string(&path)?, "A=2\n");
+
+        // No temporary file is left behind
+        let names: Vec<_> = fs::read_dir(temp_dir.path())?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<std::io::Result<_>>()?;
+        assert_eq!(names, vec![".env"]);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let private = temp_dir.path().join(".env.local");
+            write_atomic(&private, b"SECRET=1\n", true)?;
+            assert_eq!(
+                fs::metadata(&private)?.permissions().mode() & 0o777,
+                PRIVATE_MODE
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_back_up_and_restore() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join(".env");
+        fs::write(temp_dir.path().join(".env.production"), "PROD=1\n")?;
+        assert_eq!(back_up(&path, BackupMode::Single)?, None);
+        assert!(restore(&path, None).is_err());
+
+        fs::write(&path, "A=1\n")?;
+        assert_eq!(back_up(&path, BackupMode::Off)?, None);
+        let single = back_up(&path, BackupMode::Single)?.unwrap();
+        assert_eq!(single, temp_dir.path().join(".env.bak"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&single)?.permissions().mode() & 0o077, 0);
+        }
+
+        fs::write(&path, "A=2\n")?;
+        let first = back_up(&path, BackupMode::Timestamped)?.unwrap();
+        fs::write(&path, "A=3\n")?;
+        let second = back_up(&path, BackupMode::Timestamped)?.unwrap();
+        assert_ne!(first, second);
+        fs::write(&path, "A=4\n")?;
+
+        let backups = list_backups(&path)?;
+        assert_eq!(backups.len(), 3);
+        assert!(!backups.contains(&temp_dir.path().join(".env.production")));
+
+        assert_eq!(restore(&path, Some(&single))?, single);
+        assert_eq!(fs::read_to_string(&path)?, "A=1\n");
+        assert_eq!(fs::read_to_string(&second)?, "A=3\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+//! On-disk cache of per-file scan results
+//!
+//! The cache maps every scanned file (relative to the scan root) to its size,
+//! modification time, content hash and what scanning it found. Files whose
+//! size and mtime are unchanged are not reread; files whose metadata changed but
+//! whose content hash is identical are not rescanned.
+
+use crate::FileScan;
+use crate::backup;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Bump whenever the cache layout or the meaning of cached detections changes
+const CACHE_VERSION: u32 = 4;
+
+/// Files modified this close to the scan start are always re-hashed on the next
+/// run, since a later write within the same mtime granularity would go unnoticed
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Cached scan result for a single file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    /// Modification time as (seconds, nanoseconds) since the Unix epoch;
+    /// `None` when the mtime was too recent to be trusted
+    mtime: Option<(u64, u32)>,
+    hash: u64,
+    scan: FileScan,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    fingerprint: u64,
+    entries: HashMap<String, CacheEntry>,
+}
+
+/// Metadata of a file on disk, used as the cache key
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FileStamp {
+    size: u64,
+    mtime: Option<(u64, u32)>,
+}
+
+impl FileStamp {
+    /// Read the size and modification time of a file
+    pub(crate) fn of(path: &Path) -> Result<Self> {
+        let metadata =
+            fs::metadata(path).with_context(|| format!("Failed to stat file: {:?}", path))?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|d| (d.as_secs(), d.subsec_nanos()));
+        Ok(Self {
+            size: metadata.len(),
+            mtime,
+        })
+    }
+}
+
+/// Result of looking up a file in the cache
+pub(crate) enum Lookup {
+    /// Size and mtime match; the cached result can be used as-is
+    Fresh { hash: u64, scan: FileScan },
+    /// Metadata changed; the result is valid only if the content hash matches
+    Stale { hash: u64, scan: FileScan },
+    /// The file is not in the cache
+    Missing,
+}
+
+/// Persistent per-file scan cache
+pub(crate) struct ScanCache {
+    path: PathBuf,
+    root: PathBuf,
+    fingerprint: u64,
+    started: SystemTime,
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl ScanCache {
+    /// Load the cache stored at `path` for files below `root`
+    ///
+    /// A missing, unreadable or outdated cache file yields an empty cache; entries
+    /// are discarded wholesale when `fingerprint` differs from the stored one.
+    pub(crate) fn load(path: PathBuf, root: &Path, fingerprint: u64) -> Self {
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheFile>(&bytes).ok())
+            .filter(|file| file.version == CACHE_VERSION && file.fingerprint == fingerprint)
+            .map(|file| file.entries)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            root: root.to_path_buf(),
+            fingerprint,
+            started: SystemTime::now(),
+            entries,
+        }
+    }
+
+    fn key(&self, file: &Path) -> String {
+        file.strip_prefix(&self.root)
+            .unwrap_or(file)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Look up the cached scan result for a file
+    pub(crate) fn lookup(&self, file: &Path, stamp: FileStamp) -> Lookup {
+        match self.entries.get(&self.key(file)) {
+            Some(entry)
+                if entry.size == stamp.size
+                    && entry.mtime.is_some()
+                    && entry.mtime == stamp.mtime =>
+            {
+                Lookup::Fresh {
+                    hash: entry.hash,
+                    scan: entry.scan.clone(),
+                }
+            }
+            Some(entry) => Lookup::Stale {
+                hash: entry.hash,
+                scan: entry.scan.clone(),
+            },
+            None => Lookup::Missing,
+        }
+    }
+
+    /// Merge the results of the latest scan into the cache
+    ///
+    /// Entries of files that were not part of the scan are kept, so scanning a
+    /// subset of the tree doesn't evict the rest; files that no longer exist
+    /// are dropped.
+    pub(crate) fn update(&mut self, results: Vec<(PathBuf, FileStamp, u64, FileScan)>) {
+        let racy_after = self
+            .started
+            .checked_sub(RACY_WINDOW)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|d| (d.as_secs(), d.subsec_nanos()));
+
+        for (file, stamp, hash, scan) in results {
+            let mtime = match (stamp.mtime, racy_after) {
+                (Some(mtime), Some(limit)) if mtime < limit => Some(mtime),
+                _ => None,
+            };
+            let entry = CacheEntry {
+                size: stamp.size,
+                mtime,
+                hash,
+                scan,
+            };
+            self.entries.insert(self.key(&file), entry);
+        }
+
+        let root = &self.root;
+        self.entries.retain(|key, _| root.join(key).is_file());
+    }
+
+    /// Write the cache back to disk
+    pub(crate) fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create cache directory: {:?}", parent))?;
+        }
+
+        let file = CacheFile {
+            version: CACHE_VERSION,
+            fingerprint: self.fingerprint,
+            entries: self.entries.clone(),
+        };
+        let bytes = serde_json::to_vec(&file).context("Failed to serialize scan cache")?;
+
+        // Written through a per-process temporary file and renamed, so concurrent
+        // runs never see a partial file or clobber each other's temporary file
+        backup::write_atomic(&self.path, &bytes, false)
+            .with_context(|| format!("Failed to write cache file: {:?}", self.path))
+    }
+}
+
+/// 64-bit FNV-1a hash, stable across platforms and Rust versions
+pub(crate) fn content_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Detection;
+    use tempfile::TempDir;
+
+    fn scan(name: &str, line: usize) -> FileScan {
+        FileScan {
+            detections: vec![Detection {
+                name: name.to_string(),
+                line,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cache_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("main.rs");
+        fs::write(&file, "fn main() {}")?;
+        let stamp = FileStamp::of(&file)?;
+        let cache_path = temp_dir.path().join("target/autoenv-cache");
+
+        let mut cache = ScanCache::load(cache_path.clone(), temp_dir.path(), 42);
+        assert!(matches!(cache.lookup(&file, stamp), Lookup::Missing));
+        cache.update(vec![(file.clone(), stamp, 7, scan("A", 1))]);
+        cache.save()?;
+
+        let cache = ScanCache::load(cache_path.clone(), temp_dir.path(), 42);
+        match cache.lookup(&file, stamp) {
+            // The file was just written, so its mtime is inside the racy window
+            Lookup::Stale { hash, scan: cached } => {
+                assert_eq!(hash, 7);
+                assert_eq!(cached, scan("A", 1));
+            }
+            _ => panic!("Expected a stale entry for a racily-clean file"),
+        }
+
+        // A different fingerprint invalidates every entry
+        let cache = ScanCache::load(cache_path, temp_dir.path(), 43);
+        assert!(matches!(cache.lookup(&file, stamp), Lookup::Missing));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_keeps_entries_of_other_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let main = temp_dir.path().join("main.rs");
+        let lib = temp_dir.path().join("lib.rs");
+        let gone = temp_dir.path().join("gone.rs");
+        fs::write(&main, "fn main() {}")?;
+        fs::write(&lib, "")?;
+        fs::write(&gone, "")?;
+        let cache_path = temp_dir.path().join("target/autoenv-cache");
+
+        let mut cache = ScanCache::load(cache_path, temp_dir.path(), 42);
+        cache.update(vec![
+            (main.clone(), FileStamp::of(&main)?, 1, scan("A", 1)),
+            (lib.clone(), FileStamp::of(&lib)?, 2, scan("B", 1)),
+            (gone.clone(), FileStamp::of(&gone)?, 3, scan("C", 1)),
+        ]);
+
+        // Rescanning one file leaves the others cached, except the deleted one
+        fs::remove_file(&gone)?;
+        cache.update(vec![(main.clone(), FileStamp::of(&main)?, 4, scan("A", 2))]);
+
+        let stamp = FileStamp::of(&lib)?;
+        assert!(matches!(
+            cache.lookup(&lib, stamp),
+            Lookup::Stale { hash: 2, .. }
+        ));
+        assert!(matches!(
+            cache.lookup(&main, stamp),
+            Lookup::Stale { hash: 4, .. }
+        ));
+        assert!(matches!(cache.lookup(&gone, stamp), Lookup::Missing));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(content_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(
+            content_hash(b"env::var(\"A\")"),
+            content_hash(b"env::var(\"B\")")
+        );
+    }
+}
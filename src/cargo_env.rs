@@ -0,0 +1,240 @@
+//! Variables provided by Cargo at build time
+//!
+//! Variables set in the `[env]` table of `.cargo/config.toml` or emitted by a
+//! build script with `cargo:rustc-env=NAME=value` are satisfied by Cargo and
+//! need not be provided by the user. This module finds them so generated files
+//! can leave them out or mark them as provided.
+
+use crate::modules::{self, TargetKind};
+use crate::workspace::Workspace;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Instruction printed by a build script to set a variable, in the old
+/// (`cargo:`) or new (`cargo::`) syntax
+static RUSTC_ENV: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"cargo::?rustc-env=([A-Za-z_][A-Za-z0-9_]*)=([^"]*)""#).unwrap());
+
+/// How generated files treat variables provided by Cargo
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProvidedMode {
+    /// Leave them out (the default)
+    #[default]
+    Exclude,
+    /// List them commented out in a separate section, noting where they are set
+    Annotate,
+    /// List them like any other variable
+    Keep,
+}
+
+/// Where Cargo gets a variable from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Provider {
+    /// The `[env]` table of a Cargo configuration file
+    CargoConfig,
+    /// A `cargo:rustc-env` instruction of a build script
+    BuildScript,
+}
+
+/// A variable Cargo sets at build time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProvidedVariable {
+    /// Name of the environment variable
+    pub name: String,
+    /// Value, if it is a literal
+    pub value: Option<String>,
+    /// Kind of source providing the variable
+    pub provider: Provider,
+    /// File the variable is set in
+    pub origin: PathBuf,
+}
+
+impl ProvidedVariable {
+    /// Human-readable description of where the variable is set
+    pub fn describe(&self) -> String {
+        match self.provider {
+            Provider::CargoConfig => format!("[env] in {}", self.origin.display()),
+            Provider::BuildScript => format!("cargo:rustc-env in {}", self.origin.display()),
+        }
+    }
+}
+
+/// Variables from the `[env]` tables of `.cargo/config.toml` files in `dir` and
+/// its ancestors; as in Cargo, the file closest to `dir` wins
+pub fn cargo_config_env<P: AsRef<Path>>(dir: P) -> Result<Vec<ProvidedVariable>> {
+    let dir = dir.as_ref();
+    let absolute = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mut provided: BTreeMap<String, ProvidedVariable> = BTreeMap::new();
+
+    for ancestor in absolute.ancestors() {
+        for name in [".cargo/config.toml", ".cargo/config"] {
+            let path = ancestor.join(name);
+            if !path.is_file() {
+                continue;
+            }
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read Cargo config: {:?}", path))?;
+            let config: toml::Value = toml::from_str(&content)
+                .with_context(|| format!("Failed to parse Cargo config: {:?}", path))?;
+            let Some(env) = config.get("env").and_then(|env| env.as_table()) else {
+                continue;
+            };
+
+            let origin = path
+                .strip_prefix(&absolute)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| path.clone());
+            for (key, value) in env {
+                // `NAME = "value"` or `NAME = { value = "...", force = true }`
+                let value = value
+                    .as_str()
+                    .or_else(|| value.get("value").and_then(|value| value.as_str()))
+                    .map(str::to_string);
+                provided
+                    .entry(key.clone())
+                    .or_insert_with(|| ProvidedVariable {
+                        name: key.clone(),
+                        value,
+                        provider: Provider::CargoConfig,
+                        origin: origin.clone(),
+                    });
+            }
+        }
+    }
+
+    Ok(provided.into_values().collect())
+}
+
+/// Find `cargo:rustc-env=NAME=value` instructions in build script source
+fn rustc_env_instructions(source: &str) -> Vec<(String, Option<String>)> {
+    RUSTC_ENV
+        .captures_iter(source)
+        .map(|cap| {
+            let value = &cap[2];
+            // Values built with format! are not known until the script runs
+            let value = (!value.contains('{')).then(|| value.to_string());
+            (cap[1].to_string(), value)
+        })
+        .collect()
+}
+
+/// Variables emitted by the build scripts of the packages in `dir`
+pub fn build_script_env<P: AsRef<Path>>(dir: P) -> Result<Vec<ProvidedVariable>> {
+    let dir = dir.as_ref();
+    let mut scripts = Vec::new();
+
+    for package in Workspace::discover(dir)?.packages {
+        for target in modules::package_targets(dir, &package)? {
+            if target.kind == TargetKind::BuildScript && dir.join(&target.root).is_file() {
+                scripts.extend(modules::module_files(&dir.join(&target.root))?);
+            }
+        }
+    }
+
+    let mut provided: BTreeMap<String, ProvidedVariable> = BTreeMap::new();
+    for script in scripts {
+        let content = fs::read_to_string(&script)
+            .with_context(|| format!("Failed to read build script: {:?}", script))?;
+        let origin = script
+            .strip_prefix(dir)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| script.clone());
+        for (name, value) in rustc_env_instructions(&content) {
+            provided
+                .entry(name.clone())
+                .or_insert_with(|| ProvidedVariable {
+                    name,
+                    value,
+                    provider: Provider::BuildScript,
+                    origin: origin.clone(),
+                });
+        }
+    }
+
+    Ok(provided.into_values().collect())
+}
+
+/// All variables Cargo provides for the project in `dir`, keyed by name
+///
+/// `[env]` entries take precedence over build script instructions.
+pub fn provided_variables<P: AsRef<Path>>(dir: P) -> Result<BTreeMap<String, ProvidedVariable>> {
+    let dir = dir.as_ref();
+    let mut provided: BTreeMap<String, ProvidedVariable> = build_script_env(dir)?
+        .into_iter()
+        .map(|variable| (variable.name.clone(), variable))
+        .collect();
+    for variable in cargo_config_env(dir)? {
+        provided.insert(variable.name.clone(), variable);
+    }
+    Ok(provided)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rustc_env_instructions() {
+        let source = r#"
+fn main() {
+    println!("cargo:rustc-env=GIT_HASH={}", hash);
+    println!("cargo::rustc-env=BUILD_PROFILE=release");
+    println!("cargo:rerun-if-changed=build.rs");
+}
+"#;
+        assert_eq!(
+            rustc_env_instructions(source),
+            vec![
+                ("GIT_HASH".to_string(), None),
+                ("BUILD_PROFILE".to_string(), Some("release".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_provided_variables() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join(".cargo"))?;
+        fs::create_dir_all(root.join("src"))?;
+        fs::write(
+            root.join(".cargo/config.toml"),
+            "[env]\nAPP_MODE = \"dev\"\nASSET_DIR = { value = \"assets\", relative = true }\n",
+        )?;
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )?;
+        fs::write(root.join("src/main.rs"), "fn main() {}\n")?;
+        fs::write(
+            root.join("build.rs"),
+            "fn main() { println!(\"cargo:rustc-env=APP_MODE=ignored\"); \
+             println!(\"cargo:rustc-env=BUILD_TIME={}\", now()); }\n",
+        )?;
+
+        let provided = provided_variables(root)?;
+        assert_eq!(
+            provided.keys().collect::<Vec<_>>(),
+            vec!["APP_MODE", "ASSET_DIR", "BUILD_TIME"]
+        );
+        assert_eq!(provided["APP_MODE"].provider, Provider::CargoConfig);
+        assert_eq!(provided["APP_MODE"].value, Some("dev".to_string()));
+        assert_eq!(provided["ASSET_DIR"].value, Some("assets".to_string()));
+        assert_eq!(provided["BUILD_TIME"].provider, Provider::BuildScript);
+        assert_eq!(provided["BUILD_TIME"].origin, PathBuf::from("build.rs"));
+        assert_eq!(
+            provided["APP_MODE"].describe(),
+            "[env] in .cargo/config.toml"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+//! Catalogue of well-known environment variables
+//!
+//! Many variables a program reads are set by the operating system, the shell,
+//! Cargo or a CI platform rather than by the user: `HOME`, `CARGO_PKG_VERSION`,
+//! `GITHUB_TOKEN`, `RUST_BACKTRACE`. The catalogue lists them with a category
+//! and description so generated files can leave them out or annotate them
+//! without hand-maintained ignore lists.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Version of the built-in catalogue; bumped whenever entries change
+pub const CATALOGUE_VERSION: u32 = 1;
+
+/// Where a well-known variable comes from
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Deserialize,
+    Serialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Set by the operating system, login session or shell (POSIX and common extensions)
+    System,
+    /// Set by Cargo when building, running or testing
+    Cargo,
+    /// Set by continuous integration platforms
+    Ci,
+    /// Read by the Rust standard library and test harness
+    Rust,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Category::System => "system",
+            Category::Cargo => "cargo",
+            Category::Ci => "ci",
+            Category::Rust => "rust",
+        })
+    }
+}
+
+/// What generated files do with variables of a category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CatalogueAction {
+    /// Leave the variables out
+    Exclude,
+    /// List them commented out in a separate section, with their description
+    Annotate,
+    /// List them like any other variable
+    Keep,
+}
+
+impl Category {
+    /// Action applied when the configuration does not name the category
+    pub fn default_action(self) -> CatalogueAction {
+        match self {
+            Category::System | Category::Cargo => CatalogueAction::Exclude,
+            Category::Ci | Category::Rust => CatalogueAction::Annotate,
+        }
+    }
+}
+
+/// A well-known variable, or a family of variables when the name ends in `*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CatalogueEntry {
+    /// Variable name, or a prefix followed by `*`
+    pub name: &'static str,
+    /// Where the variable comes from
+    pub category: Category,
+    /// What the variable holds
+    pub description: &'static str,
+}
+
+impl CatalogueEntry {
+    /// Whether the entry covers a variable name
+    pub fn matches(&self, name: &str) -> bool {
+        match self.name.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => self.name == name,
+        }
+    }
+}
+
+const fn entry(
+    name: &'static str,
+    category: Category,
+    description: &'static str,
+) -> CatalogueEntry {
+    CatalogueEntry {
+        name,
+        category,
+        description,
+    }
+}
+
+use Category::{Cargo, Ci, Rust, System};
+
+/// The built-in catalogue; exact names come before prefix families
+const ENTRIES: &[CatalogueEntry] = &[
+    // POSIX and shell
+    entry("HOME", System, "Home directory of the current user"),
+    entry("PATH", System, "Directories searched for executables"),
+    entry("USER", System, "Name of the current user"),
+    entry("USERNAME", System, "Name of the current user (Windows)"),
+    entry("LOGNAME", System, "Login name of the current user"),
+    entry("SHELL", System, "Login shell of the current user"),
+    entry("PWD", System, "Current working directory"),
+    entry("OLDPWD", System, "Previous working directory"),
+    entry("TERM", System, "Terminal type"),
+    entry("COLORTERM", System, "Terminal color support"),
+    entry("NO_COLOR", System, "Disables colored output when set"),
+    entry("LANG", System, "Default locale"),
+    entry("LANGUAGE", System, "Preferred languages for messages"),
+    entry("LC_ALL", System, "Locale override for all categories"),
+    entry("TZ", System, "Time zone"),
+    entry("TMPDIR", System, "Directory for temporary files"),
+    entry("TEMP", System, "Directory for temporary files (Windows)"),
+    entry("TMP", System, "Directory for temporary files (Windows)"),
+    entry("EDITOR", System, "Preferred text editor"),
+    entry("VISUAL", System, "Preferred visual editor"),
+    entry("PAGER", System, "Preferred pager"),
+    entry("HOSTNAME", System, "Name of the host"),
+    entry("DISPLAY", System, "X11 display"),
+    entry(
+        "XDG_CONFIG_HOME",
+        System,
+        "Base directory for user configuration",
+    ),
+    entry("XDG_DATA_HOME", System, "Base directory for user data"),
+    entry("XDG_CACHE_HOME", System, "Base directory for user caches"),
+    entry(
+        "XDG_RUNTIME_DIR",
+        System,
+        "Directory for user runtime files",
+    ),
+    entry(
+        "APPDATA",
+        System,
+        "Roaming application data directory (Windows)",
+    ),
+    entry(
+        "LOCALAPPDATA",
+        System,
+        "Local application data directory (Windows)",
+    ),
+    entry(
+        "USERPROFILE",
+        System,
+        "Home directory of the current user (Windows)",
+    ),
+    entry("LC_*", System, "Locale category setting"),
+    // Cargo
+    entry(
+        "CARGO",
+        Cargo,
+        "Path to the cargo binary performing the build",
+    ),
+    entry("CARGO_HOME", Cargo, "Cargo's home directory"),
+    entry("CARGO_TARGET_DIR", Cargo, "Directory for build artifacts"),
+    entry(
+        "CARGO_MANIFEST_DIR",
+        Cargo,
+        "Directory containing the package manifest",
+    ),
+    entry("CARGO_MANIFEST_PATH", Cargo, "Path to the package manifest"),
+    entry(
+        "CARGO_CRATE_NAME",
+        Cargo,
+        "Name of the crate being compiled",
+    ),
+    entry("CARGO_BIN_NAME", Cargo, "Name of the binary being compiled"),
+    entry(
+        "CARGO_PRIMARY_PACKAGE",
+        Cargo,
+        "Set for packages selected on the command line",
+    ),
+    entry(
+        "CARGO_ENCODED_RUSTFLAGS",
+        Cargo,
+        "Extra flags passed to rustc",
+    ),
+    entry("OUT_DIR", Cargo, "Output directory of the build script"),
+    entry(
+        "NUM_JOBS",
+        Cargo,
+        "Parallelism of the build (build scripts)",
+    ),
+    entry("OPT_LEVEL", Cargo, "Optimization level (build scripts)"),
+    entry("RUSTC", Cargo, "Compiler used by Cargo"),
+    entry("RUSTDOC", Cargo, "Documentation generator used by Cargo"),
+    entry("RUSTC_WRAPPER", Cargo, "Wrapper around rustc"),
+    entry("RUSTC_LINKER", Cargo, "Linker used for the target"),
+    entry("RUSTFLAGS", Cargo, "Extra flags passed to rustc"),
+    entry("CARGO_PKG_*", Cargo, "Package metadata from the manifest"),
+    entry("CARGO_FEATURE_*", Cargo, "Enabled feature (build scripts)"),
+    entry("CARGO_CFG_*", Cargo, "Target configuration (build scripts)"),
+    entry(
+        "CARGO_BIN_EXE_*",
+        Cargo,
+        "Path to a binary of the package (integration tests)",
+    ),
+    entry("DEP_*", Cargo, "Metadata from a dependency's build script"),
+    // CI platforms
+    entry("CI", Ci, "Set by most CI platforms"),
+    entry("CONTINUOUS_INTEGRATION", Ci, "Set by some CI platforms"),
+    entry("BUILD_NUMBER", Ci, "Build number (Jenkins and others)"),
+    entry("JENKINS_URL", Ci, "URL of the Jenkins server"),
+    entry(
+        "GITHUB_TOKEN",
+        Ci,
+        "Token provided to GitHub Actions workflows",
+    ),
+    entry("GITHUB_*", Ci, "GitHub Actions context"),
+    entry("RUNNER_*", Ci, "GitHub Actions runner context"),
+    entry("GITLAB_CI", Ci, "Set in GitLab CI jobs"),
+    entry("CI_*", Ci, "GitLab CI job context"),
+    entry("TRAVIS", Ci, "Set in Travis CI builds"),
+    entry("TRAVIS_*", Ci, "Travis CI build context"),
+    entry("CIRCLECI", Ci, "Set in CircleCI jobs"),
+    entry("CIRCLE_*", Ci, "CircleCI job context"),
+    entry("BUILDKITE", Ci, "Set in Buildkite jobs"),
+    entry("BUILDKITE_*", Ci, "Buildkite job context"),
+    entry("TF_BUILD", Ci, "Set in Azure Pipelines"),
+    entry("APPVEYOR", Ci, "Set in AppVeyor builds"),
+    entry("DRONE", Ci, "Set in Drone builds"),
+    // Rust runtime
+    entry("RUST_BACKTRACE", Rust, "Print a backtrace on panic"),
+    entry(
+        "RUST_LIB_BACKTRACE",
+        Rust,
+        "Capture backtraces in std::backtrace",
+    ),
+    entry(
+        "RUST_MIN_STACK",
+        Rust,
+        "Minimum stack size of spawned threads",
+    ),
+    entry(
+        "RUST_TEST_THREADS",
+        Rust,
+        "Number of threads used by the test harness",
+    ),
+    entry("RUST_TEST_NOCAPTURE", Rust, "Show output of passing tests"),
+    entry(
+        "RUST_TEST_TIME_UNIT",
+        Rust,
+        "Warning threshold for unit test time",
+    ),
+];
+
+/// All entries of the built-in catalogue
+pub fn entries() -> &'static [CatalogueEntry] {
+    ENTRIES
+}
+
+/// Find the catalogue entry covering a variable, preferring exact names
+pub fn lookup(name: &str) -> Option<&'static CatalogueEntry> {
+    ENTRIES.iter().find(|entry| entry.name == name).or_else(|| {
+        ENTRIES
+            .iter()
+            .filter(|entry| entry.matches(name))
+            .max_by_key(|entry| entry.name.len())
+    })
+}
+
+/// The action for a category, taking configured overrides into account
+pub fn action_for(
+    category: Category,
+    overrides: Option<&BTreeMap<Category, CatalogueAction>>,
+) -> CatalogueAction {
+    overrides
+        .and_then(|overrides| overrides.get(&category).copied())
+        .unwrap_or_else(|| category.default_action())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("HOME").unwrap().category, Category::System);
+        assert_eq!(lookup("GITHUB_TOKEN").unwrap().name, "GITHUB_TOKEN");
+        assert_eq!(lookup("GITHUB_SHA").unwrap().name, "GITHUB_*");
+        assert_eq!(
+            lookup("CARGO_PKG_VERSION").unwrap().category,
+            Category::Cargo
+        );
+        assert_eq!(lookup("RUST_BACKTRACE").unwrap().category, Category::Rust);
+        assert!(lookup("DATABASE_URL").is_none());
+        // Prefix families need the full prefix
+        assert!(lookup("CIRCLE").is_none());
+    }
+
+    #[test]
+    fn test_action_overrides() {
+        assert_eq!(action_for(Category::System, None), CatalogueAction::Exclude);
+        let overrides = BTreeMap::from([(Category::System, CatalogueAction::Keep)]);
+        assert_eq!(
+            action_for(Category::System, Some(&overrides)),
+            CatalogueAction::Keep
+        );
+        assert_eq!(
+            action_for(Category::Ci, Some(&overrides)),
+            CatalogueAction::Annotate
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! Environment variable changelog between two versions of a project
+//!
+//! Compares two scans and lists the variables that were added or removed, and
+//! those whose requiredness, default value or type changed. The result renders
+//! as plain text, JSON or Markdown for release notes and deployment checklists.
+
+use crate::git::GitRevisionSource;
+use crate::{EnvScanner, VariableInfo};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// A single attribute of a variable that differs between two versions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AttributeChange {
+    /// Name of the attribute: `required`, `default` or `type`
+    pub attribute: String,
+    /// Value in the old version, `None` if unknown or unset
+    pub old: Option<String>,
+    /// Value in the new version, `None` if unknown or unset
+    pub new: Option<String>,
+}
+
+/// A variable present in both versions whose attributes changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangedVariable {
+    /// Name of the environment variable
+    pub name: String,
+    /// The attributes that changed
+    pub changes: Vec<AttributeChange>,
+}
+
+/// Differences in environment variable usage between two versions
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Changelog {
+    /// Label of the old version, e.g. a git revision
+    pub old: String,
+    /// Label of the new version
+    pub new: String,
+    /// Variables only used by the new version
+    pub added: Vec<VariableInfo>,
+    /// Variables only used by the old version
+    pub removed: Vec<VariableInfo>,
+    /// Variables used by both versions in a different way
+    pub changed: Vec<ChangedVariable>,
+}
+
+fn requiredness(required: Option<bool>) -> Option<String> {
+    required.map(|required| if required { "required" } else { "optional" }.to_string())
+}
+
+fn describe_value(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("(none)")
+}
+
+impl Changelog {
+    /// Compare the variables of two scans
+    pub fn between(
+        old_label: &str,
+        old: &BTreeMap<String, VariableInfo>,
+        new_label: &str,
+        new: &BTreeMap<String, VariableInfo>,
+    ) -> Self {
+        let added = new
+            .values()
+            .filter(|info| !old.contains_key(&info.name))
+            .cloned()
+            .collect();
+        let removed = old
+            .values()
+            .filter(|info| !new.contains_key(&info.name))
+            .cloned()
+            .collect();
+
+        let changed = new
+            .values()
+            .filter_map(|new_info| {
+                let old_info = old.get(&new_info.name)?;
+                let candidates = [
+                    (
+                        "required",
+                        requiredness(old_info.required),
+                        requiredness(new_info.required),
+                    ),
+                    (
+                        "default",
+                        old_info.default.clone(),
+                        new_info.default.clone(),
+                    ),
+                    ("type", old_info.var_type.clone(), new_info.var_type.clone()),
+                ];
+                let changes: Vec<_> = candidates
+                    .into_iter()
+                    .filter(|(_, old, new)| old != new)
+                    .map(|(attribute, old, new)| AttributeChange {
+                        attribute: attribute.to_string(),
+                        old,
+                        new,
+                    })
+                    .collect();
+
+                (!changes.is_empty()).then(|| ChangedVariable {
+                    name: new_info.name.clone(),
+                    changes,
+                })
+            })
+            .collect();
+
+        Self {
+            old: old_label.to_string(),
+            new: new_label.to_string(),
+            added,
+            removed,
+            changed,
+        }
+    }
+
+    /// Whether the two versions use environment variables identically
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Render as human-readable text
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "Environment variable changes {}..{}",
+            self.old, self.new
+        )
+        .unwrap();
+
+        if self.is_empty() {
+            writeln!(out, "No changes.").unwrap();
+            return out;
+        }
+
+        if !self.added.is_empty() {
+            writeln!(out, "Added:").unwrap();
+            for info in &self.added {
+                writeln!(out, "  + {}{}", info.name, attributes_suffix(info)).unwrap();
+            }
+        }
+        if !self.removed.is_empty() {
+            writeln!(out, "Removed:").unwrap();
+            for info in &self.removed {
+                writeln!(out, "  - {}", info.name).unwrap();
+            }
+        }
+        if !self.changed.is_empty() {
+            writeln!(out, "Changed:").unwrap();
+            for variable in &self.changed {
+                let changes: Vec<_> = variable
+                    .changes
+                    .iter()
+                    .map(|change| {
+                        format!(
+                            "{} {} -> {}",
+                            change.attribute,
+                            describe_value(&change.old),
+                            describe_value(&change.new)
+                        )
+                    })
+                    .collect();
+                writeln!(out, "  ~ {}: {}", variable.name, changes.join(", ")).unwrap();
+            }
+        }
+
+        out
+    }
+
+    /// Render as Markdown, suitable for release notes
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "## Environment variable changes (`{}..{}`)",
+            self.old, self.new
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+
+        if self.is_empty() {
+            writeln!(out, "No changes.").unwrap();
+            return out;
+        }
+
+        if !self.added.is_empty() {
+            writeln!(out, "### Added").unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "| Variable | Required | Default | Type |").unwrap();
+            writeln!(out, "| --- | --- | --- | --- |").unwrap();
+            for info in &self.added {
+                writeln!(
+                    out,
+                    "| `{}` | {} | {} | {} |",
+                    info.name,
+                    requiredness(info.required).unwrap_or_else(|| "unknown".to_string()),
+                    code_or_dash(&info.default),
+                    code_or_dash(&info.var_type)
+                )
+                .unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        if !self.removed.is_empty() {
+            writeln!(out, "### Removed").unwrap();
+            writeln!(out).unwrap();
+            for info in &self.removed {
+                writeln!(out, "- `{}`", info.name).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        if !self.changed.is_empty() {
+            writeln!(out, "### Changed").unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "| Variable | Attribute | Old | New |").unwrap();
+            writeln!(out, "| --- | --- | --- | --- |").unwrap();
+            for variable in &self.changed {
+                for change in &variable.changes {
+                    writeln!(
+                        out,
+                        "| `{}` | {} | {} | {} |",
+                        variable.name,
+                        change.attribute,
+                        code_or_dash(&change.old),
+                        code_or_dash(&change.new)
+                    )
+                    .unwrap();
+                }
+            }
+            writeln!(out).unwrap();
+        }
+
+        out
+    }
+
+    /// Render as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize changelog")
+    }
+}
+
+fn code_or_dash(value: &Option<String>) -> String {
+    match value {
+        Some(value) => format!("`{}`", value.replace('|', "\\|")),
+        None => "-".to_string(),
+    }
+}
+
+/// Summary of the known attributes of a variable, e.g. ` (required, type u16)`
+fn attributes_suffix(info: &VariableInfo) -> String {
+    let mut attributes = Vec::new();
+    if let Some(required) = requiredness(info.required) {
+        attributes.push(required);
+    }
+    if let Some(default) = &info.default {
+        attributes.push(format!("default {}", default));
+    }
+    if let Some(var_type) = &info.var_type {
+        attributes.push(format!("type {}", var_type));
+    }
+
+    if attributes.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", attributes.join(", "))
+    }
+}
+
+/// Scan two git revisions of the project in `dir` and compare them
+pub fn diff_revisions<P: AsRef<Path>>(
+    scanner: &EnvScanner,
+    dir: P,
+    old_rev: &str,
+    new_rev: &str,
+) -> Result<Changelog> {
+    let dir = dir.as_ref();
+    let old = scanner
+        .scan_source_detailed(&GitRevisionSource::new(dir, old_rev)?)
+        .with_context(|| format!("Failed to scan revision {}", old_rev))?;
+    let new = scanner
+        .scan_source_detailed(&GitRevisionSource::new(dir, new_rev)?)
+        .with_context(|| format!("Failed to scan revision {}", new_rev))?;
+
+    Ok(Changelog::between(old_rev, &old, new_rev, &new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(name: &str, required: Option<bool>, default: Option<&str>) -> VariableInfo {
+        VariableInfo {
+            name: name.to_string(),
+            required,
+            default: default.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn map(infos: Vec<VariableInfo>) -> BTreeMap<String, VariableInfo> {
+        infos
+            .into_iter()
+            .map(|info| (info.name.clone(), info))
+            .collect()
+    }
+
+    #[test]
+    fn test_changelog_between() {
+        let old = map(vec![
+            info("KEPT", Some(true), None),
+            info("PORT", Some(false), Some("3000")),
+            info("REMOVED", None, None),
+        ]);
+        let new = map(vec![
+            info("ADDED", Some(true), None),
+            info("KEPT", Some(true), None),
+            info("PORT", Some(true), None),
+        ]);
+
+        let changelog = Changelog::between("v1", &old, "v2", &new);
+
+        assert_eq!(changelog.added, vec![info("ADDED", Some(true), None)]);
+        assert_eq!(changelog.removed, vec![info("REMOVED", None, None)]);
+        assert_eq!(
+            changelog.changed,
+            vec![ChangedVariable {
+                name: "PORT".to_string(),
+                changes: vec![
+                    AttributeChange {
+                        attribute: "required".to_string(),
+                        old: Some("optional".to_string()),
+                        new: Some("required".to_string()),
+                    },
+                    AttributeChange {
+                        attribute: "default".to_string(),
+                        old: Some("3000".to_string()),
+                        new: None,
+                    },
+                ],
+            }]
+        );
+
+        let text = changelog.to_text();
+        assert!(text.contains("  + ADDED (required)"));
+        assert!(text.contains("  - REMOVED"));
+        assert!(text.contains("  ~ PORT: required optional -> required, default 3000 -> (none)"));
+
+        let markdown = changelog.to_markdown();
+        assert!(markdown.contains("| `ADDED` | required | - | - |"));
+        assert!(markdown.contains("| `PORT` | default | `3000` | - |"));
+
+        let json: serde_json::Value = serde_json::from_str(&changelog.to_json().unwrap()).unwrap();
+        assert_eq!(json["added"][0]["name"], "ADDED");
+        assert_eq!(json["changed"][0]["changes"][0]["attribute"], "required");
+    }
+
+    #[test]
+    fn test_empty_changelog() {
+        let vars = map(vec![info("SAME", Some(true), None)]);
+        let changelog = Changelog::between("a", &vars, "b", &vars);
+        assert!(changelog.is_empty());
+        assert!(changelog.to_text().contains("No changes."));
+    }
+}
@@ -0,0 +1,215 @@
+//! Checking that a committed env file is up to date, for CI
+//!
+//! [`check_directory`] scans a directory like `generate` does and compares the
+//! result with the existing output file without writing anything. The file is
+//! out of date when the code reads variables it doesn't list, or when it sets
+//! variables the code no longer reads.
+
+use crate::dotenv::Document;
+use crate::{EnvScanner, stale};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// How an env file differs from what the code needs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CheckReport {
+    /// Path of the checked file
+    pub path: PathBuf,
+    /// Whether the file exists
+    pub exists: bool,
+    /// Variables the code reads that the file doesn't list, sorted
+    pub missing: Vec<String>,
+    /// Variables the file sets that the code no longer reads, in file order
+    pub stale: Vec<String>,
+}
+
+impl CheckReport {
+    /// Whether the file lists exactly the variables the code needs
+    pub fn is_up_to_date(&self) -> bool {
+        self.exists && self.missing.is_empty() && self.stale.is_empty()
+    }
+
+    /// Render the report as plain text
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let path = self.path.display();
+        if self.is_up_to_date() {
+            writeln!(out, "{} is up to date.", path).unwrap();
+            return out;
+        }
+
+        if self.exists {
+            writeln!(out, "{} is out of date.", path).unwrap();
+        } else {
+            writeln!(out, "{} does not exist.", path).unwrap();
+        }
+        if !self.missing.is_empty() {
+            writeln!(out, "Missing (read by the code):").unwrap();
+            for name in &self.missing {
+                writeln!(out, "  + {}", name).unwrap();
+            }
+        }
+        if !self.stale.is_empty() {
+            writeln!(out, "Stale (no longer read by the code):").unwrap();
+            for name in &self.stale {
+                writeln!(out, "  - {}", name).unwrap();
+            }
+        }
+        writeln!(out, "Run `autoenv generate` to update it.").unwrap();
+        out
+    }
+
+    /// Render the report as Markdown, e.g. for a pull request comment
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "## `{}`", self.path.display()).unwrap();
+        writeln!(out).unwrap();
+        if self.is_up_to_date() {
+            writeln!(out, "Up to date.").unwrap();
+            return out;
+        }
+
+        if !self.exists {
+            writeln!(out, "The file does not exist.").unwrap();
+            writeln!(out).unwrap();
+        }
+        if !self.missing.is_empty() {
+            writeln!(out, "### Missing").unwrap();
+            writeln!(out).unwrap();
+            for name in &self.missing {
+                writeln!(out, "- `{}`", name).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        if !self.stale.is_empty() {
+            writeln!(out, "### Stale").unwrap();
+            writeln!(out).unwrap();
+            for name in &self.stale {
+                writeln!(out, "- `{}`", name).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        writeln!(out, "Run `autoenv generate` to update it.").unwrap();
+        out
+    }
+
+    /// Render the report as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize check report")
+    }
+}
+
+/// Compare the env file at `output_path` with the variables `dir` needs
+///
+/// Variables that only appear commented out in a generated file, such as
+/// well-known ones, are not required, and variables in the file that are
+/// commented out count as listed. Variables matching `keep_unused` are never
+/// stale. Nothing is written.
+pub fn check_directory<P, O>(scanner: &EnvScanner, dir: P, output_path: O) -> Result<CheckReport>
+where
+    P: AsRef<Path>,
+    O: AsRef<Path>,
+{
+    let output_path = output_path.as_ref();
+    let plan = scanner.plan_directory(dir)?;
+    let document = if output_path.exists() {
+        Some(Document::read_as(output_path, scanner.dialect())?)
+    } else {
+        None
+    };
+
+    let mut missing: Vec<String> = plan
+        .variables
+        .iter()
+        .cloned()
+        .chain(
+            plan.sections
+                .iter()
+                .flat_map(|section| &section.entries)
+                .filter(|entry| !entry.commented_out)
+                .map(|entry| entry.name.clone()),
+        )
+        .filter(|name| document.as_ref().is_none_or(|doc| !doc.mentions(name)))
+        .collect();
+    missing.sort();
+    missing.dedup();
+
+    let stale = match &document {
+        Some(document) => stale::find_stale(document, &plan.names(), scanner.keep_unused()),
+        None => Vec::new(),
+    };
+
+    Ok(CheckReport {
+        path: output_path.to_path_buf(),
+        exists: document.is_some(),
+        missing,
+        stale,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_directory() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output = temp_dir.path().join(".env.example");
+        fs::write(
+            temp_dir.path().join("main.rs"),
+            r#"fn main() {
+                std::env::var("API_KEY").unwrap();
+                std::env::var("PORT").ok();
+                std::env::var("RUST_BACKTRACE").ok();
+            }"#,
+        )?;
+
+        let config = Config {
+            keep_unused: Some(vec!["LOCAL_*".to_string()]),
+            ..Default::default()
+        };
+        let scanner = EnvScanner::with_config(config)?;
+
+        let report = check_directory(&scanner, temp_dir.path(), &output)?;
+        assert!(!report.exists);
+        assert_eq!(report.missing, vec!["API_KEY", "PORT"]);
+        assert!(report.to_text().contains("does not exist"));
+
+        fs::write(&output, "# Keys\nAPI_KEY=\nOLD_SECRET=\nLOCAL_DEBUG=1\n")?;
+        let report = check_directory(&scanner, temp_dir.path(), &output)?;
+        assert!(!report.is_up_to_date());
+        assert_eq!(report.missing, vec!["PORT"]);
+        assert_eq!(report.stale, vec!["OLD_SECRET"]);
+        assert_eq!(
+            report.to_text(),
+            format!(
+                "{} is out of date.\nMissing (read by the code):\n  + PORT\n\
+                 Stale (no longer read by the code):\n  - OLD_SECRET\n\
+                 Run `autoenv generate` to update it.\n",
+                output.display()
+            )
+        );
+
+        // What generate writes passes, and checking writes nothing
+        let before = fs::read_to_string(&output)?;
+        let config = Config {
+            stale: Some(stale::StaleMode::Prune),
+            keep_unused: Some(vec!["LOCAL_*".to_string()]),
+            ..Default::default()
+        };
+        let generator = EnvScanner::with_config(config)?;
+        assert_eq!(fs::read_to_string(&output)?, before);
+        let plan = generator.plan_directory(temp_dir.path())?;
+        generator.generate_annotated_env_file(&plan.variables, &plan.sections, &output)?;
+        let report = check_directory(&scanner, temp_dir.path(), &output)?;
+        assert!(report.is_up_to_date(), "{}", report.to_text());
+        assert!(report.to_json()?.contains("\"missing\": []"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,593 @@
+//! Environment variables read by dependencies
+//!
+//! Many variables are never read by the project's own code but by the crates it
+//! depends on, such as `RUST_LOG` for `env_logger` or `HTTPS_PROXY` for
+//! `reqwest`. A knowledge base keyed by crate name lists these variables; a
+//! crate's variables are implied when the crate appears in `Cargo.lock` or is
+//! referred to by path in Rust source. Rules can be narrowed with triggers,
+//! e.g. `RUST_LOG` for `tracing-subscriber` only applies when
+//! `EnvFilter::from_default_env` is called.
+
+use crate::modules::{Token, tokenize};
+use crate::{Config, EnvScanner};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// A variable a dependency may read
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KnownVariable {
+    /// Name of the environment variable
+    pub name: String,
+    /// What the variable controls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Variables read by one crate
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DependencyRule {
+    /// Variables the crate reads
+    pub variables: Vec<KnownVariable>,
+    /// Source snippets of which at least one must appear for the variables to be
+    /// implied, e.g. `from_default_env`; empty means the dependency alone suffices
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<String>,
+}
+
+/// A variable implied by a dependency of the project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DependencyVariable {
+    /// Name of the environment variable
+    pub name: String,
+    /// What the variable controls
+    pub description: Option<String>,
+    /// Crate that reads the variable
+    pub dependency: String,
+}
+
+/// Crate name, triggers, and `(variable, description)` pairs
+type BuiltinRule = (
+    &'static str,
+    &'static [&'static str],
+    &'static [(&'static str, &'static str)],
+);
+
+/// Built-in knowledge base
+const BUILTIN_RULES: &[BuiltinRule] = &[
+    (
+        "env_logger",
+        &[],
+        &[
+            ("RUST_LOG", "Log level filter directives"),
+            ("RUST_LOG_STYLE", "Whether log output is colored"),
+        ],
+    ),
+    (
+        "tracing-subscriber",
+        &["from_default_env", "try_from_default_env"],
+        &[("RUST_LOG", "Log level filter directives")],
+    ),
+    (
+        "sqlx",
+        &[
+            "query!",
+            "query_as!",
+            "query_scalar!",
+            "query_file!",
+            "migrate!",
+        ],
+        &[
+            (
+                "DATABASE_URL",
+                "Database checked against by the query macros at compile time",
+            ),
+            (
+                "SQLX_OFFLINE",
+                "Use prepared query data instead of a database",
+            ),
+        ],
+    ),
+    (
+        "reqwest",
+        &[],
+        &[
+            ("HTTP_PROXY", "Proxy for HTTP requests"),
+            ("HTTPS_PROXY", "Proxy for HTTPS requests"),
+            ("ALL_PROXY", "Proxy for all requests"),
+            ("NO_PROXY", "Hosts that bypass the proxy"),
+        ],
+    ),
+    (
+        "aws-config",
+        &[],
+        &[
+            ("AWS_ACCESS_KEY_ID", "AWS access key"),
+            ("AWS_SECRET_ACCESS_KEY", "AWS secret key"),
+            (
+                "AWS_SESSION_TOKEN",
+                "AWS session token for temporary credentials",
+            ),
+            ("AWS_REGION", "AWS region"),
+            ("AWS_PROFILE", "Profile from the shared AWS config files"),
+        ],
+    ),
+    (
+        "openssl-probe",
+        &[],
+        &[
+            ("SSL_CERT_FILE", "CA certificate bundle"),
+            ("SSL_CERT_DIR", "Directory of CA certificates"),
+        ],
+    ),
+    (
+        "sentry",
+        &[],
+        &[
+            ("SENTRY_DSN", "Sentry project DSN"),
+            ("SENTRY_ENVIRONMENT", "Environment reported to Sentry"),
+            ("SENTRY_RELEASE", "Release reported to Sentry"),
+        ],
+    ),
+    (
+        "opentelemetry-otlp",
+        &[],
+        &[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "OTLP collector endpoint"),
+            ("OTEL_SERVICE_NAME", "Service name reported with telemetry"),
+        ],
+    ),
+    (
+        "console-subscriber",
+        &[],
+        &[(
+            "TOKIO_CONSOLE_BIND",
+            "Address the tokio-console server binds to",
+        )],
+    ),
+    (
+        "rocket",
+        &[],
+        &[
+            ("ROCKET_PROFILE", "Configuration profile"),
+            ("ROCKET_ADDRESS", "Address to listen on"),
+            ("ROCKET_PORT", "Port to listen on"),
+        ],
+    ),
+];
+
+/// The built-in knowledge base, keyed by crate name
+pub fn builtin_rules() -> BTreeMap<String, DependencyRule> {
+    BUILTIN_RULES
+        .iter()
+        .map(|(dependency, triggers, variables)| {
+            let rule = DependencyRule {
+                variables: variables
+                    .iter()
+                    .map(|(name, description)| KnownVariable {
+                        name: name.to_string(),
+                        description: Some(description.to_string()),
+                    })
+                    .collect(),
+                triggers: triggers.iter().map(|t| t.to_string()).collect(),
+            };
+            (dependency.to_string(), rule)
+        })
+        .collect()
+}
+
+/// A package entry of `Cargo.lock`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    /// Package name
+    pub name: String,
+    /// Resolved version
+    pub version: String,
+    /// Where the package comes from, e.g. `registry+https://...`; `None` for
+    /// packages of the workspace itself
+    pub source: Option<String>,
+    /// Names of the packages it depends on directly
+    pub dependencies: Vec<String>,
+}
+
+/// All packages in the `Cargo.lock` of `dir` or its closest ancestor
+///
+/// Returns an empty list if there is no lock file.
+pub fn locked_packages<P: AsRef<Path>>(dir: P) -> Result<Vec<LockedPackage>> {
+    let dir = dir.as_ref();
+    let absolute = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let Some(lock_path) = absolute
+        .ancestors()
+        .map(|ancestor| ancestor.join("Cargo.lock"))
+        .find(|path| path.is_file())
+    else {
+        return Ok(Vec::new());
+    };
+
+    let content = fs::read_to_string(&lock_path)
+        .with_context(|| format!("Failed to read lock file: {:?}", lock_path))?;
+    let lock: toml::Value = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse lock file: {:?}", lock_path))?;
+
+    let field = |package: &toml::Value, key: &str| {
+        package
+            .get(key)
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+    };
+    Ok(lock
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|package| {
+            // Entries are `name`, or `name version (source)` when ambiguous
+            let dependencies = package
+                .get("dependencies")
+                .and_then(|dependencies| dependencies.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|dependency| dependency.as_str()?.split_whitespace().next())
+                .map(str::to_string)
+                .collect();
+            Some(LockedPackage {
+                name: field(package, "name")?,
+                version: field(package, "version").unwrap_or_default(),
+                source: field(package, "source"),
+                dependencies,
+            })
+        })
+        .collect())
+}
+
+/// Names of all packages in the `Cargo.lock` of `dir` or its closest ancestor
+///
+/// Returns an empty set if there is no lock file.
+pub fn locked_crates<P: AsRef<Path>>(dir: P) -> Result<HashSet<String>> {
+    Ok(locked_packages(dir)?
+        .into_iter()
+        .map(|package| package.name)
+        .collect())
+}
+
+/// Packages in the `Cargo.lock` of `dir` or its closest ancestor that `package`
+/// depends on, directly or indirectly
+///
+/// Returns an empty set if there is no lock file or it doesn't list `package`.
+pub fn package_dependencies<P: AsRef<Path>>(dir: P, package: &str) -> Result<HashSet<String>> {
+    let packages = locked_packages(dir)?;
+    let mut reachable = HashSet::new();
+    let mut pending = vec![package];
+    while let Some(name) = pending.pop() {
+        for locked in packages.iter().filter(|locked| locked.name == name) {
+            for dependency in &locked.dependencies {
+                if reachable.insert(dependency.clone()) {
+                    pending.push(dependency);
+                }
+            }
+        }
+    }
+    Ok(reachable)
+}
+
+/// Crates from `Cargo.lock` whose variables may apply to the code of
+/// `package`, or to the whole project in `dir` for `None`
+///
+/// Empty when dependency variables are disabled, without reading the lock file.
+pub(crate) fn usable_crates(
+    scanner: &EnvScanner,
+    dir: &Path,
+    package: Option<&str>,
+) -> Result<HashSet<String>> {
+    if !scanner.config.dependency_variables.unwrap_or(true) {
+        return Ok(HashSet::new());
+    }
+    match package {
+        Some(package) => package_dependencies(dir, package),
+        None => locked_crates(dir),
+    }
+}
+
+/// Crates and rule triggers that source code refers to
+///
+/// Found per file while scanning, so the scan cache keeps them along with the
+/// variable lookups and planning never reads a file again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SourceMentions {
+    /// Crates named by the first segment of a path, e.g. `reqwest` in
+    /// `reqwest::Client::new()`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub crates: BTreeSet<String>,
+    /// Triggers of dependency rules that appear in the code
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub triggers: BTreeSet<String>,
+}
+
+impl SourceMentions {
+    /// Find crate paths and any of `triggers` in Rust source
+    ///
+    /// Comments and string literals are skipped, and triggers only match whole
+    /// tokens, so `query!` doesn't match `my_query!`.
+    pub fn find(source: &str, triggers: &[String]) -> Self {
+        let tokens = tokenize(source);
+
+        let mut crates = BTreeSet::new();
+        for (index, window) in tokens.windows(3).enumerate() {
+            let [Token::Ident(name), Token::Punct(':'), Token::Punct(':')] = window else {
+                continue;
+            };
+            // In `a::name::` the name is a module of `a`; `::name::` starts a path
+            let nested = index >= 3
+                && matches!(
+                    &tokens[index - 2..index],
+                    [Token::Punct(':'), Token::Punct(':')]
+                )
+                && matches!(tokens[index - 3], Token::Ident(_) | Token::Punct('>'));
+            // Types such as `Vec::new` and the path keywords are no crates
+            let is_crate = name.starts_with(|c: char| c.is_lowercase() || c == '_')
+                && !matches!(name.as_str(), "crate" | "self" | "super");
+            if is_crate && !nested {
+                crates.insert(name.clone());
+            }
+        }
+
+        let triggers = triggers
+            .iter()
+            .filter(|trigger| {
+                let needle = tokenize(trigger);
+                !needle.is_empty()
+                    && tokens
+                        .windows(needle.len())
+                        .any(|window| window == needle.as_slice())
+            })
+            .cloned()
+            .collect();
+
+        Self { crates, triggers }
+    }
+}
+
+impl<'a> FromIterator<&'a SourceMentions> for SourceMentions {
+    fn from_iter<I: IntoIterator<Item = &'a SourceMentions>>(iter: I) -> Self {
+        let mut combined = Self::default();
+        for mentions in iter {
+            combined.crates.extend(mentions.crates.iter().cloned());
+            combined.triggers.extend(mentions.triggers.iter().cloned());
+        }
+        combined
+    }
+}
+
+/// Work out which known dependency variables apply to a project
+///
+/// `locked` are the crates from `Cargo.lock`; `mentions` tell which crates the
+/// source refers to by path (`reqwest::`) and which rule triggers it contains.
+pub fn implied_by(
+    rules: &BTreeMap<String, DependencyRule>,
+    locked: &HashSet<String>,
+    mentions: &SourceMentions,
+) -> Vec<DependencyVariable> {
+    let mut implied: BTreeMap<String, DependencyVariable> = BTreeMap::new();
+    for (dependency, rule) in rules {
+        let used =
+            locked.contains(dependency) || mentions.crates.contains(&dependency.replace('-', "_"));
+        let triggered = rule.triggers.is_empty()
+            || rule
+                .triggers
+                .iter()
+                .any(|trigger| mentions.triggers.contains(trigger));
+        if !used || !triggered {
+            continue;
+        }
+
+        for variable in &rule.variables {
+            implied
+                .entry(variable.name.clone())
+                .or_insert_with(|| DependencyVariable {
+                    name: variable.name.clone(),
+                    description: variable.description.clone(),
+                    dependency: dependency.clone(),
+                });
+        }
+    }
+
+    implied.into_values().collect()
+}
+
+/// The built-in knowledge base extended with the rules from `config`
+pub(crate) fn configured_rules(config: &Config) -> BTreeMap<String, DependencyRule> {
+    let mut rules = builtin_rules();
+    if let Some(custom) = &config.dependency_rules {
+        rules.extend(custom.clone());
+    }
+    rules
+}
+
+/// Every trigger of the rules that apply with `config`, sorted; none when
+/// dependency variables are disabled
+pub(crate) fn configured_triggers(config: &Config) -> Vec<String> {
+    if !config.dependency_variables.unwrap_or(true) {
+        return Vec::new();
+    }
+    let triggers: BTreeSet<String> = configured_rules(config)
+        .into_values()
+        .flat_map(|rule| rule.triggers)
+        .collect();
+    triggers.into_iter().collect()
+}
+
+/// Variables implied by the dependencies of some code
+///
+/// `locked` are the crates from `Cargo.lock` the code may use, see
+/// [`package_dependencies`], and `mentions` are those of its scanned files.
+/// Uses the built-in knowledge base extended with the rules from the
+/// scanner's configuration. Ignored variables are left out, and nothing is
+/// returned when dependency variables are disabled.
+pub fn implied_variables(
+    scanner: &EnvScanner,
+    locked: &HashSet<String>,
+    mentions: &SourceMentions,
+) -> Vec<DependencyVariable> {
+    if !scanner.config.dependency_variables.unwrap_or(true) {
+        return Vec::new();
+    }
+
+    implied_by(&configured_rules(&scanner.config), locked, mentions)
+        .into_iter()
+        .filter(|variable| !scanner.is_ignored(&variable.name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    fn names(variables: &[DependencyVariable]) -> Vec<&str> {
+        variables.iter().map(|v| v.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_implied_by_lock_file_and_source() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"app\"\nversion = \"0.1.0\"\n\
+             dependencies = [\n \"tracing-subscriber 0.3.0 (registry+https://example.com)\",\n]\n\n\
+             [[package]]\nname = \"env_logger\"\nversion = \"0.11.0\"\n\n\
+             [[package]]\nname = \"tracing-subscriber\"\nversion = \"0.3.0\"\n\
+             dependencies = [\"sharded-slab\"]\n",
+        )?;
+
+        let locked = locked_crates(temp_dir.path())?;
+        assert!(locked.contains("env_logger"));
+        let mut used: Vec<String> = package_dependencies(temp_dir.path(), "app")?
+            .into_iter()
+            .collect();
+        used.sort();
+        assert_eq!(used, vec!["sharded-slab", "tracing-subscriber"]);
+        assert!(package_dependencies(temp_dir.path(), "unknown")?.is_empty());
+
+        let mentions = SourceMentions::find("fn main() { let c = reqwest::Client::new(); }\n", &[]);
+        let implied = implied_by(&builtin_rules(), &locked, &mentions);
+        assert_eq!(
+            names(&implied),
+            vec![
+                "ALL_PROXY",
+                "HTTPS_PROXY",
+                "HTTP_PROXY",
+                "NO_PROXY",
+                "RUST_LOG",
+                "RUST_LOG_STYLE"
+            ]
+        );
+        let rust_log = implied.iter().find(|v| v.name == "RUST_LOG").unwrap();
+        assert_eq!(rust_log.dependency, "env_logger");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mentions_skip_comments_strings_and_longer_names() {
+        let triggers = vec!["query!".to_string(), "from_default_env".to_string()];
+        let mentions = SourceMentions::find(
+            r#"// sqlx::query!("SELECT 1")
+            use my_sqlx::query;
+            fn f(pool: sentry::Hub) -> Vec<u8> {
+                let s = "reqwest::get from_default_env";
+                my_query!(x);
+                ::rocket::build();
+                crate::db::sqlx::connect();
+                Vec::new()
+            }"#,
+            &triggers,
+        );
+        let crates: Vec<&str> = mentions.crates.iter().map(String::as_str).collect();
+        assert_eq!(crates, vec!["my_sqlx", "rocket", "sentry"]);
+        assert!(mentions.triggers.is_empty());
+
+        let mentions = SourceMentions::find("sqlx::query!(\"SELECT 1\")", &triggers);
+        assert!(mentions.crates.contains("sqlx"));
+        assert!(mentions.triggers.contains("query!"));
+    }
+
+    #[test]
+    fn test_triggers_and_custom_rules() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join("main.rs"),
+            "fn main() { tracing_subscriber::fmt().with_env_filter(EnvFilter::from_default_env()); }\n",
+        )?;
+
+        let config = crate::Config {
+            cache: Some(false),
+            ignore: Some(vec!["MY_IGNORED".to_string()]),
+            dependency_rules: Some(BTreeMap::from([(
+                "tracing-subscriber".to_string(),
+                DependencyRule {
+                    variables: vec![
+                        KnownVariable {
+                            name: "MY_FILTER".to_string(),
+                            description: None,
+                        },
+                        KnownVariable {
+                            name: "MY_IGNORED".to_string(),
+                            description: None,
+                        },
+                    ],
+                    triggers: vec!["from_default_env".to_string()],
+                },
+            )])),
+            ..Default::default()
+        };
+        let scanner = EnvScanner::with_config(config)?;
+        let (_, implied) = scanner.scan_directory_with_dependencies(temp_dir.path())?;
+        assert_eq!(names(&implied), vec!["MY_FILTER"]);
+
+        // Without the trigger the dependency alone does not imply anything
+        fs::write(
+            temp_dir.path().join("main.rs"),
+            "fn main() { tracing_subscriber::fmt().init(); }\n",
+        )?;
+        let (_, implied) = scanner.scan_directory_with_dependencies(temp_dir.path())?;
+        assert!(implied.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_mentions_are_not_read_again() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"app\"\n",
+        )?;
+        let file = temp_dir.path().join("main.rs");
+        fs::write(&file, "fn main() { reqwest::get(url); }\n")?;
+        // An old mtime keeps the cache entry out of the racy window
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(&file)?
+            .set_modified(old)?;
+
+        let scanner = EnvScanner::new()?;
+        let plan = scanner.plan_directory(temp_dir.path())?;
+        assert!(plan.names().contains("HTTPS_PROXY"));
+
+        // Same size and mtime: the cached result is used without reading the file,
+        // so the edit goes unnoticed
+        fs::write(&file, "fn main() { nothing::xyz(url); }\n")?;
+        fs::File::options()
+            .write(true)
+            .open(&file)?
+            .set_modified(old)?;
+        let plan = scanner.plan_directory(temp_dir.path())?;
+        assert!(plan.names().contains("HTTPS_PROXY"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,886 @@
+//! Detection in deployment files and other languages
+//!
+//! The variables a service needs are not only read by its Rust code: shell
+//! scripts such as `entrypoint.sh` expand `$VAR`, Dockerfiles declare `ENV` and
+//! `ARG`, and compose files list `environment:` entries and interpolate
+//! `${VAR:-default}`. Repositories may also hold Python, JavaScript/TypeScript
+//! or Go services sharing the same `.env`. Each file type has a detector here;
+//! the scanner picks the detector from the file name.
+
+use crate::Detection;
+use crate::workspace::glob_match;
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Bump whenever a detector changes what it finds, to invalidate cached results
+pub(crate) const DETECTORS_VERSION: u32 = 1;
+
+/// Variables bash sets itself; expanding them does not read the environment
+const SHELL_BUILTINS: &[&str] = &[
+    "BASH",
+    "BASHPID",
+    "BASH_REMATCH",
+    "BASH_SOURCE",
+    "BASH_VERSION",
+    "EUID",
+    "FUNCNAME",
+    "GROUPS",
+    "HOSTTYPE",
+    "IFS",
+    "LINENO",
+    "OPTARG",
+    "OPTERR",
+    "OPTIND",
+    "OSTYPE",
+    "PIPESTATUS",
+    "PPID",
+    "PS1",
+    "PS2",
+    "PS4",
+    "RANDOM",
+    "REPLY",
+    "SECONDS",
+    "SHLVL",
+    "UID",
+];
+
+/// `os.environ["NAME"]`, `environ['NAME']`
+static PYTHON_SUBSCRIPT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\benviron\s*\[\s*(?:"([A-Za-z_][A-Za-z0-9_]*)"|'([A-Za-z_][A-Za-z0-9_]*)')\s*\]"#)
+        .unwrap()
+});
+
+/// `os.getenv("NAME", "default")`, `os.environ.get('NAME')`
+static PYTHON_GET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"\b(?:getenv|environ\s*\.\s*get)\s*\(\s*(?:"([A-Za-z_][A-Za-z0-9_]*)"|'([A-Za-z_][A-Za-z0-9_]*)')\s*(?:,\s*(?:"([^"]*)"|'([^']*)'))?"#,
+    )
+    .unwrap()
+});
+
+/// `process.env.NAME`, `process.env["NAME"]`, `import.meta.env.NAME`, optionally
+/// followed by `|| "default"` or `?? "default"`
+static JS_ACCESS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"\b(?:process|import\.meta)\.env(?:\.([A-Za-z_$][A-Za-z0-9_$]*)|\[\s*(?:"([^"]+)"|'([^']+)'|`([^`$]+)`)\s*\])(?:\s*(?:\|\||\?\?)\s*(?:"([^"]*)"|'([^']*)'|`([^`$]*)`|(\d+(?:\.\d+)?)))?"#,
+    )
+    .unwrap()
+});
+
+/// `const { NAME, OTHER = "default" } = process.env`
+static JS_DESTRUCTURE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\{([^{}]*)\}\s*=\s*(?:process|import\.meta)\.env\b"#).unwrap());
+
+/// `os.Getenv("NAME")`, `os.LookupEnv("NAME")`
+static GO_LOOKUP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\bos\.(Getenv|LookupEnv)\s*\(\s*(?:"([^"]+)"|`([^`]+)`)\s*\)"#).unwrap()
+});
+
+/// Type of a scanned file, which decides the detector used for it
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Deserialize,
+    Serialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileKind {
+    /// Rust source (`.rs`)
+    #[default]
+    Rust,
+    /// Shell script (`.sh`, `.bash`)
+    Shell,
+    /// Dockerfile or Containerfile
+    Dockerfile,
+    /// Docker Compose file (`docker-compose.yml`, `compose.yaml`, ...)
+    Compose,
+    /// Python source (`.py`)
+    Python,
+    /// JavaScript or TypeScript source (`.js`, `.mjs`, `.cjs`, `.jsx`, `.ts`, `.tsx`, ...)
+    #[serde(rename = "javascript")]
+    #[value(name = "javascript")]
+    JavaScript,
+    /// Go source (`.go`)
+    Go,
+}
+
+impl FileKind {
+    /// Recognize the type of a file from its name
+    pub fn of(path: &Path) -> Option<FileKind> {
+        let name = path.file_name()?.to_str()?;
+        let extension = path.extension().and_then(|ext| ext.to_str());
+
+        if extension == Some("rs") {
+            return Some(FileKind::Rust);
+        }
+        match extension {
+            Some("sh" | "bash") => return Some(FileKind::Shell),
+            Some("py") => return Some(FileKind::Python),
+            Some("js" | "mjs" | "cjs" | "jsx" | "ts" | "mts" | "cts" | "tsx") => {
+                // Type declarations hold no code
+                if !name.ends_with(".d.ts") {
+                    return Some(FileKind::JavaScript);
+                }
+                return None;
+            }
+            Some("go") => return Some(FileKind::Go),
+            _ => {}
+        }
+        if name == "Dockerfile"
+            || name == "Containerfile"
+            || name.starts_with("Dockerfile.")
+            || name.to_ascii_lowercase().ends_with(".dockerfile")
+        {
+            return Some(FileKind::Dockerfile);
+        }
+        if matches!(extension, Some("yml" | "yaml"))
+            && (name.starts_with("docker-compose") || name.starts_with("compose"))
+        {
+            return Some(FileKind::Compose);
+        }
+        None
+    }
+
+    /// Whether the file describes how a service is deployed rather than its code
+    pub fn is_deployment(self) -> bool {
+        matches!(
+            self,
+            FileKind::Shell | FileKind::Dockerfile | FileKind::Compose
+        )
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileKind::Rust => "rust",
+            FileKind::Shell => "shell",
+            FileKind::Dockerfile => "dockerfile",
+            FileKind::Compose => "compose",
+            FileKind::Python => "python",
+            FileKind::JavaScript => "javascript",
+            FileKind::Go => "go",
+        })
+    }
+}
+
+/// A detector for files the built-in detectors don't cover
+///
+/// Register one with [`EnvScanner::with_detector`](crate::EnvScanner::with_detector);
+/// directory scans then also list the files matching its patterns and merge its
+/// detections with those of the built-in detectors. Detectors run on several
+/// files in parallel.
+pub trait Detector: Send + Sync {
+    /// Name used in error messages
+    fn name(&self) -> &str;
+
+    /// File name globs with `*` and `?` wildcards, e.g. `*.dsl`
+    fn patterns(&self) -> &[String];
+
+    /// Detect variables in a file, given its path relative to the scan root
+    ///
+    /// An error fails the whole scan rather than leaving the file out, since a
+    /// file written from a partial scan would silently lack its variables.
+    fn detect(&self, path: &Path, content: &str) -> Result<Vec<Detection>>;
+
+    /// Identifies the detector's behavior in the scan cache; change it whenever
+    /// the detections for unchanged files may change (default: the name)
+    fn fingerprint(&self) -> String {
+        self.name().to_string()
+    }
+
+    /// Whether the detector handles a file
+    fn handles(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                self.patterns()
+                    .iter()
+                    .any(|pattern| glob_match(pattern, name))
+            })
+    }
+}
+
+/// Detect variables in a file other than Rust source, which the scanner handles
+pub(crate) fn detect(kind: FileKind, content: &str) -> Vec<Detection> {
+    match kind {
+        FileKind::Rust => Vec::new(),
+        FileKind::Shell => detect_shell(content),
+        FileKind::Dockerfile => detect_dockerfile(content),
+        FileKind::Compose => detect_compose(content),
+        FileKind::Python => detect_python(content),
+        FileKind::JavaScript => detect_javascript(content),
+        FileKind::Go => detect_go(content),
+    }
+}
+
+fn is_name_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_'
+}
+
+fn is_name_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+/// Whether a shell variable name looks like an environment variable; scripts
+/// conventionally use lowercase names for their own variables
+fn is_env_style(name: &str) -> bool {
+    name.chars().any(|ch| ch.is_ascii_uppercase())
+        && !name.chars().any(|ch| ch.is_ascii_lowercase())
+}
+
+/// Length of the variable name at the start of `text`
+fn name_len(text: &str) -> usize {
+    if !text.starts_with(is_name_start) {
+        return 0;
+    }
+    text.find(|ch| !is_name_char(ch)).unwrap_or(text.len())
+}
+
+/// Strip one level of matching single or double quotes
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+/// Find `$NAME` and `${NAME...}` expansions in a line
+///
+/// `${NAME:-default}` and `${NAME-default}` make the variable optional with a
+/// default, `${NAME:?message}` makes it required. `$$` is skipped, as it is an
+/// escaped dollar in compose files and the process ID in shell scripts.
+fn expansions(text: &str, line: usize, detections: &mut Vec<Detection>) {
+    let mut rest = text;
+    while let Some(pos) = rest.find('$') {
+        rest = &rest[pos + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            rest = after;
+            continue;
+        }
+
+        let Some(braced) = rest.strip_prefix('{') else {
+            let len = name_len(rest);
+            if len > 0 {
+                detections.push(Detection {
+                    name: rest[..len].to_string(),
+                    line,
+                    ..Default::default()
+                });
+            }
+            rest = &rest[len..];
+            continue;
+        };
+
+        let len = name_len(braced);
+        let Some(end) = braced.find('}') else {
+            break;
+        };
+        if len == 0 || len > end {
+            rest = &braced[end + 1..];
+            continue;
+        }
+
+        let mut detection = Detection {
+            name: braced[..len].to_string(),
+            line,
+            ..Default::default()
+        };
+        let modifier = &braced[len..end];
+        let operator = modifier.strip_prefix(':').unwrap_or(modifier);
+        if let Some(default) = operator
+            .strip_prefix('-')
+            .or_else(|| operator.strip_prefix('='))
+        {
+            detection.required = Some(false);
+            detection.default = (!default.is_empty()).then(|| unquote(default).to_string());
+        } else if operator.starts_with('?') {
+            detection.required = Some(true);
+        } else if operator.starts_with('+') {
+            detection.required = Some(false);
+        }
+        detections.push(detection);
+        rest = &braced[end + 1..];
+    }
+}
+
+/// Remove a trailing `# comment` and the contents of single-quoted strings,
+/// which the shell does not expand
+fn shell_code(line: &str) -> String {
+    let mut code = String::with_capacity(line.len());
+    let mut single = false;
+    let mut double = false;
+    let mut previous = ' ';
+
+    for ch in line.chars() {
+        match ch {
+            '\'' if !double => single = !single,
+            '"' if !single && previous != '\\' => double = !double,
+            '#' if !single && !double && previous.is_whitespace() => break,
+            _ if single => {}
+            _ => code.push(ch),
+        }
+        previous = ch;
+    }
+    code
+}
+
+/// Names a line of shell assigns: `NAME=...`, `export NAME=...`, `local NAME`,
+/// `read NAME`, `for NAME in`
+fn shell_assignments(code: &str) -> Vec<String> {
+    let mut words = code.split_whitespace();
+    let mut names = Vec::new();
+
+    while let Some(word) = words.next() {
+        match word {
+            "export" | "readonly" | "declare" | "typeset" => continue,
+            "local" | "read" => {
+                names.extend(
+                    words
+                        .by_ref()
+                        .filter(|word| !word.starts_with('-'))
+                        .map(|word| word.split('=').next().unwrap_or(word).to_string()),
+                );
+            }
+            "for" => names.extend(words.next().map(str::to_string)),
+            _ => {
+                let len = name_len(word);
+                if len > 0 && word[len..].starts_with('=') {
+                    names.push(word[..len].to_string());
+                    continue;
+                }
+            }
+        }
+        break;
+    }
+
+    names.retain(|name| name_len(name) == name.len() && !name.is_empty());
+    names
+}
+
+/// Detect the environment variables a shell script reads
+///
+/// Variables the script assigns before using them are its own and are left
+/// out, unless the assignment reads the variable itself, as in
+/// `PORT="${PORT:-8080}"`.
+fn detect_shell(content: &str) -> Vec<Detection> {
+    let mut assigned: Vec<String> = Vec::new();
+    let mut detections = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        let code = shell_code(line);
+
+        let mut found = Vec::new();
+        expansions(&code, index + 1, &mut found);
+        detections.extend(found.into_iter().filter(|detection| {
+            is_env_style(&detection.name)
+                && !SHELL_BUILTINS.contains(&detection.name.as_str())
+                && !assigned.contains(&detection.name)
+        }));
+
+        assigned.extend(shell_assignments(&code));
+    }
+
+    detections
+}
+
+/// Join lines continued with a trailing backslash, keeping the first line number
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if current.is_none() && (trimmed.is_empty() || trimmed.starts_with('#')) {
+            continue;
+        }
+        let (start, mut text) = current.take().unwrap_or((index + 1, String::new()));
+        match trimmed.strip_suffix('\\') {
+            Some(continued) => {
+                text.push_str(continued);
+                text.push(' ');
+                current = Some((start, text));
+            }
+            None => {
+                text.push_str(trimmed);
+                lines.push((start, text));
+            }
+        }
+    }
+    lines.extend(current);
+    lines
+}
+
+/// Split arguments on whitespace, keeping quoted parts together
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+
+    for ch in text.chars() {
+        match (quote, ch) {
+            (Some(q), _) if ch == q => quote = None,
+            (None, '"' | '\'') => quote = Some(ch),
+            (None, _) if ch.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            _ => word.push(ch),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// A variable declared with a value, or without one when `value` is `None`
+fn declared(name: &str, value: Option<&str>, line: usize) -> Option<Detection> {
+    if name_len(name) != name.len() || name.is_empty() {
+        return None;
+    }
+    Some(Detection {
+        name: name.to_string(),
+        line,
+        required: value.map(|_| false),
+        default: value
+            .filter(|value| !value.is_empty() && !value.contains('$'))
+            .map(str::to_string),
+        ..Default::default()
+    })
+}
+
+/// Detect the variables declared by `ENV` and `ARG` instructions of a Dockerfile
+fn detect_dockerfile(content: &str) -> Vec<Detection> {
+    let mut detections = Vec::new();
+
+    for (line, text) in logical_lines(content) {
+        let (instruction, args) = text.split_once(char::is_whitespace).unwrap_or((&text, ""));
+        let args = args.trim();
+
+        if instruction.eq_ignore_ascii_case("ENV") {
+            let words = split_words(args);
+            match words.first() {
+                // Legacy form: `ENV NAME value with spaces`
+                Some(first) if !first.contains('=') => {
+                    let value = args[first.len()..].trim();
+                    detections.extend(declared(first, Some(unquote(value)), line));
+                }
+                _ => detections.extend(words.iter().filter_map(|word| {
+                    let (name, value) = word.split_once('=')?;
+                    declared(name, Some(value), line)
+                })),
+            }
+        } else if instruction.eq_ignore_ascii_case("ARG") {
+            detections.extend(split_words(args).iter().filter_map(
+                |word| match word.split_once('=') {
+                    Some((name, value)) => declared(name, Some(value), line),
+                    None => declared(word, None, line),
+                },
+            ));
+        }
+    }
+
+    detections
+}
+
+/// Indentation width of a line
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Remove a trailing YAML comment
+fn yaml_code(line: &str) -> &str {
+    match line.find(" #") {
+        Some(pos) => &line[..pos],
+        None if line.trim_start().starts_with('#') => "",
+        None => line,
+    }
+}
+
+/// Detect the variables a compose file passes to containers or interpolates
+///
+/// Entries of `environment:` blocks, in list (`- NAME=value`, `- NAME`) or map
+/// (`NAME: value`) form, are reported along with every `${VAR}` interpolation.
+/// An entry without a value is passed through from the environment compose runs in.
+fn detect_compose(content: &str) -> Vec<Detection> {
+    let mut detections = Vec::new();
+    let mut environment: Option<usize> = None;
+
+    for (index, line) in content.lines().enumerate() {
+        let code = yaml_code(line);
+        if code.trim().is_empty() {
+            continue;
+        }
+        let line_number = index + 1;
+        expansions(code, line_number, &mut detections);
+
+        let trimmed = code.trim();
+        if let Some(block) = environment {
+            if indent(code) <= block {
+                environment = None;
+            } else if let Some(entry) = trimmed.strip_prefix("- ") {
+                let entry = unquote(entry);
+                let (name, value) = match entry.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (entry, None),
+                };
+                detections.extend(declared(name.trim(), value, line_number));
+                continue;
+            } else if let Some((name, value)) = trimmed.split_once(':') {
+                let value = unquote(value);
+                let value = (!value.is_empty() && value != "null").then_some(value);
+                detections.extend(declared(unquote(name), value, line_number));
+                continue;
+            }
+        }
+
+        if trimmed == "environment:" {
+            environment = Some(indent(code));
+        }
+    }
+
+    detections
+}
+
+/// Lines of code with their 1-based numbers, leaving out lines that are
+/// entirely a comment starting with one of `markers`
+fn code_lines<'a>(
+    content: &'a str,
+    markers: &'a [&'a str],
+) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let trimmed = line.trim_start();
+            !markers.iter().any(|marker| trimmed.starts_with(marker))
+        })
+        .map(|(index, line)| (index + 1, line))
+}
+
+/// The first participating group among `groups`
+fn first_group(cap: &regex::Captures, groups: &[usize]) -> Option<String> {
+    groups
+        .iter()
+        .find_map(|&group| cap.get(group))
+        .map(|m| m.as_str().to_string())
+}
+
+/// Detect `os.environ[...]`, `os.environ.get(...)` and `os.getenv(...)` in Python
+///
+/// Subscripting raises `KeyError` for unset variables, so it makes the variable
+/// required; the `get` forms cope with it being unset.
+fn detect_python(content: &str) -> Vec<Detection> {
+    let mut detections = Vec::new();
+
+    for (line, code) in code_lines(content, &["#"]) {
+        for cap in PYTHON_SUBSCRIPT.captures_iter(code) {
+            detections.extend(first_group(&cap, &[1, 2]).map(|name| Detection {
+                name,
+                line,
+                required: Some(true),
+                ..Default::default()
+            }));
+        }
+        for cap in PYTHON_GET.captures_iter(code) {
+            detections.extend(first_group(&cap, &[1, 2]).map(|name| Detection {
+                name,
+                line,
+                required: Some(false),
+                default: first_group(&cap, &[3, 4]),
+                ..Default::default()
+            }));
+        }
+    }
+
+    detections
+}
+
+/// Detect `process.env` and `import.meta.env` accesses in JavaScript and TypeScript
+fn detect_javascript(content: &str) -> Vec<Detection> {
+    let mut detections = Vec::new();
+
+    for (line, code) in code_lines(content, &["//", "/*", "*"]) {
+        for cap in JS_ACCESS.captures_iter(code) {
+            let default = first_group(&cap, &[5, 6, 7, 8]);
+            detections.extend(first_group(&cap, &[1, 2, 3, 4]).map(|name| Detection {
+                name,
+                line,
+                required: default.as_ref().map(|_| false),
+                default,
+                ..Default::default()
+            }));
+        }
+
+        for cap in JS_DESTRUCTURE.captures_iter(code) {
+            for binding in cap[1].split(',') {
+                let (binding, default) = match binding.split_once('=') {
+                    Some((binding, default)) => (binding, Some(unquote(default).to_string())),
+                    None => (binding, None),
+                };
+                // `NAME: alias` reads NAME; `...rest` reads nothing in particular
+                let name = binding.split(':').next().unwrap_or(binding).trim();
+                if name.is_empty() || name_len(name) != name.len() {
+                    continue;
+                }
+                detections.push(Detection {
+                    name: name.to_string(),
+                    line,
+                    required: default.as_ref().map(|_| false),
+                    default,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    detections
+}
+
+/// Detect `os.Getenv` and `os.LookupEnv` calls in Go
+///
+/// `LookupEnv` reports whether the variable is set, so the code copes with it
+/// being unset.
+fn detect_go(content: &str) -> Vec<Detection> {
+    let mut detections = Vec::new();
+
+    for (line, code) in code_lines(content, &["//"]) {
+        for cap in GO_LOOKUP.captures_iter(code) {
+            detections.extend(first_group(&cap, &[2, 3]).map(|name| Detection {
+                name,
+                line,
+                required: (&cap[1] == "LookupEnv").then_some(false),
+                ..Default::default()
+            }));
+        }
+    }
+
+    detections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(detections: &[Detection]) -> Vec<(&str, usize, Option<bool>, Option<&str>)> {
+        detections
+            .iter()
+            .map(|d| (d.name.as_str(), d.line, d.required, d.default.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_file_kind_of() {
+        assert_eq!(FileKind::of(Path::new("src/main.rs")), Some(FileKind::Rust));
+        assert_eq!(
+            FileKind::of(Path::new("docker/entrypoint.sh")),
+            Some(FileKind::Shell)
+        );
+        assert_eq!(
+            FileKind::of(Path::new("Dockerfile")),
+            Some(FileKind::Dockerfile)
+        );
+        assert_eq!(
+            FileKind::of(Path::new("Dockerfile.prod")),
+            Some(FileKind::Dockerfile)
+        );
+        assert_eq!(
+            FileKind::of(Path::new("api.Dockerfile")),
+            Some(FileKind::Dockerfile)
+        );
+        assert_eq!(
+            FileKind::of(Path::new("docker-compose.override.yml")),
+            Some(FileKind::Compose)
+        );
+        assert_eq!(
+            FileKind::of(Path::new("compose.yaml")),
+            Some(FileKind::Compose)
+        );
+        assert_eq!(FileKind::of(Path::new(".github/ci.yml")), None);
+        assert_eq!(FileKind::of(Path::new("README.md")), None);
+    }
+
+    #[test]
+    fn test_detect_shell() {
+        let script = r#"#!/bin/sh
+set -eu
+# $COMMENTED is not read
+: "${DATABASE_URL:?DATABASE_URL must be set}"
+PORT="${PORT:-8080}"
+workers=4
+LOG_DIR=/var/log/app
+echo "Starting on $PORT with $workers workers, logging to $LOG_DIR" # $IGNORED
+echo 'literal $NOT_EXPANDED'
+exec app --bind "0.0.0.0:${PORT}" --token "$API_TOKEN" --pid $$ --line $LINENO
+"#;
+        assert_eq!(
+            summary(&detect_shell(script)),
+            vec![
+                ("DATABASE_URL", 4, Some(true), None),
+                ("PORT", 5, Some(false), Some("8080")),
+                ("API_TOKEN", 10, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_dockerfile() {
+        let dockerfile = "FROM rust:1.80\n\
+                          ARG RUST_PROFILE=release\n\
+                          ARG GIT_SHA\n\
+                          # ENV COMMENTED=1\n\
+                          ENV APP_ENV=production \\\n    BIND_ADDR=\"0.0.0.0:8080\"\n\
+                          env LEGACY_NAME legacy value\n\
+                          ENV BUILD_SHA=$GIT_SHA\n\
+                          RUN cargo build --profile $RUST_PROFILE\n";
+        assert_eq!(
+            summary(&detect_dockerfile(dockerfile)),
+            vec![
+                ("RUST_PROFILE", 2, Some(false), Some("release")),
+                ("GIT_SHA", 3, None, None),
+                ("APP_ENV", 5, Some(false), Some("production")),
+                ("BIND_ADDR", 5, Some(false), Some("0.0.0.0:8080")),
+                ("LEGACY_NAME", 7, Some(false), Some("legacy value")),
+                ("BUILD_SHA", 8, Some(false), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_compose() {
+        let compose = r#"services:
+  api:
+    image: "registry.example.com/api:${IMAGE_TAG:-latest}"
+    environment:
+      - DATABASE_URL
+      - "LOG_LEVEL=info"
+      - PRICE=$$5
+    ports:
+      - "${API_PORT:-8080}:8080"
+  worker:
+    environment:
+      QUEUE_URL: ${QUEUE_URL:?set QUEUE_URL}
+      CONCURRENCY: 4 # per container
+      EMPTY:
+"#;
+        assert_eq!(
+            summary(&detect_compose(compose)),
+            vec![
+                ("IMAGE_TAG", 3, Some(false), Some("latest")),
+                ("DATABASE_URL", 5, None, None),
+                ("LOG_LEVEL", 6, Some(false), Some("info")),
+                ("PRICE", 7, Some(false), None),
+                ("API_PORT", 9, Some(false), Some("8080")),
+                ("QUEUE_URL", 12, Some(true), None),
+                ("QUEUE_URL", 12, Some(false), None),
+                ("CONCURRENCY", 13, Some(false), Some("4")),
+                ("EMPTY", 14, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_kind_of_languages() {
+        assert_eq!(
+            FileKind::of(Path::new("worker/tasks.py")),
+            Some(FileKind::Python)
+        );
+        assert_eq!(
+            FileKind::of(Path::new("web/src/App.tsx")),
+            Some(FileKind::JavaScript)
+        );
+        assert_eq!(
+            FileKind::of(Path::new("web/vite.config.mjs")),
+            Some(FileKind::JavaScript)
+        );
+        assert_eq!(FileKind::of(Path::new("web/env.d.ts")), None);
+        assert_eq!(FileKind::of(Path::new("cmd/main.go")), Some(FileKind::Go));
+    }
+
+    #[test]
+    fn test_detect_python() {
+        let source = r#"import os
+from os import environ, getenv
+
+# os.environ["COMMENTED"]
+DATABASE_URL = os.environ["DATABASE_URL"]
+broker = environ.get('BROKER_URL', 'redis://localhost')
+workers = int(getenv("WORKERS", "4"))
+debug = os.getenv("DEBUG") == "1"
+"#;
+        assert_eq!(
+            summary(&detect_python(source)),
+            vec![
+                ("DATABASE_URL", 5, Some(true), None),
+                ("BROKER_URL", 6, Some(false), Some("redis://localhost")),
+                ("WORKERS", 7, Some(false), Some("4")),
+                ("DEBUG", 8, Some(false), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_javascript() {
+        let source = r#"// process.env.COMMENTED
+const apiUrl = process.env.API_URL;
+const port = Number(process.env["PORT"] ?? 3000);
+const mode = process.env.NODE_MODE || "development";
+const key = import.meta.env.VITE_PUBLIC_KEY;
+const { SESSION_SECRET, REGION = "eu-west-1", LOG_FORMAT: format } = process.env;
+"#;
+        assert_eq!(
+            summary(&detect_javascript(source)),
+            vec![
+                ("API_URL", 2, None, None),
+                ("PORT", 3, Some(false), Some("3000")),
+                ("NODE_MODE", 4, Some(false), Some("development")),
+                ("VITE_PUBLIC_KEY", 5, None, None),
+                ("SESSION_SECRET", 6, None, None),
+                ("REGION", 6, Some(false), Some("eu-west-1")),
+                ("LOG_FORMAT", 6, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_go() {
+        let source = "package main\n\n\
+                      // os.Getenv(\"COMMENTED\")\n\
+                      func main() {\n\
+                      \tdsn := os.Getenv(\"DATABASE_DSN\")\n\
+                      \tif token, ok := os.LookupEnv(`API_TOKEN`); ok {\n\
+                      \t}\n\
+                      }\n";
+        assert_eq!(
+            summary(&detect_go(source)),
+            vec![
+                ("DATABASE_DSN", 5, None, None),
+                ("API_TOKEN", 6, Some(false), None),
+            ]
+        );
+    }
+}
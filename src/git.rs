@@ -0,0 +1,587 @@
+//! Scanning files changed relative to a git revision or the index
+//!
+//! All repository access goes through the local `git` executable; no network
+//! operations are ever performed.
+
+use crate::source::Source;
+use crate::{EnvScanner, vendor};
+use anyhow::{Context, Result, bail};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::Mutex;
+
+/// What to compare the current state of the repository against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeBase {
+    /// Working tree compared to a commit, branch or tag
+    Revision(String),
+    /// Staged changes, i.e. the index compared to `HEAD`
+    Index,
+}
+
+impl ChangeBase {
+    /// Object name of the base version of `path`
+    fn base_object(&self, path: &Path) -> String {
+        match self {
+            ChangeBase::Revision(rev) => format!("{}:./{}", rev, path.display()),
+            ChangeBase::Index => format!("HEAD:./{}", path.display()),
+        }
+    }
+}
+
+/// Variables found in changed files and how they differ from the base
+#[derive(Debug, Clone, Default)]
+pub struct ChangeReport {
+    /// Changed files of any scanned kind, relative to the scanned directory
+    pub files: Vec<PathBuf>,
+    /// Variables used by the current version of the changed files
+    pub variables: HashSet<String>,
+    /// Variables the project did not use before the change, sorted by name
+    pub added: Vec<String>,
+    /// Variables the project no longer uses after the change, sorted by name
+    pub removed: Vec<String>,
+}
+
+/// Run a git command in `dir` and return its standard output
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(output.stdout)
+}
+
+/// Check that `rev` names a commit in the repository containing `dir`
+pub fn verify_revision(dir: &Path, rev: &str) -> Result<()> {
+    git(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", rev),
+        ],
+    )
+    .with_context(|| format!("Unknown git revision: {}", rev))?;
+    Ok(())
+}
+
+/// How git treats a file in the working tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The file is in the index, so it is or will be committed
+    Tracked,
+    /// The file matches a `.gitignore` pattern
+    Ignored,
+    /// The file is neither tracked nor ignored and could be committed by accident
+    Untracked,
+}
+
+/// How git treats the file at `path`, `None` outside a repository or without git
+pub fn file_status(path: &Path) -> Option<FileStatus> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name()?.to_str()?;
+    git(dir, &["rev-parse", "--is-inside-work-tree"]).ok()?;
+
+    let status = if git(dir, &["ls-files", "--error-unmatch", "--", name]).is_ok() {
+        FileStatus::Tracked
+    } else if git(dir, &["check-ignore", "--quiet", "--", name]).is_ok() {
+        FileStatus::Ignored
+    } else {
+        FileStatus::Untracked
+    };
+    Some(status)
+}
+
+/// A `git cat-file --batch` process for reading many blobs efficiently
+struct BlobReader {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl BlobReader {
+    fn spawn(dir: &Path) -> Result<Self> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to run git")?;
+        let stdin = child.stdin.take().context("Failed to open git stdin")?;
+        let stdout = child.stdout.take().context("Failed to open git stdout")?;
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    /// Read a blob such as `HEAD:./src/main.rs` or `:./src/main.rs` (the index),
+    /// returning `None` if the object does not exist
+    fn read(&mut self, object: &str) -> Result<Option<Vec<u8>>> {
+        writeln!(self.stdin, "{}", object)?;
+        self.stdin.flush()?;
+
+        let mut header = String::new();
+        self.stdout.read_line(&mut header)?;
+        // "<oid> <type> <size>" for existing objects, "<object> missing" otherwise
+        let mut fields = header.split_whitespace().rev();
+        let Some(size) = fields.next().and_then(|size| size.parse::<usize>().ok()) else {
+            return Ok(None);
+        };
+
+        let mut content = vec![0; size];
+        self.stdout.read_exact(&mut content)?;
+        // Every object is followed by a newline
+        self.stdout.read_exact(&mut [0; 1])?;
+
+        if fields.next() != Some("blob") {
+            return Ok(None);
+        }
+        Ok(Some(content))
+    }
+}
+
+impl Drop for BlobReader {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The tree of a git revision, read straight from the object database
+///
+/// No worktree is needed: `autoenv scan --rev v1.2.0` answers which variables
+/// release 1.2 used without checking it out.
+pub struct GitRevisionSource {
+    dir: PathBuf,
+    rev: String,
+    reader: Mutex<BlobReader>,
+}
+
+impl GitRevisionSource {
+    /// Create a source for the files below `dir` as of revision `rev`
+    pub fn new<P: AsRef<Path>>(dir: P, rev: &str) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        verify_revision(&dir, rev)?;
+        let reader = Mutex::new(BlobReader::spawn(&dir)?);
+        Ok(Self {
+            dir,
+            rev: rev.to_string(),
+            reader,
+        })
+    }
+
+    /// The revision this source reads from
+    pub fn revision(&self) -> &str {
+        &self.rev
+    }
+
+    /// Read the file at `path` as of the revision, `None` if it doesn't exist there
+    fn read_blob(&self, path: &Path) -> Result<Option<Vec<u8>>> {
+        let object = format!("{}:./{}", self.rev, path.display());
+        self.reader
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Reader of {} is unusable", self.rev))?
+            .read(&object)
+    }
+}
+
+impl Source for GitRevisionSource {
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        let output = git(
+            &self.dir,
+            &["ls-tree", "-r", "--name-only", "-z", &self.rev, "--", "."],
+        )?;
+        // A committed vendor directory isn't first-party code; where it is comes
+        // from the Cargo configuration of the revision, not of the working tree
+        let vendor_dir = vendor::relative_vendor_dir(|name| {
+            let bytes = self.read_blob(Path::new(name)).ok()??;
+            String::from_utf8(bytes).ok()
+        });
+        Ok(split_paths(&output)
+            .filter(|path| !path.starts_with(&vendor_dir))
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> Result<String> {
+        let bytes = self
+            .read_blob(path)?
+            .with_context(|| format!("File not found in {}: {:?}", self.rev, path))?;
+        String::from_utf8(bytes)
+            .with_context(|| format!("File is not valid UTF-8: {}:./{}", self.rev, path.display()))
+    }
+}
+
+/// Split NUL-separated git output into paths
+fn split_paths(output: &[u8]) -> impl Iterator<Item = PathBuf> + '_ {
+    output
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| PathBuf::from(String::from_utf8_lossy(name).into_owned()))
+}
+
+/// List the files below `dir` that differ from `base`, relative to `dir`
+///
+/// When comparing the working tree against a revision, untracked files that are
+/// not ignored count as changed too.
+pub fn changed_files(dir: &Path, base: &ChangeBase) -> Result<Vec<PathBuf>> {
+    let mut args = vec!["diff", "--name-only", "--no-renames", "--relative", "-z"];
+    match base {
+        ChangeBase::Revision(rev) => args.push(rev),
+        ChangeBase::Index => args.push("--cached"),
+    }
+    args.push("--");
+
+    let mut files: Vec<PathBuf> = split_paths(&git(dir, &args)?).collect();
+
+    if let ChangeBase::Revision(_) = base {
+        let untracked = git(dir, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+        files.extend(split_paths(&untracked));
+        files.sort();
+        files.dedup();
+    }
+
+    Ok(files)
+}
+
+/// Scan only the files changed relative to `base` and compare against it
+///
+/// Both versions of every changed file the scanner handles are scanned: the current one from
+/// the working tree (or from the index for [`ChangeBase::Index`]) and the base
+/// one from the object database. A variable only counts as added or removed if
+/// no unchanged file in the working tree uses it.
+pub fn scan_changes<P: AsRef<Path>>(
+    scanner: &EnvScanner,
+    dir: P,
+    base: &ChangeBase,
+) -> Result<ChangeReport> {
+    let dir = dir.as_ref();
+    // A repository without commits has nothing to compare staged changes against
+    let has_base = match base {
+        ChangeBase::Revision(rev) => {
+            verify_revision(dir, rev)?;
+            true
+        }
+        ChangeBase::Index => verify_revision(dir, "HEAD").is_ok(),
+    };
+
+    let files: Vec<PathBuf> = changed_files(dir, base)?
+        .into_iter()
+        .filter(|path| scanner.scans(path))
+        .collect();
+
+    // Blobs are read through a single `git cat-file --batch` process, then
+    // scanned in parallel
+    let mut reader = BlobReader::spawn(dir)?;
+    let mut read = |object: String| -> Result<Option<String>> {
+        reader
+            .read(&object)?
+            .map(|bytes| {
+                String::from_utf8(bytes)
+                    .with_context(|| format!("Git object is not valid UTF-8: {}", object))
+            })
+            .transpose()
+    };
+    let contents = files
+        .iter()
+        .map(|path| {
+            let current = match base {
+                ChangeBase::Revision(_) => fs::read_to_string(dir.join(path)).ok(),
+                ChangeBase::Index => read(format!(":./{}", path.display()))?,
+            };
+            let previous = if has_base {
+                read(base.base_object(path))?
+            } else {
+                None
+            };
+            Ok((path, current, previous))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let versions = contents
+        .into_par_iter()
+        .map(|(path, current, previous)| {
+            let detect = |content: Option<String>| {
+                content
+                    .map(|content| scanner.detect_path(path, &content))
+                    .transpose()
+                    .map(|detections| scanner.variable_names(detections.iter().flatten()))
+            };
+            Ok((detect(current)?, detect(previous)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut variables = HashSet::new();
+    let mut previous_variables = HashSet::new();
+    for (current, previous) in versions {
+        variables.extend(current);
+        previous_variables.extend(previous);
+    }
+
+    let mut added: Vec<String> = variables.difference(&previous_variables).cloned().collect();
+    let mut removed: Vec<String> = previous_variables.difference(&variables).cloned().collect();
+
+    // Variables still used by unchanged files were neither introduced nor dropped
+    if !added.is_empty() || !removed.is_empty() {
+        let changed: HashSet<&PathBuf> = files.iter().collect();
+        let source = scanner.fs_source(dir);
+        let unchanged_files = source
+            .files()?
+            .into_iter()
+            .filter(|file| !changed.contains(file))
+            .collect();
+        let scanned = scanner.scan_files(&source, unchanged_files)?;
+        let elsewhere =
+            scanner.variable_names(scanned.iter().flat_map(|(_, scan)| &scan.detections));
+
+        added.retain(|name| !elsewhere.contains(name));
+        removed.retain(|name| !elsewhere.contains(name));
+    }
+
+    added.sort();
+    removed.sort();
+
+    Ok(ChangeReport {
+        files,
+        variables,
+        added,
+        removed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::RequireDetector;
+    use tempfile::TempDir;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .expect("Failed to run git")
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    fn write(dir: &Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn init_repo() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        run_git(root, &["init", "-q"]);
+        write(
+            root,
+            "src/main.rs",
+            r#"fn main() { env::var("KEPT"); env::var("DROPPED"); }"#,
+        );
+        write(root, "src/lib.rs", r#"pub fn f() { env::var("SHARED"); }"#);
+        run_git(root, &["add", "."]);
+        run_git(root, &["commit", "-q", "-m", "initial"]);
+        temp_dir
+    }
+
+    fn no_cache_scanner() -> EnvScanner {
+        let config = crate::Config {
+            cache: Some(false),
+            ..Default::default()
+        };
+        EnvScanner::with_config(config).unwrap()
+    }
+
+    #[test]
+    fn test_scan_changes_since_revision() -> Result<()> {
+        let temp_dir = init_repo();
+        let root = temp_dir.path();
+        write(
+            root,
+            "src/main.rs",
+            r#"fn main() { env::var("KEPT"); env::var("SHARED"); env::var("NEW"); }"#,
+        );
+        write(
+            root,
+            "src/untracked.rs",
+            r#"fn f() { env::var("UNTRACKED"); }"#,
+        );
+
+        let report = scan_changes(
+            &no_cache_scanner(),
+            root,
+            &ChangeBase::Revision("HEAD".to_string()),
+        )?;
+
+        assert_eq!(
+            report.files,
+            vec![
+                PathBuf::from("src/main.rs"),
+                PathBuf::from("src/untracked.rs")
+            ]
+        );
+        assert_eq!(report.variables.len(), 4);
+        // SHARED was already used by the unchanged lib.rs
+        assert_eq!(
+            report.added,
+            vec!["NEW".to_string(), "UNTRACKED".to_string()]
+        );
+        assert_eq!(report.removed, vec!["DROPPED".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_staged_changes_reads_index() -> Result<()> {
+        let temp_dir = init_repo();
+        let root = temp_dir.path();
+        write(root, "src/new.rs", r#"fn f() { env::var("STAGED"); }"#);
+        run_git(root, &["add", "src/new.rs"]);
+        // Unstaged edits must not be reported
+        write(root, "src/new.rs", r#"fn f() { env::var("UNSTAGED"); }"#);
+
+        let report = scan_changes(&no_cache_scanner(), root, &ChangeBase::Index)?;
+
+        assert_eq!(report.files, vec![PathBuf::from("src/new.rs")]);
+        assert_eq!(report.added, vec!["STAGED".to_string()]);
+        assert!(report.removed.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_revision_without_checkout() -> Result<()> {
+        let temp_dir = init_repo();
+        let root = temp_dir.path();
+        run_git(root, &["tag", "v1.0.0"]);
+        write(root, "src/main.rs", r#"fn main() { env::var("LATER"); }"#);
+        run_git(root, &["commit", "-q", "-am", "later"]);
+
+        let source = GitRevisionSource::new(root, "v1.0.0")?;
+        assert_eq!(
+            source.files()?,
+            vec![PathBuf::from("src/lib.rs"), PathBuf::from("src/main.rs")]
+        );
+
+        let variables = no_cache_scanner().scan_source(&source)?;
+        let mut variables: Vec<_> = variables.into_iter().collect();
+        variables.sort();
+        assert_eq!(variables, vec!["DROPPED", "KEPT", "SHARED"]);
+
+        assert!(source.read(Path::new("src/missing.rs")).is_err());
+        assert!(GitRevisionSource::new(root, "v9.9.9").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_revision_with_custom_detector() -> Result<()> {
+        let temp_dir = init_repo();
+        let root = temp_dir.path();
+        write(root, "conf/app.dsl", "require API_TOKEN\n");
+        write(root, "conf/notes.txt", "require NOT_SCANNED\n");
+        write(
+            root,
+            "vendor/dep/src/lib.rs",
+            r#"pub fn f() { env::var("VENDORED"); }"#,
+        );
+        run_git(root, &["add", "."]);
+        run_git(root, &["commit", "-q", "-m", "config"]);
+
+        let scanner = no_cache_scanner().with_detector(RequireDetector(vec!["*.dsl".to_string()]));
+        let variables = scanner.scan_source_detailed(&GitRevisionSource::new(root, "HEAD")?)?;
+        assert_eq!(
+            variables.keys().collect::<Vec<_>>(),
+            vec!["API_TOKEN", "DROPPED", "KEPT", "SHARED"]
+        );
+        assert_eq!(
+            variables["API_TOKEN"].locations[0].file,
+            PathBuf::from("conf/app.dsl")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_revision_vendor_dir_comes_from_revision() -> Result<()> {
+        let temp_dir = init_repo();
+        let root = temp_dir.path();
+        write(
+            root,
+            ".cargo/config.toml",
+            "[source.vendored-sources]\ndirectory = \"third_party\"\n",
+        );
+        write(
+            root,
+            "third_party/dep/src/lib.rs",
+            r#"pub fn f() { env::var("VENDORED"); }"#,
+        );
+        run_git(root, &["add", "."]);
+        run_git(root, &["commit", "-q", "-m", "vendor"]);
+
+        // The working tree no longer vendors, but the revision still does
+        fs::remove_file(root.join(".cargo/config.toml"))?;
+
+        let files = GitRevisionSource::new(root, "HEAD")?.files()?;
+        assert!(files.contains(&PathBuf::from("src/main.rs")));
+        assert!(!files.iter().any(|path| path.starts_with("third_party")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_revision() {
+        let temp_dir = init_repo();
+        let result = scan_changes(
+            &no_cache_scanner(),
+            temp_dir.path(),
+            &ChangeBase::Revision("does-not-exist".to_string()),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_file_status() {
+        let temp_dir = init_repo();
+        let root = temp_dir.path();
+        assert_eq!(
+            file_status(&root.join("src/main.rs")),
+            Some(FileStatus::Tracked)
+        );
+
+        write(root, ".env", "API_KEY=secret\n");
+        assert_eq!(file_status(&root.join(".env")), Some(FileStatus::Untracked));
+        write(root, ".gitignore", "/.env\n");
+        assert_eq!(file_status(&root.join(".env")), Some(FileStatus::Ignored));
+
+        let outside = TempDir::new().unwrap();
+        assert_eq!(file_status(&outside.path().join(".env")), None);
+    }
+}
@@ -0,0 +1,470 @@
+//! Variable references inside env file values
+//!
+//! Unquoted and double-quoted values may refer to other variables, as
+//! `dotenvy` and shells allow:
+//!
+//! - `$NAME` and `${NAME}` expand to the variable's value
+//! - `${NAME:-default}` uses `default` if `NAME` is unset or empty, and
+//!   `${NAME-default}` only if it is unset; defaults may hold references
+//! - `${NAME:?message}` is an error if `NAME` is unset or empty, and
+//!   `${NAME?message}` only if it is unset
+//!
+//! Single-quoted text and `\$` stay literal, and so does a `$` that doesn't
+//! start a well-formed reference. The parser splits each value into
+//! [`Segment`]s; [`resolve`] expands them.
+
+use crate::dotenv::Entry;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A piece of a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Text taken as it is
+    Text(String),
+    /// A reference to another variable
+    Reference(Reference),
+}
+
+/// A reference to a variable, such as `${PORT:-8080}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// Name of the variable referred to
+    pub name: String,
+    /// What happens when the variable is unset
+    pub modifier: Modifier,
+}
+
+/// How a reference treats an unset variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Modifier {
+    /// `$NAME` or `${NAME}`: expands to nothing
+    Plain,
+    /// `${NAME:-default}`, or `${NAME-default}` if `if_empty` is false
+    Default {
+        value: Vec<Segment>,
+        /// Whether an empty value counts as unset, as with `:-`
+        if_empty: bool,
+    },
+    /// `${NAME:?message}`, or `${NAME?message}` if `if_empty` is false
+    Required {
+        message: String,
+        /// Whether an empty value counts as unset, as with `:?`
+        if_empty: bool,
+    },
+}
+
+/// Split a value into text and references
+///
+/// `active` holds the sorted byte offsets of the `$` signs in `value` that may
+/// start a reference; others were escaped or quoted.
+pub(crate) fn parse_template(value: &str, active: &[usize]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut index = 0;
+    while let Some(ch) = value[index..].chars().next() {
+        if active.binary_search(&index).is_ok()
+            && let Some((reference, end)) = parse_reference(value, index, active)
+        {
+            if !text.is_empty() {
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+            }
+            segments.push(Segment::Reference(reference));
+            index = end;
+            continue;
+        }
+        text.push(ch);
+        index += ch.len_utf8();
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    segments
+}
+
+/// Parse the reference whose `$` is at `start`, returning it and its end offset
+fn parse_reference(value: &str, start: usize, active: &[usize]) -> Option<(Reference, usize)> {
+    let rest = &value[start + 1..];
+    let Some(inner) = rest.strip_prefix('{') else {
+        // `$NAME` takes the longest run of name characters
+        let len = rest
+            .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+            .unwrap_or(rest.len());
+        let name = &rest[..len];
+        if name.is_empty() || name.starts_with(|ch: char| ch.is_ascii_digit()) {
+            return None;
+        }
+        let reference = Reference {
+            name: name.to_string(),
+            modifier: Modifier::Plain,
+        };
+        return Some((reference, start + 1 + len));
+    };
+
+    let name_len = inner
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_' || ch == '.'))
+        .unwrap_or(inner.len());
+    let name = &inner[..name_len];
+    if name.is_empty() {
+        return None;
+    }
+    let after_name = start + 2 + name_len;
+    let (operator, if_empty) = match &value[after_name..] {
+        tail if tail.starts_with('}') => {
+            let reference = Reference {
+                name: name.to_string(),
+                modifier: Modifier::Plain,
+            };
+            return Some((reference, after_name + 1));
+        }
+        tail if tail.starts_with(":-") => ('-', true),
+        tail if tail.starts_with(":?") => ('?', true),
+        tail if tail.starts_with('-') => ('-', false),
+        tail if tail.starts_with('?') => ('?', false),
+        _ => return None,
+    };
+
+    let operand_start = after_name + if if_empty { 2 } else { 1 };
+    let operand_end = matching_brace(value, operand_start, active)?;
+    let operand = &value[operand_start..operand_end];
+    let modifier = if operator == '-' {
+        let shifted: Vec<usize> = active
+            .iter()
+            .filter(|&&offset| offset >= operand_start && offset < operand_end)
+            .map(|offset| offset - operand_start)
+            .collect();
+        Modifier::Default {
+            value: parse_template(operand, &shifted),
+            if_empty,
+        }
+    } else {
+        Modifier::Required {
+            message: operand.to_string(),
+            if_empty,
+        }
+    };
+    let reference = Reference {
+        name: name.to_string(),
+        modifier,
+    };
+    Some((reference, operand_end + 1))
+}
+
+/// Offset of the `}` closing a reference whose operand starts at `from`
+fn matching_brace(value: &str, from: usize, active: &[usize]) -> Option<usize> {
+    let mut depth = 0;
+    let mut index = from;
+    while let Some(ch) = value[index..].chars().next() {
+        match ch {
+            '$' if active.binary_search(&index).is_ok() && value[index + 1..].starts_with('{') => {
+                depth += 1;
+                index += 2;
+                continue;
+            }
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            _ => {}
+        }
+        index += ch.len_utf8();
+    }
+    None
+}
+
+/// Why a value couldn't be fully expanded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Problem {
+    /// A reference without a default to a variable that isn't set
+    Undefined {
+        key: String,
+        line: usize,
+        name: String,
+    },
+    /// A reference to a variable the file only assigns on the same line or
+    /// further down, which `dotenvy` doesn't see; this covers variables referring
+    /// to themselves or to each other
+    Forward {
+        key: String,
+        line: usize,
+        name: String,
+        /// Line of the assignment the reference doesn't see
+        assigned: usize,
+    },
+    /// A `${NAME:?message}` reference to a variable that isn't set
+    Required {
+        key: String,
+        line: usize,
+        name: String,
+        message: String,
+    },
+}
+
+impl Problem {
+    /// Variable whose value holds the reference
+    pub fn key(&self) -> &str {
+        match self {
+            Problem::Undefined { key, .. }
+            | Problem::Forward { key, .. }
+            | Problem::Required { key, .. } => key,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Undefined { key, line, name } => write!(
+                f,
+                "{} (line {}) refers to {}, which is not set",
+                key, line, name
+            ),
+            Problem::Forward {
+                key, line, name, ..
+            } if key == name => {
+                write!(f, "{} (line {}) refers to itself", key, line)
+            }
+            Problem::Forward {
+                key,
+                line,
+                name,
+                assigned,
+            } => write!(
+                f,
+                "{} (line {}) refers to {}, which is only set further down (line {})",
+                key, line, name, assigned
+            ),
+            Problem::Required {
+                key,
+                line,
+                name,
+                message,
+            } if message.is_empty() => {
+                write!(f, "{} (line {}) requires {} to be set", key, line, name)
+            }
+            Problem::Required {
+                key,
+                line,
+                name,
+                message,
+            } => write!(f, "{} (line {}) requires {}: {}", key, line, name, message),
+        }
+    }
+}
+
+/// Values of an env file with all references expanded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolved {
+    /// Expanded value of every variable, from its last assignment
+    pub values: BTreeMap<String, String>,
+    /// References that couldn't be expanded; they expand to nothing
+    pub problems: Vec<Problem>,
+}
+
+/// Expands assignments in file order
+struct Resolver<'a> {
+    entries: &'a [Entry],
+    fallback: &'a dyn Fn(&str) -> Option<String>,
+    /// Expanded values of the assignments so far
+    values: Vec<String>,
+    problems: Vec<Problem>,
+}
+
+impl Resolver<'_> {
+    /// Value of the variable `name` as seen from the assignment at `index`
+    fn lookup(&self, name: &str, index: usize) -> Option<String> {
+        if let Some(value) = (self.fallback)(name) {
+            return Some(value);
+        }
+        let earlier = (0..index).rev().find(|&i| self.entries[i].key == name)?;
+        Some(self.values[earlier].clone())
+    }
+
+    fn expand(&mut self, segments: &[Segment], index: usize) -> String {
+        let mut out = String::new();
+        for segment in segments {
+            let reference = match segment {
+                Segment::Text(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+                Segment::Reference(reference) => reference,
+            };
+            let value = self.lookup(&reference.name, index);
+            let is_unset = |if_empty: bool| match &value {
+                Some(value) => if_empty && value.is_empty(),
+                None => true,
+            };
+            match &reference.modifier {
+                Modifier::Default {
+                    value: default,
+                    if_empty,
+                } if is_unset(*if_empty) => {
+                    let default = self.expand(default, index);
+                    out.push_str(&default);
+                }
+                Modifier::Required { message, if_empty } if is_unset(*if_empty) => {
+                    let entry = &self.entries[index];
+                    self.problems.push(Problem::Required {
+                        key: entry.key.clone(),
+                        line: entry.line,
+                        name: reference.name.clone(),
+                        message: message.clone(),
+                    });
+                }
+                Modifier::Plain if value.is_none() => {
+                    let entry = &self.entries[index];
+                    let assigned = self.entries[index..]
+                        .iter()
+                        .find(|later| later.key == reference.name);
+                    self.problems.push(match assigned {
+                        Some(later) => Problem::Forward {
+                            key: entry.key.clone(),
+                            line: entry.line,
+                            name: reference.name.clone(),
+                            assigned: later.line,
+                        },
+                        None => Problem::Undefined {
+                            key: entry.key.clone(),
+                            line: entry.line,
+                            name: reference.name.clone(),
+                        },
+                    });
+                }
+                _ => out.push_str(value.as_deref().unwrap_or_default()),
+            }
+        }
+        out
+    }
+}
+
+/// Expand the references in `entries`
+///
+/// As `dotenvy` does, a reference sees the value `fallback` gives first, e.g.
+/// from the process environment, and failing that the variable's last
+/// assignment before it. Assignments further down the file are never seen;
+/// referring to one is reported as [`Problem::Forward`].
+pub fn resolve(entries: &[Entry], fallback: &dyn Fn(&str) -> Option<String>) -> Resolved {
+    let mut resolver = Resolver {
+        entries,
+        fallback,
+        values: Vec::with_capacity(entries.len()),
+        problems: Vec::new(),
+    };
+    let mut values = BTreeMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let value = resolver.expand(&entry.parts, index);
+        resolver.values.push(value.clone());
+        values.insert(entry.key.clone(), value);
+    }
+    Resolved {
+        values,
+        problems: resolver.problems,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dotenv::{Dialect, Document};
+
+    fn resolve_str(input: &str, fallback: &dyn Fn(&str) -> Option<String>) -> Resolved {
+        let document = Document::parse(input).unwrap();
+        let entries: Vec<Entry> = document.entries().cloned().collect();
+        resolve(&entries, fallback)
+    }
+
+    #[test]
+    fn test_parse_template() {
+        let document =
+            Document::parse("A=$HOST:${PORT:-80}/x\nB='$HOST'\nC=\"\\$HOST ${X\"\nD=$1${}\n")
+                .unwrap();
+        let entries: Vec<&Entry> = document.entries().collect();
+        assert_eq!(
+            entries[0].parts,
+            vec![
+                Segment::Reference(Reference {
+                    name: "HOST".to_string(),
+                    modifier: Modifier::Plain,
+                }),
+                Segment::Text(":".to_string()),
+                Segment::Reference(Reference {
+                    name: "PORT".to_string(),
+                    modifier: Modifier::Default {
+                        value: vec![Segment::Text("80".to_string())],
+                        if_empty: true,
+                    },
+                }),
+                Segment::Text("/x".to_string()),
+            ]
+        );
+        // Quoted, escaped and malformed references stay literal
+        assert_eq!(entries[1].parts, vec![Segment::Text("$HOST".to_string())]);
+        assert_eq!(
+            entries[2].parts,
+            vec![Segment::Text("$HOST ${X".to_string())]
+        );
+        assert_eq!(entries[3].parts, vec![Segment::Text("$1${}".to_string())]);
+        assert_eq!(entries[0].value, "$HOST:${PORT:-80}/x");
+
+        // docker --env-file doesn't interpolate
+        let document = Document::parse_as("A=$HOST\n", Dialect::Docker).unwrap();
+        let entry = document.entries().next().unwrap();
+        assert_eq!(entry.parts, vec![Segment::Text("$HOST".to_string())]);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let input = "BASE_URL=https://example.com\n\
+                     API_URL=${BASE_URL}/api\n\
+                     PORT=${PORT:-8080}\n\
+                     EMPTY=\n\
+                     A=${EMPTY:-a}${EMPTY-b}${MISSING-${BASE_URL}}\n\
+                     PATH=${PATH}:/opt/bin\n\
+                     HOST=file\n\
+                     URL=http://$HOST\n\
+                     LATER=$FORWARD\n\
+                     FORWARD=forward\n";
+        let fallback = |name: &str| match name {
+            "PATH" => Some("/usr/bin".to_string()),
+            "HOST" => Some("process".to_string()),
+            _ => None,
+        };
+        let resolved = resolve_str(input, &fallback);
+        // The fallback wins over the file, and later assignments aren't seen
+        assert_eq!(
+            resolved
+                .problems
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["LATER (line 9) refers to FORWARD, which is only set further down (line 10)"]
+        );
+        assert_eq!(resolved.values["API_URL"], "https://example.com/api");
+        assert_eq!(resolved.values["PORT"], "8080");
+        assert_eq!(resolved.values["A"], "ahttps://example.com");
+        assert_eq!(resolved.values["PATH"], "/usr/bin:/opt/bin");
+        assert_eq!(resolved.values["URL"], "http://process");
+        assert_eq!(resolved.values["LATER"], "");
+    }
+
+    #[test]
+    fn test_resolve_problems() {
+        let input = "URL=$HOST/x\nKEY=${SECRET:?set SECRET first}\nA=$B\nB=${A}\nSELF=$SELF\n";
+        let resolved = resolve_str(input, &|_| None);
+        assert_eq!(resolved.values["URL"], "/x");
+        assert_eq!(
+            resolved
+                .problems
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "URL (line 1) refers to HOST, which is not set",
+                "KEY (line 2) requires SECRET: set SECRET first",
+                "A (line 3) refers to B, which is only set further down (line 4)",
+                "SELF (line 5) refers to itself",
+            ]
+        );
+    }
+}
@@ -3,16 +3,51 @@
 //! A fast Rust library for scanning .rs files to detect environment variable usage
 //! and generating .env files with parallel processing and efficient pattern matching.
 
+pub mod backup;
+mod cache;
+pub mod cargo_env;
+pub mod catalogue;
+pub mod changelog;
+pub mod check;
+pub mod dependencies;
+pub mod detectors;
+pub mod dotenv;
+pub mod git;
+pub mod interpolate;
+pub mod modules;
+pub mod plugin;
+pub mod preview;
+pub mod secrets;
+pub mod source;
+pub mod stale;
+mod usage;
+pub mod validate;
+pub mod vendor;
+pub mod watch;
+pub mod workspace;
+
 use aho_corasick::AhoCorasick;
 use anyhow::{Context, Result};
+use backup::BackupMode;
+use cache::{FileStamp, Lookup, ScanCache};
+use cargo_env::ProvidedMode;
+use catalogue::{CatalogueAction, Category};
+use dependencies::{DependencyRule, DependencyVariable, SourceMentions};
+use detectors::{Detector, FileKind};
+use dotenv::{Dialect, Document};
+use modules::ModuleTreeSource;
+use plugin::{PluginConfig, PluginDetector};
+use preview::PlannedWrite;
 use rayon::prelude::*;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::fs::{self, File};
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use source::{FsSource, Source};
+use stale::StaleMode;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Mutex;
+use workspace::{Workspace, WorkspaceOutput};
 
 /// Configuration for the environment generator
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -23,6 +58,51 @@ pub struct Config {
     pub merge_existing: Option<bool>,
     /// List of variable names to ignore
     pub ignore: Option<Vec<String>>,
+    /// Whether to cache per-file scan results between runs (default: true)
+    pub cache: Option<bool>,
+    /// Location of the scan cache, relative to the scanned directory
+    /// (default: "target/autoenv-cache" in Cargo projects, none elsewhere)
+    pub cache_path: Option<String>,
+    /// Layout of the generated file(s) in a multi-crate workspace
+    /// (default: "combined")
+    pub workspace_output: Option<WorkspaceOutput>,
+    /// Only scan files reachable from crate roots through `mod` declarations,
+    /// instead of every `.rs` file on disk (default: false)
+    pub module_tree: Option<bool>,
+    /// Whether to add variables read by dependencies, e.g. `RUST_LOG` for
+    /// `env_logger` (default: true)
+    pub dependency_variables: Option<bool>,
+    /// Additional knowledge about variables read by dependencies, keyed by
+    /// crate name; entries replace the built-in rules for the same crate
+    pub dependency_rules: Option<BTreeMap<String, DependencyRule>>,
+    /// How to treat variables set by `[env]` in `.cargo/config.toml` or by a
+    /// build script's `cargo:rustc-env` (default: "exclude")
+    pub cargo_provided: Option<ProvidedMode>,
+    /// What to do with well-known variables per catalogue category: "exclude",
+    /// "annotate" or "keep" (default: system and cargo excluded, ci and rust annotated)
+    pub catalogue: Option<BTreeMap<Category, CatalogueAction>>,
+    /// Whether to scan shell scripts, Dockerfiles and compose files alongside
+    /// Rust sources (default: true)
+    pub deployment_files: Option<bool>,
+    /// Source languages to scan: "rust", "python", "javascript" (including
+    /// TypeScript) and "go" (default: ["rust"])
+    pub languages: Option<Vec<FileKind>>,
+    /// External detector processes, each handling the files matching its patterns
+    pub plugins: Option<Vec<PluginConfig>>,
+    /// Syntax of the generated file: "dotenvy", "docker" (for `docker run
+    /// --env-file`) or "shell" (default: "dotenvy")
+    pub dialect: Option<Dialect>,
+    /// What to do with variables in the output file the code no longer reads:
+    /// "report", "comment" or "prune" (default: "report")
+    pub stale: Option<StaleMode>,
+    /// Variables never treated as stale; `*` matches any run of characters
+    pub keep_unused: Option<Vec<String>>,
+    /// Types the values of variables must parse as, e.g. `PORT = "u16"`,
+    /// overriding those inferred from `.parse::<T>()` calls
+    pub types: Option<BTreeMap<String, String>>,
+    /// Whether to keep a copy of a generated file before replacing it: "off",
+    /// "single" (`.env.bak`) or "timestamped" (default: "off")
+    pub backup: Option<BackupMode>,
 }
 
 impl Default for Config {
@@ -31,15 +111,273 @@ impl Default for Config {
             output: Some(".env".to_string()),
             merge_existing: Some(true),
             ignore: Some(vec![]),
+            cache: Some(true),
+            cache_path: None,
+            workspace_output: Some(WorkspaceOutput::Combined),
+            module_tree: Some(false),
+            dependency_variables: Some(true),
+            dependency_rules: None,
+            cargo_provided: Some(ProvidedMode::Exclude),
+            catalogue: None,
+            deployment_files: Some(true),
+            languages: Some(vec![FileKind::Rust]),
+            plugins: None,
+            dialect: Some(Dialect::Dotenvy),
+            stale: Some(StaleMode::Report),
+            keep_unused: None,
+            types: None,
+            backup: Some(BackupMode::Off),
         }
     }
 }
 
+/// Default location of the scan cache, relative to the scanned directory, used
+/// when that directory is a Cargo project
+pub const DEFAULT_CACHE_PATH: &str = "target/autoenv-cache";
+
+/// Call patterns that may introduce an environment variable lookup
+const ENV_PATTERNS: &[&str] = &[
+    "std::env::var(",
+    "env::var(",
+    "dotenv::var(",
+    "std::env::var_os(",
+    "env::var_os(",
+    "dotenv::var_os(",
+];
+
+/// How much code before a lookup is considered when inferring its usage
+const USAGE_PREFIX_LEN: usize = 200;
+
+/// A titled group of variables appended to a generated env file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvSection {
+    /// Heading written as a comment above the entries
+    pub title: String,
+    /// Variables in the section, in the order they are written
+    pub entries: Vec<AnnotatedVariable>,
+}
+
+/// A variable written with an explanatory comment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnnotatedVariable {
+    /// Name of the environment variable
+    pub name: String,
+    /// Comment written on the line above the variable
+    pub comment: Option<String>,
+    /// Value shown for a commented-out entry
+    pub value: Option<String>,
+    /// Whether the variable is written as a comment, i.e. need not be set
+    pub commented_out: bool,
+}
+
+/// What goes into the generated env file of a directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvPlan {
+    /// Variables read by the scanned code that need a value
+    pub variables: HashSet<String>,
+    /// Annotated sections following the main list, e.g. variables read by dependencies
+    pub sections: Vec<EnvSection>,
+}
+
+impl EnvPlan {
+    /// Names of all variables in the plan, commented-out entries included
+    pub fn names(&self) -> HashSet<&str> {
+        self.variables
+            .iter()
+            .map(String::as_str)
+            .chain(
+                self.sections
+                    .iter()
+                    .flat_map(|section| &section.entries)
+                    .map(|entry| entry.name.as_str()),
+            )
+            .collect()
+    }
+
+    /// Number of variables that need a value, commented-out entries excluded
+    pub fn len(&self) -> usize {
+        let extra: HashSet<&str> = self
+            .sections
+            .iter()
+            .flat_map(|section| &section.entries)
+            .filter(|entry| !entry.commented_out && !self.variables.contains(&entry.name))
+            .map(|entry| entry.name.as_str())
+            .collect();
+        self.variables.len() + extra.len()
+    }
+
+    /// Whether the file would list no variables at all
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty() && self.sections.iter().all(|s| s.entries.is_empty())
+    }
+}
+
+/// Section listing variables read by dependencies, each noting the dependency
+fn dependency_section(implied: &[DependencyVariable]) -> EnvSection {
+    EnvSection {
+        title: "Read by dependencies".to_string(),
+        entries: implied
+            .iter()
+            .map(|variable| AnnotatedVariable {
+                name: variable.name.clone(),
+                comment: Some(match &variable.description {
+                    Some(description) => format!("{} ({})", description, variable.dependency),
+                    None => format!("({})", variable.dependency),
+                }),
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
+
+/// A single environment variable reference found in a source file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Detection {
+    /// Name of the environment variable
+    pub name: String,
+    /// 1-based line number of the call
+    pub line: usize,
+    /// Whether the code fails without the variable (`.unwrap()`, `.expect(..)`, `?`)
+    /// or copes with it being unset (`.ok()`, `.unwrap_or(..)`); `None` if unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    /// Literal fallback value used when the variable is unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Type the value is parsed into, e.g. `u16` for `.parse::<u16>()`
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "type")]
+    pub var_type: Option<String>,
+    /// Free-form details reported by a detector plugin, e.g. a description or
+    /// the section of the configuration file
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, serde_json::Value>,
+}
+
+impl Detection {
+    /// Fill in usage information from another detection of the same lookup
+    fn absorb(&mut self, other: Detection) {
+        self.required = self.required.or(other.required);
+        self.default = self.default.take().or(other.default);
+        self.var_type = self.var_type.take().or(other.var_type);
+        for (key, value) in other.metadata {
+            self.metadata.entry(key).or_insert(value);
+        }
+    }
+
+    /// Sort detections by line and merge those of the same variable on one line
+    fn merge_duplicates(detections: &mut Vec<Detection>) {
+        detections.sort_by(|a, b| a.line.cmp(&b.line).then_with(|| a.name.cmp(&b.name)));
+        detections.dedup_by(|later, earlier| {
+            let duplicate = later.line == earlier.line && later.name == earlier.name;
+            if duplicate {
+                earlier.absorb(std::mem::take(later));
+            }
+            duplicate
+        });
+    }
+}
+
+/// What scanning a single file found
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct FileScan {
+    /// Environment variable lookups, sorted by line
+    pub(crate) detections: Vec<Detection>,
+    /// Crates and dependency rule triggers the file refers to; only collected
+    /// for Rust sources when dependency variables are enabled
+    #[serde(default)]
+    pub(crate) mentions: SourceMentions,
+}
+
+/// Where a variable is used, and what the code reveals about it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VariableInfo {
+    /// Name of the environment variable
+    pub name: String,
+    /// `Some(true)` if any use requires the variable, `Some(false)` if all known
+    /// uses cope with it being unset, `None` if nothing could be inferred
+    pub required: Option<bool>,
+    /// Literal fallback value, taken from the first use that has one
+    pub default: Option<String>,
+    /// Type the value is parsed into, taken from the first use that has one
+    pub var_type: Option<String>,
+    /// Details reported by detector plugins; each key is taken from the first
+    /// use that has it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, serde_json::Value>,
+    /// Every place the variable is read, sorted by file and line
+    pub locations: Vec<Location>,
+}
+
+/// A position in a scanned file
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct Location {
+    /// Path of the file, relative to the scanned directory
+    pub file: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+    /// Type of the file, which decides how the variable was detected; `None`
+    /// for files only handled by added detectors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<FileKind>,
+}
+
+impl VariableInfo {
+    /// Aggregate per-file detections into one entry per variable
+    pub fn collect<'a, I>(detections: I) -> BTreeMap<String, VariableInfo>
+    where
+        I: IntoIterator<Item = (&'a Path, &'a Detection)>,
+    {
+        let mut sorted: Vec<_> = detections.into_iter().collect();
+        sorted.sort_by(|a, b| a.0.cmp(b.0).then_with(|| a.1.line.cmp(&b.1.line)));
+
+        let mut variables = BTreeMap::new();
+        for (file, detection) in sorted {
+            let info = variables
+                .entry(detection.name.clone())
+                .or_insert_with(|| VariableInfo {
+                    name: detection.name.clone(),
+                    ..Default::default()
+                });
+
+            info.required = match (info.required, detection.required) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (None, None) => None,
+            };
+            if info.default.is_none() {
+                info.default = detection.default.clone();
+            }
+            if info.var_type.is_none() {
+                info.var_type = detection.var_type.clone();
+            }
+            for (key, value) in &detection.metadata {
+                info.metadata
+                    .entry(key.clone())
+                    .or_insert_with(|| value.clone());
+            }
+            info.locations.push(Location {
+                file: file.to_path_buf(),
+                line: detection.line,
+                kind: FileKind::of(file),
+            });
+        }
+
+        variables
+    }
+}
+
 /// Environment variable scanner with efficient pattern matching
 pub struct EnvScanner {
     patterns: AhoCorasick,
     extract_regex: Regex,
     config: Config,
+    detectors: Vec<Box<dyn Detector>>,
+    /// Dependency rule triggers looked for in Rust sources
+    triggers: Vec<String>,
+    /// Files that would have been written, when in dry-run mode
+    planned: Option<Mutex<Vec<PlannedWrite>>>,
+    /// Files written or found up to date, outside dry-run mode
+    generated: Mutex<Vec<PathBuf>>,
 }
 
 impl EnvScanner {
@@ -51,16 +389,8 @@ impl EnvScanner {
     /// Create a scanner with custom configuration
     pub fn with_config(config: Config) -> Result<Self> {
         // Patterns to search for environment variable calls
-        let patterns = vec![
-            "std::env::var(",
-            "env::var(",
-            "dotenv::var(",
-            "std::env::var_os(",
-            "env::var_os(",
-            "dotenv::var_os(",
-        ];
-
-        let ac = AhoCorasick::new(patterns).context("Failed to create Aho-Corasick automaton")?;
+        let ac =
+            AhoCorasick::new(ENV_PATTERNS).context("Failed to create Aho-Corasick automaton")?;
 
         // Regex to extract string literals from env var calls (more strict)
         let extract_regex = Regex::new(
@@ -68,13 +398,95 @@ impl EnvScanner {
         )
         .context("Failed to compile extraction regex")?;
 
+        let detectors = config
+            .plugins
+            .iter()
+            .flatten()
+            .map(|plugin| Box::new(PluginDetector::new(plugin.clone())) as Box<dyn Detector>)
+            .collect();
+        let triggers = dependencies::configured_triggers(&config);
+
         Ok(Self {
             patterns: ac,
             extract_regex,
             config,
+            detectors,
+            triggers,
+            planned: None,
+            generated: Mutex::new(Vec::new()),
         })
     }
 
+    /// Add a detector for files the built-in detectors don't cover
+    pub fn with_detector<D: Detector + 'static>(mut self, detector: D) -> Self {
+        self.detectors.push(Box::new(detector));
+        self
+    }
+
+    /// Record generated files instead of writing them
+    ///
+    /// Files the scanner would write are collected and can be inspected with
+    /// [`EnvScanner::planned_writes`]; nothing on disk is touched.
+    pub fn dry_run(mut self) -> Self {
+        self.planned = Some(Mutex::new(Vec::new()));
+        self
+    }
+
+    /// Files recorded in dry-run mode, in the order they would have been written
+    pub fn planned_writes(&self) -> Vec<PlannedWrite> {
+        self.planned
+            .as_ref()
+            .and_then(|planned| planned.lock().ok().map(|planned| planned.clone()))
+            .unwrap_or_default()
+    }
+
+    /// Files generated so far, in the order they were written
+    ///
+    /// Files that were already up to date are included; nothing is recorded in
+    /// dry-run mode.
+    pub fn generated_files(&self) -> Vec<PathBuf> {
+        self.generated
+            .lock()
+            .map(|generated| generated.clone())
+            .unwrap_or_default()
+    }
+
+    /// Write a generated file atomically, or record it in dry-run mode
+    ///
+    /// A file that would change is backed up first if the `backup` setting asks.
+    /// A file holding values is made readable by its owner only.
+    fn write_output(&self, path: &Path, content: String) -> Result<()> {
+        let old = match fs::read_to_string(path) {
+            Ok(old) => Some(old),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read file: {:?}", path));
+            }
+        };
+        let Some(planned) = &self.planned else {
+            if let Ok(mut generated) = self.generated.lock() {
+                generated.push(path.to_path_buf());
+            }
+            // Leave an unchanged file alone rather than piling up identical backups
+            if old.as_deref() == Some(content.as_str()) {
+                return Ok(());
+            }
+            backup::back_up(path, self.config.backup.unwrap_or_default())?;
+            let private = secrets::has_values(&content, self.dialect());
+            return backup::write_atomic(path, content.as_bytes(), private)
+                .with_context(|| format!("Failed to create file: {:?}", path));
+        };
+        planned
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Dry-run record is unusable"))?
+            .push(PlannedWrite {
+                path: path.to_path_buf(),
+                old,
+                new: content,
+            });
+        Ok(())
+    }
+
     /// Load configuration from a TOML file
     pub fn load_config<P: AsRef<Path>>(config_path: P) -> Result<Config> {
         let content = fs::read_to_string(config_path).context("Failed to read config file")?;
@@ -82,15 +494,67 @@ impl EnvScanner {
         Ok(config)
     }
 
-    /// Scan a single file for environment variable usage
-    fn scan_file<P: AsRef<Path>>(&self, path: P) -> Result<HashSet<String>> {
-        let content = fs::read_to_string(&path)
-            .with_context(|| format!("Failed to read file: {:?}", path.as_ref()))?;
+    /// Fingerprint of everything that influences per-file detections
+    ///
+    /// Cached scan results are only reused when the fingerprint matches.
+    fn fingerprint(&self) -> u64 {
+        let mut key = String::new();
+        key.push_str(self.extract_regex.as_str());
+        for pattern in ENV_PATTERNS {
+            key.push('\n');
+            key.push_str(pattern);
+        }
+        key.push_str(&format!("\ndetectors-{}", detectors::DETECTORS_VERSION));
+        for detector in &self.detectors {
+            key.push('\n');
+            key.push_str(&detector.fingerprint());
+            key.push_str(&detector.patterns().join(","));
+        }
+        key.push_str(&format!(
+            "\nmentions-{}",
+            self.config.dependency_variables.unwrap_or(true)
+        ));
+        for trigger in &self.triggers {
+            key.push('\n');
+            key.push_str(trigger);
+        }
+        cache::content_hash(key.as_bytes())
+    }
+
+    /// Check whether a variable is on the ignore list
+    fn is_ignored(&self, name: &str) -> bool {
+        self.config
+            .ignore
+            .as_ref()
+            .is_some_and(|ignore_list| ignore_list.iter().any(|ignored| ignored == name))
+    }
+
+    /// Extract variable names from a piece of source text, attributing them to `line`
+    fn collect_matches(&self, text: &str, line: usize, detections: &mut Vec<Detection>) {
+        // Fast pattern search using Aho-Corasick before running the regex
+        if !self.patterns.is_match(text) {
+            return;
+        }
+
+        for cap in self.extract_regex.captures_iter(text) {
+            if let Some(var_name) = cap.get(1) {
+                detections.push(Detection {
+                    name: var_name.as_str().to_string(),
+                    line,
+                    ..Default::default()
+                });
+            }
+        }
+    }
 
-        let mut variables = HashSet::new();
+    /// Detect environment variable usage in source code
+    ///
+    /// The ignore list is not applied here so that detections can be cached
+    /// independently of it.
+    pub(crate) fn detect(&self, content: &str) -> Vec<Detection> {
+        let mut detections = Vec::new();
 
-        // Process the entire file content to handle multiline cases
-        for line in content.lines() {
+        for (index, line) in content.lines().enumerate() {
             let trimmed_line = line.trim();
 
             // Skip comments and empty lines
@@ -98,156 +562,518 @@ impl EnvScanner {
                 continue;
             }
 
-            // Check if this is inside a string literal (basic check)
-            if let Some(comment_pos) = line.find("//") {
-                let before_comment = &line[..comment_pos];
-                // Only process the part before the comment
-                if self.patterns.is_match(before_comment) {
-                    for cap in self.extract_regex.captures_iter(before_comment) {
-                        if let Some(var_name) = cap.get(1) {
-                            let var_name = var_name.as_str().to_string();
-
-                            // Check if variable should be ignored
-                            if let Some(ignore_list) = &self.config.ignore {
-                                if !ignore_list.contains(&var_name) {
-                                    variables.insert(var_name);
-                                }
-                            } else {
-                                variables.insert(var_name);
-                            }
-                        }
-                    }
-                }
-            } else {
-                // Fast pattern search using Aho-Corasick
-                if self.patterns.is_match(&line) {
-                    // Extract variable names using regex
-                    for cap in self.extract_regex.captures_iter(&line) {
-                        if let Some(var_name) = cap.get(1) {
-                            let var_name = var_name.as_str().to_string();
-
-                            // Check if variable should be ignored
-                            if let Some(ignore_list) = &self.config.ignore {
-                                if !ignore_list.contains(&var_name) {
-                                    variables.insert(var_name);
-                                }
-                            } else {
-                                variables.insert(var_name);
-                            }
-                        }
-                    }
-                }
-            }
+            // Only process the part before a trailing comment
+            let code = match line.find("//") {
+                Some(comment_pos) => &line[..comment_pos],
+                None => line,
+            };
+            self.collect_matches(code, index + 1, &mut detections);
         }
 
-        // Handle multiline patterns by normalizing whitespace
-        let normalized_content = content
-            .lines()
-            .map(|line| line.trim())
-            .filter(|line| !line.starts_with("//") && !line.is_empty())
-            .collect::<Vec<_>>()
-            .join(" ");
+        // Handle multiline patterns by normalizing whitespace, remembering where
+        // each source line starts in the normalized content
+        let mut normalized_content = String::with_capacity(content.len());
+        let mut line_starts = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with("//") || line.is_empty() {
+                continue;
+            }
+            if !normalized_content.is_empty() {
+                normalized_content.push(' ');
+            }
+            line_starts.push((normalized_content.len(), index + 1));
+            normalized_content.push_str(line);
+        }
 
         if self.patterns.is_match(&normalized_content) {
             for cap in self.extract_regex.captures_iter(&normalized_content) {
-                if let Some(var_name) = cap.get(1) {
-                    let var_name = var_name.as_str().to_string();
-
-                    // Check if variable should be ignored
-                    if let Some(ignore_list) = &self.config.ignore {
-                        if !ignore_list.contains(&var_name) {
-                            variables.insert(var_name);
-                        }
-                    } else {
-                        variables.insert(var_name);
+                if let (Some(call), Some(var_name)) = (cap.get(0), cap.get(1)) {
+                    let slot = line_starts.partition_point(|&(start, _)| start <= call.start());
+                    let line = line_starts.get(slot.wrapping_sub(1)).map_or(1, |&(_, l)| l);
+
+                    let mut prefix_start = call.start().saturating_sub(USAGE_PREFIX_LEN);
+                    while !normalized_content.is_char_boundary(prefix_start) {
+                        prefix_start += 1;
                     }
+                    let usage = usage::infer(
+                        &normalized_content[prefix_start..call.start()],
+                        &normalized_content[call.end()..],
+                    );
+
+                    detections.push(Detection {
+                        name: var_name.as_str().to_string(),
+                        line,
+                        required: usage.required,
+                        default: usage.default,
+                        var_type: usage.var_type,
+                        ..Default::default()
+                    });
                 }
             }
         }
 
-        Ok(variables)
+        // Both passes find single-line calls; keep one detection per call
+        Detection::merge_duplicates(&mut detections);
+        detections
+    }
+
+    /// Detect environment variable usage in a file, using the built-in detector
+    /// for its type and any added detectors handling it; files no detector
+    /// handles are treated as Rust source
+    pub(crate) fn detect_path(&self, path: &Path, content: &str) -> Result<Vec<Detection>> {
+        let mut handled = false;
+        let mut detections = Vec::new();
+        for detector in self.detectors.iter().filter(|d| d.handles(path)) {
+            handled = true;
+            detections.extend(
+                detector
+                    .detect(path, content)
+                    .with_context(|| format!("Detector {} failed", detector.name()))?,
+            );
+        }
+
+        match FileKind::of(path) {
+            Some(FileKind::Rust) => detections.extend(self.detect(content)),
+            Some(kind) => detections.extend(detectors::detect(kind, content)),
+            None if !handled => detections.extend(self.detect(content)),
+            None => {}
+        }
+        Detection::merge_duplicates(&mut detections);
+        Ok(detections)
+    }
+
+    /// Scan the content of a file: detect variable lookups and, in Rust
+    /// sources, find what dependency rules look for
+    fn scan_content(&self, path: &Path, content: &str) -> Result<FileScan> {
+        let detections = self.detect_path(path, content)?;
+        let mentions = match FileKind::of(path) {
+            Some(FileKind::Rust) if self.config.dependency_variables.unwrap_or(true) => {
+                SourceMentions::find(content, &self.triggers)
+            }
+            _ => SourceMentions::default(),
+        };
+        Ok(FileScan {
+            detections,
+            mentions,
+        })
+    }
+
+    /// Whether a directory or revision scan includes a file, given its path
+    /// relative to the scan root
+    pub(crate) fn scans(&self, path: &Path) -> bool {
+        let skipped = path.parent().is_some_and(|dir| {
+            dir.components().any(|component| match component {
+                Component::Normal(name) => name.to_str().is_some_and(source::is_skipped_dir),
+                _ => false,
+            })
+        });
+        if skipped {
+            return false;
+        }
+        if self.detectors.iter().any(|detector| detector.handles(path)) {
+            return true;
+        }
+
+        match FileKind::of(path) {
+            Some(kind) if kind.is_deployment() => self.config.deployment_files.unwrap_or(true),
+            Some(kind) => match &self.config.languages {
+                Some(languages) => languages.contains(&kind),
+                None => kind == FileKind::Rust,
+            },
+            None => false,
+        }
+    }
+
+    /// File name patterns of the added detectors
+    fn detector_patterns(&self) -> Vec<String> {
+        self.detectors
+            .iter()
+            .flat_map(|detector| detector.patterns().iter().cloned())
+            .collect()
     }
 
-    /// Find all .rs files in a directory recursively
-    fn find_rust_files<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>> {
-        let mut rust_files = Vec::new();
+    /// Source for all scannable files below `dir`, including those handled by
+    /// added detectors and leaving out vendored dependencies
+    pub(crate) fn fs_source(&self, dir: &Path) -> FsSource {
+        FsSource::new(dir)
+            .with_patterns(self.detector_patterns())
+            .with_excluded(vec![vendor::vendor_dir(dir)])
+    }
 
-        fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
+    /// Read a single file and detect environment variable usage in it
+    fn detect_file(&self, path: &Path) -> Result<Vec<Detection>> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Failed to read file: {:?}", path))?;
+        self.detect_path(path, &content)
+    }
 
-                if path.is_dir() {
-                    // Skip target and hidden directories
-                    if let Some(name) = path.file_name() {
-                        if let Some(name_str) = name.to_str() {
-                            if name_str.starts_with('.') || name_str == "target" {
-                                continue;
-                            }
-                        }
-                    }
-                    walk_dir(&path, files)?;
-                } else if path.extension().map_or(false, |ext| ext == "rs") {
-                    files.push(path);
-                }
+    /// Scan a single file for environment variable usage
+    pub fn scan_file<P: AsRef<Path>>(&self, path: P) -> Result<HashSet<String>> {
+        let detections = self.detect_file(path.as_ref())?;
+        Ok(self.variable_names(&detections))
+    }
+
+    /// Location of the scan cache for a scan rooted at `dir`, if caching is enabled
+    ///
+    /// Without a configured location, the cache goes into `target/` of a Cargo
+    /// project; other directories aren't cached rather than getting a `target/`
+    /// they never had.
+    fn cache_path(&self, dir: &Path) -> Option<PathBuf> {
+        if !self.config.cache.unwrap_or(true) {
+            return None;
+        }
+
+        match &self.config.cache_path {
+            Some(cache_path) => Some(dir.join(cache_path)),
+            None if dir.join("Cargo.toml").is_file() || dir.join("target").is_dir() => {
+                Some(dir.join(DEFAULT_CACHE_PATH))
             }
-            Ok(())
+            None => None,
+        }
+    }
+
+    /// Scan a single file, reusing the cached result when the file is unchanged
+    fn scan_file_cached(
+        &self,
+        cache: &ScanCache,
+        root: &Path,
+        relative: &Path,
+    ) -> Result<(FileStamp, u64, FileScan)> {
+        let file = &root.join(relative);
+        let stamp = FileStamp::of(file)?;
+
+        let cached = match cache.lookup(file, stamp) {
+            Lookup::Fresh { hash, scan } => return Ok((stamp, hash, scan)),
+            Lookup::Stale { hash, scan } => Some((hash, scan)),
+            Lookup::Missing => None,
+        };
+
+        let content =
+            fs::read_to_string(file).with_context(|| format!("Failed to read file: {:?}", file))?;
+        let hash = cache::content_hash(content.as_bytes());
+
+        let scan = match cached {
+            Some((cached, scan)) if cached == hash => scan,
+            _ => self.scan_content(relative, &content)?,
+        };
+        Ok((stamp, hash, scan))
+    }
+
+    /// Scan the given files of a source in parallel and return what was found in
+    /// each file
+    ///
+    /// For sources on the local file system, per-file results are cached on disk
+    /// (in `target/autoenv-cache` of a Cargo project by default) unless disabled
+    /// in the configuration, and only files that changed since the previous scan
+    /// are read again.
+    pub(crate) fn scan_files(
+        &self,
+        source: &dyn Source,
+        mut files: Vec<PathBuf>,
+    ) -> Result<Vec<(PathBuf, FileScan)>> {
+        files.retain(|file| self.scans(file));
+        let cached = source
+            .local_root()
+            .and_then(|root| Some((root, self.cache_path(root)?)));
+
+        let Some((root, cache_path)) = cached else {
+            return files
+                .into_par_iter()
+                .map(|file| {
+                    let scan = self.scan_content(&file, &source.read(&file)?)?;
+                    Ok((file, scan))
+                })
+                .collect();
+        };
+
+        let mut cache = ScanCache::load(cache_path, root, self.fingerprint());
+
+        // Parallel processing of files, consulting the cache for each one
+        let results = files
+            .into_par_iter()
+            .map(|file| {
+                let (stamp, hash, scan) = self.scan_file_cached(&cache, root, &file)?;
+                Ok((file, stamp, hash, scan))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let scanned = results
+            .iter()
+            .map(|(file, _, _, scan)| (file.clone(), scan.clone()))
+            .collect();
+
+        cache.update(
+            results
+                .into_iter()
+                .map(|(file, stamp, hash, scan)| (root.join(file), stamp, hash, scan))
+                .collect(),
+        );
+        // The cache is only an optimization; failing to persist it must not fail the scan
+        let _ = cache.save();
+
+        Ok(scanned)
+    }
+
+    /// Collect the names of non-ignored variables from a set of detections
+    pub(crate) fn variable_names<'a, I>(&self, detections: I) -> HashSet<String>
+    where
+        I: IntoIterator<Item = &'a Detection>,
+    {
+        detections
+            .into_iter()
+            .filter(|detection| !self.is_ignored(&detection.name))
+            .map(|detection| detection.name.clone())
+            .collect()
+    }
+
+    /// Scan all files of a source in parallel and collect environment variables
+    pub fn scan_source(&self, source: &dyn Source) -> Result<HashSet<String>> {
+        let files = source.files()?;
+
+        if files.is_empty() {
+            return Ok(HashSet::new());
         }
 
-        walk_dir(dir.as_ref(), &mut rust_files)?;
-        Ok(rust_files)
+        let scanned = self.scan_files(source, files)?;
+        Ok(self.variable_names(scanned.iter().flat_map(|(_, scan)| &scan.detections)))
+    }
+
+    /// Scan all files of a source and describe every variable found
+    ///
+    /// Unlike [`EnvScanner::scan_source`], this keeps the locations of each use and
+    /// the requiredness, default and type inferred from the surrounding code.
+    pub fn scan_source_detailed(
+        &self,
+        source: &dyn Source,
+    ) -> Result<BTreeMap<String, VariableInfo>> {
+        let scanned = self.scan_files(source, source.files()?)?;
+        Ok(self.describe(&scanned))
+    }
+
+    /// Describe the non-ignored variables found by a scan
+    pub(crate) fn describe(
+        &self,
+        scanned: &[(PathBuf, FileScan)],
+    ) -> BTreeMap<String, VariableInfo> {
+        VariableInfo::collect(scanned.iter().flat_map(|(file, scan)| {
+            scan.detections
+                .iter()
+                .filter(|detection| !self.is_ignored(&detection.name))
+                .map(move |detection| (file.as_path(), detection))
+        }))
+    }
+
+    /// Scan all .rs files in a directory and describe every variable found
+    pub fn scan_directory_detailed<P: AsRef<Path>>(
+        &self,
+        dir: P,
+    ) -> Result<BTreeMap<String, VariableInfo>> {
+        self.scan_source_detailed(self.directory_source(dir.as_ref()).as_ref())
+    }
+
+    /// Scan a directory and describe every variable found, together with the
+    /// variables its dependencies imply
+    ///
+    /// The directory is scanned once; what dependency rules look for is taken
+    /// from the same, cached per-file results.
+    pub fn scan_directory_with_dependencies<P: AsRef<Path>>(
+        &self,
+        dir: P,
+    ) -> Result<(BTreeMap<String, VariableInfo>, Vec<DependencyVariable>)> {
+        let dir = dir.as_ref();
+        let scanned = self.scan_tree(dir)?;
+        let mentions: SourceMentions = scanned.iter().map(|(_, scan)| &scan.mentions).collect();
+        let locked = dependencies::usable_crates(self, dir, None)?;
+        let implied = dependencies::implied_variables(self, &locked, &mentions);
+        Ok((self.describe(&scanned), implied))
     }
 
     /// Scan all .rs files in parallel and collect environment variables
+    ///
+    /// Per-file results are cached between runs unless caching is disabled in the
+    /// configuration.
     pub fn scan_directory<P: AsRef<Path>>(&self, dir: P) -> Result<HashSet<String>> {
-        let rust_files = self.find_rust_files(dir)?;
+        self.scan_source(self.directory_source(dir.as_ref()).as_ref())
+    }
 
-        if rust_files.is_empty() {
-            return Ok(HashSet::new());
+    /// Scan every file of a directory and return what was found in each
+    pub(crate) fn scan_tree(&self, dir: &Path) -> Result<Vec<(PathBuf, FileScan)>> {
+        let source = self.directory_source(dir);
+        self.scan_files(source.as_ref(), source.files()?)
+    }
+
+    /// Source for scanning a directory: every `.rs` file, or only those reachable
+    /// from crate roots when module tree mode is enabled
+    fn directory_source(&self, dir: &Path) -> Box<dyn Source> {
+        if self.config.module_tree.unwrap_or(false) {
+            Box::new(ModuleTreeSource::new(dir))
+        } else {
+            Box::new(self.fs_source(dir))
         }
+    }
 
-        // Use Mutex to safely collect results from parallel threads
-        let all_variables = Mutex::new(HashSet::new());
+    /// Read the existing output file when merging into it
+    ///
+    /// Variables it sets that are not in `used` are handled as the `stale`
+    /// setting asks. Returns `None` when merging is disabled or there is
+    /// nothing to merge into, in which case the file is written from scratch.
+    fn existing_document(&self, path: &Path, used: &HashSet<&str>) -> Result<Option<Document>> {
+        if !self.config.merge_existing.unwrap_or(true) || !path.exists() {
+            return Ok(None);
+        }
+        let mut document = Document::read_as(path, self.dialect())?;
+        let mode = self.config.stale.unwrap_or_default();
+        stale::apply(
+            &mut document,
+            used,
+            self.keep_unused(),
+            mode,
+            &stale::today(),
+        );
+        Ok((!document.is_blank()).then_some(document))
+    }
 
-        // Parallel processing of files
-        rust_files.par_iter().try_for_each(|file| -> Result<()> {
-            let variables = self.scan_file(file)?;
+    /// Variables set in the existing output file that the plan no longer lists
+    ///
+    /// Variables matching the `keep_unused` patterns are left out. Empty when
+    /// merging is disabled or the file doesn't exist.
+    pub fn stale_variables<P: AsRef<Path>>(
+        &self,
+        plan: &EnvPlan,
+        output_path: P,
+    ) -> Result<Vec<String>> {
+        let output_path = output_path.as_ref();
+        if !self.config.merge_existing.unwrap_or(true) || !output_path.exists() {
+            return Ok(Vec::new());
+        }
+        let document = Document::read_as(output_path, self.dialect())?;
+        Ok(stale::find_stale(
+            &document,
+            &plan.names(),
+            self.keep_unused(),
+        ))
+    }
 
-            if !variables.is_empty() {
-                let mut all_vars = all_variables.lock().unwrap();
-                all_vars.extend(variables);
-            }
+    pub(crate) fn keep_unused(&self) -> &[String] {
+        self.config.keep_unused.as_deref().unwrap_or_default()
+    }
 
-            Ok(())
-        })?;
+    /// Types of variables declared in the configuration
+    pub(crate) fn declared_types(&self) -> BTreeMap<String, String> {
+        self.config.types.clone().unwrap_or_default()
+    }
+
+    /// Syntax of the generated env files
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.config.dialect.unwrap_or_default()
+    }
 
-        Ok(all_variables.into_inner().unwrap())
+    /// Scan a directory and work out what its generated env file contains
+    ///
+    /// Besides the variables read by the code, this adds those read by
+    /// dependencies and handles variables Cargo provides at build time as
+    /// configured.
+    pub fn plan_directory<P: AsRef<Path>>(&self, dir: P) -> Result<EnvPlan> {
+        let dir = dir.as_ref();
+        self.plan_scanned(dir, &self.scan_tree(dir)?)
     }
 
-    /// Read existing .env file and return variables as HashMap
-    fn read_existing_env<P: AsRef<Path>>(
+    /// Work out the generated env file of `dir` from all of its scanned files
+    pub(crate) fn plan_scanned(
         &self,
-        path: P,
-    ) -> Result<std::collections::HashMap<String, String>> {
-        let mut existing = std::collections::HashMap::new();
-
-        if path.as_ref().exists() {
-            let content = fs::read_to_string(path)?;
-            for line in content.lines() {
-                let line = line.trim();
-                if line.is_empty() || line.starts_with('#') {
-                    continue;
-                }
+        dir: &Path,
+        scanned: &[(PathBuf, FileScan)],
+    ) -> Result<EnvPlan> {
+        let scans: Vec<&FileScan> = scanned.iter().map(|(_, scan)| scan).collect();
+        let locked = dependencies::usable_crates(self, dir, None)?;
+        self.plan_files(dir, &locked, &scans)
+    }
 
-                if let Some(eq_pos) = line.find('=') {
-                    let key = line[..eq_pos].trim().to_string();
-                    let value = line[eq_pos + 1..].trim().to_string();
-                    existing.insert(key, value);
+    /// Work out the generated env file for some of the scanned files
+    ///
+    /// `dir` is the directory of the package the files belong to, which decides
+    /// the variables Cargo provides, and `locked` are the crates from
+    /// `Cargo.lock` the files may use.
+    pub(crate) fn plan_files(
+        &self,
+        dir: &Path,
+        locked: &HashSet<String>,
+        scans: &[&FileScan],
+    ) -> Result<EnvPlan> {
+        let mut variables = self.variable_names(scans.iter().flat_map(|scan| &scan.detections));
+        let mentions: SourceMentions = scans.iter().map(|scan| &scan.mentions).collect();
+        let mut implied = dependencies::implied_variables(self, locked, &mentions);
+        let mut sections = Vec::new();
+
+        // Well-known variables set by the system, Cargo or CI
+        let mut well_known = Vec::new();
+        variables.retain(|name| {
+            let Some(entry) = catalogue::lookup(name) else {
+                return true;
+            };
+            match catalogue::action_for(entry.category, self.config.catalogue.as_ref()) {
+                CatalogueAction::Keep => true,
+                CatalogueAction::Exclude => false,
+                CatalogueAction::Annotate => {
+                    well_known.push(AnnotatedVariable {
+                        name: name.clone(),
+                        comment: Some(format!("{} ({})", entry.description, entry.category)),
+                        commented_out: true,
+                        ..Default::default()
+                    });
+                    false
                 }
             }
+        });
+        well_known.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mode = self.config.cargo_provided.unwrap_or_default();
+        let mut provided_section = None;
+        if mode != ProvidedMode::Keep {
+            let provided = cargo_env::provided_variables(dir)?;
+            let used: Vec<_> = provided
+                .values()
+                .filter(|variable| {
+                    variables.contains(&variable.name)
+                        || implied.iter().any(|implied| implied.name == variable.name)
+                })
+                .collect();
+
+            if mode == ProvidedMode::Annotate && !used.is_empty() {
+                provided_section = Some(EnvSection {
+                    title: "Provided by Cargo at build time".to_string(),
+                    entries: used
+                        .iter()
+                        .map(|variable| AnnotatedVariable {
+                            name: variable.name.clone(),
+                            comment: Some(format!("Set by {}", variable.describe())),
+                            value: variable.value.clone(),
+                            commented_out: true,
+                        })
+                        .collect(),
+                });
+            }
+
+            variables.retain(|name| !provided.contains_key(name));
+            implied.retain(|variable| !provided.contains_key(&variable.name));
         }
 
-        Ok(existing)
+        if !implied.is_empty() {
+            sections.push(dependency_section(&implied));
+        }
+        sections.extend(provided_section);
+        if !well_known.is_empty() {
+            sections.push(EnvSection {
+                title: "Well-known variables, usually set by the environment".to_string(),
+                entries: well_known,
+            });
+        }
+
+        Ok(EnvPlan {
+            variables,
+            sections,
+        })
     }
 
     /// Generate .env file with detected variables
@@ -256,41 +1082,143 @@ impl EnvScanner {
         variables: &HashSet<String>,
         output_path: P,
     ) -> Result<()> {
-        let output_path = output_path.as_ref();
-        let merge_existing = self.config.merge_existing.unwrap_or(true);
-
-        let mut existing_vars = if merge_existing {
-            self.read_existing_env(output_path)?
-        } else {
-            std::collections::HashMap::new()
-        };
-
-        // Add new variables with empty values if they don't exist
-        for var in variables {
-            existing_vars.entry(var.clone()).or_insert_with(String::new);
-        }
-
-        // Sort variables for consistent output
-        let mut sorted_vars: Vec<_> = existing_vars.iter().collect();
-        sorted_vars.sort_by(|a, b| a.0.cmp(b.0));
-
-        // Write to file
-        let mut file = File::create(output_path)
-            .with_context(|| format!("Failed to create file: {:?}", output_path))?;
+        self.generate_env_file_with_dependencies(variables, &[], output_path)
+    }
 
-        writeln!(file, "# Auto-generated environment variables")?;
-        writeln!(file, "# Add your values below")?;
-        writeln!(file)?;
+    /// Generate .env file with detected variables and those read by dependencies
+    ///
+    /// Variables implied only by dependencies are listed in a separate section at
+    /// the end, each with a comment naming the dependency.
+    pub fn generate_env_file_with_dependencies<P: AsRef<Path>>(
+        &self,
+        variables: &HashSet<String>,
+        implied: &[DependencyVariable],
+        output_path: P,
+    ) -> Result<()> {
+        let section = dependency_section(implied);
+        self.generate_annotated_env_file(variables, &[section], output_path)
+    }
 
-        for (key, value) in sorted_vars {
-            if value.is_empty() {
-                writeln!(file, "{}=", key)?;
-            } else {
-                writeln!(file, "{}={}", key, value)?;
+    /// Generate .env file with detected variables followed by annotated sections
+    ///
+    /// Section entries the code reads directly (those in `variables`) are listed
+    /// with the other variables instead. Entries marked as commented out are
+    /// written as `# NAME=value`.
+    ///
+    /// When merging into an existing file, the file is kept as it is and only
+    /// variables it doesn't mention yet, set or commented out, are added: new
+    /// variables after the last one at the top of the file, new section entries
+    /// at the end of their section, which is created if missing.
+    pub fn generate_annotated_env_file<P: AsRef<Path>>(
+        &self,
+        variables: &HashSet<String>,
+        sections: &[EnvSection],
+        output_path: P,
+    ) -> Result<()> {
+        let output_path = output_path.as_ref();
+        let used: HashSet<&str> = variables
+            .iter()
+            .map(String::as_str)
+            .chain(
+                sections
+                    .iter()
+                    .flat_map(|section| section.entries.iter().map(|entry| entry.name.as_str())),
+            )
+            .collect();
+        let existing = self.existing_document(output_path, &used)?;
+        let is_new = |name: &str| existing.as_ref().is_none_or(|doc| !doc.mentions(name));
+
+        let mut sorted_vars: Vec<&String> = variables.iter().filter(|var| is_new(var)).collect();
+        sorted_vars.sort();
+        let main_lines: Vec<String> = sorted_vars.iter().map(|var| format!("{}=", var)).collect();
+
+        // Take section entries out of the main list; each variable is listed once
+        let mut listed: HashSet<&str> = variables.iter().map(String::as_str).collect();
+        let mut section_lines: Vec<(String, Vec<String>)> = Vec::new();
+        for section in sections {
+            let mut lines = Vec::new();
+            for entry in &section.entries {
+                if !listed.insert(&entry.name) || !is_new(&entry.name) {
+                    continue;
+                }
+                if let Some(comment) = &entry.comment {
+                    lines.push(format!("# {}", comment));
+                }
+                if entry.commented_out {
+                    let suggested = entry.value.as_deref().unwrap_or_default();
+                    let suggested = dotenv::quote(suggested, self.dialect())
+                        .with_context(|| format!("Failed to write {}", entry.name))?;
+                    lines.push(format!("# {}={}", entry.name, suggested));
+                } else {
+                    lines.push(format!("{}=", entry.name));
+                }
+            }
+            if !lines.is_empty() {
+                section_lines.push((format!("# {}", section.title), lines));
             }
         }
+        let sections = section_lines;
+
+        let content = match existing {
+            Some(mut document) => {
+                let headings: Vec<&str> = sections.iter().map(|(h, _)| h.as_str()).collect();
+                document.insert_into_group(None, &headings, &main_lines);
+                for (heading, lines) in &sections {
+                    document.insert_into_group(Some(heading), &headings, lines);
+                }
+                document.to_string()
+            }
+            None => {
+                let mut content = String::from(
+                    "# Auto-generated environment variables\n# Add your values below\n",
+                );
+                if !main_lines.is_empty() {
+                    content.push('\n');
+                }
+                for line in &main_lines {
+                    content.push_str(line);
+                    content.push('\n');
+                }
+                for (heading, lines) in &sections {
+                    content.push('\n');
+                    content.push_str(heading);
+                    content.push('\n');
+                    for line in lines {
+                        content.push_str(line);
+                        content.push('\n');
+                    }
+                }
+                content
+            }
+        };
 
-        Ok(())
+        self.write_output(output_path, content)
+    }
+
+    /// Generate .env file with variables grouped into titled sections
+    ///
+    /// Sections are written in the given order, variables sorted within each.
+    /// When merging, the existing file is kept as it is and variables it doesn't
+    /// set yet are added at the end of their section.
+    pub fn generate_sectioned_env_file<P: AsRef<Path>>(
+        &self,
+        sections: &[(String, BTreeSet<String>)],
+        output_path: P,
+    ) -> Result<()> {
+        let sections: Vec<EnvSection> = sections
+            .iter()
+            .map(|(title, names)| EnvSection {
+                title: format!("[{}]", title),
+                entries: names
+                    .iter()
+                    .map(|name| AnnotatedVariable {
+                        name: name.clone(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            })
+            .collect();
+        self.generate_annotated_env_file(&HashSet::new(), &sections, output_path)
     }
 }
 
@@ -308,12 +1236,28 @@ pub fn generate_env_file<P: AsRef<Path>>(path: P) -> Result<()> {
 /// Generate .env file with custom configuration
 pub fn generate_env_file_with_config<P: AsRef<Path>>(path: P, config: Config) -> Result<()> {
     let scanner = EnvScanner::with_config(config.clone())?;
-    let variables = scanner.scan_directory(&path)?;
-
     let output_file = config.output.unwrap_or_else(|| ".env".to_string());
-    let output_path = path.as_ref().join(output_file);
+    let output_path = path.as_ref().join(&output_file);
+
+    let layout = config.workspace_output.unwrap_or_default();
+    if layout == WorkspaceOutput::PerBinary {
+        modules::generate_per_binary(&scanner, &path, &output_file)?;
+        return Ok(());
+    }
+    if layout != WorkspaceOutput::Combined {
+        let workspace = Workspace::discover(&path)?;
+        if workspace.is_multi_package() {
+            if layout == WorkspaceOutput::PerCrate {
+                workspace::generate_per_crate(&scanner, &path, &workspace, &output_file)?;
+            } else {
+                workspace::generate_sections(&scanner, &path, &workspace, output_path)?;
+            }
+            return Ok(());
+        }
+    }
 
-    scanner.generate_env_file(&variables, output_path)?;
+    let plan = scanner.plan_directory(&path)?;
+    scanner.generate_annotated_env_file(&plan.variables, &plan.sections, output_path)?;
     Ok(())
 }
 
@@ -323,8 +1267,8 @@ pub fn generate_env_file_to<P: AsRef<Path>, O: AsRef<Path>>(
     output_path: O,
 ) -> Result<()> {
     let scanner = EnvScanner::new()?;
-    let variables = scanner.scan_directory(scan_path)?;
-    scanner.generate_env_file(&variables, output_path)?;
+    let plan = scanner.plan_directory(scan_path)?;
+    scanner.generate_annotated_env_file(&plan.variables, &plan.sections, output_path)?;
     Ok(())
 }
 
@@ -433,6 +1377,136 @@ fn main() {
         Ok(())
     }
 
+    #[test]
+    fn test_merge_existing_dotenv_syntax() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output = temp_dir.path().join(".env");
+        let existing_env = r#"export API_KEY=abc123 # production key
+GREETING="hello # not a comment"
+PATTERN='$HOME/*'
+TLS_KEY="-----BEGIN KEY-----
+abc
+-----END KEY-----"
+"#;
+        fs::write(&output, existing_env)?;
+        create_test_file(
+            temp_dir.path(),
+            "main.rs",
+            r#"fn main() { std::env::var("API_KEY"); std::env::var("TLS_KEY"); }"#,
+        )?;
+
+        let scanner = EnvScanner::new()?;
+        let variables = scanner.scan_directory(temp_dir.path())?;
+        scanner.generate_env_file(&variables, &output)?;
+        // A second run must read back exactly what the first one wrote
+        scanner.generate_env_file(&variables, &output)?;
+
+        let values: std::collections::HashMap<_, _> = dotenv::parse_file(&output)?
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect();
+        assert_eq!(values["API_KEY"], "abc123");
+        assert_eq!(values["GREETING"], "hello # not a comment");
+        assert_eq!(values["PATTERN"], "$HOME/*");
+        assert_eq!(
+            values["TLS_KEY"],
+            "-----BEGIN KEY-----\nabc\n-----END KEY-----"
+        );
+
+        fs::write(&output, "GOOD=1\nBAD=\"unterminated\n")?;
+        let err = scanner.generate_env_file(&variables, &output).unwrap_err();
+        assert!(format!("{:#}", err).contains("line 2, column 5: unterminated double quote"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_preserves_layout() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output = temp_dir.path().join(".env");
+        let existing_env = "# Service settings\nZ_LAST=1\nexport A_FIRST='kept as is'   # quoted\n\n# Local only\nDEBUG_SQL=true\n";
+        fs::write(&output, existing_env)?;
+        create_test_file(
+            temp_dir.path(),
+            "main.rs",
+            r#"fn main() { std::env::var("A_FIRST"); std::env::var("NEW_VAR"); }"#,
+        )?;
+
+        let scanner = EnvScanner::new()?;
+        let variables = scanner.scan_directory(temp_dir.path())?;
+        scanner.generate_env_file(&variables, &output)?;
+        assert_eq!(
+            fs::read_to_string(&output)?,
+            format!("{}NEW_VAR=\n", existing_env)
+        );
+
+        // Nothing new to add leaves the file untouched
+        let before = fs::read_to_string(&output)?;
+        scanner.generate_env_file(&variables, &output)?;
+        assert_eq!(fs::read_to_string(&output)?, before);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_handles_stale_variables() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output = temp_dir.path().join(".env");
+        fs::write(&output, "API_KEY=1\nREMOVED_VAR=2\nLOCAL_DEBUG=3\n")?;
+        create_test_file(
+            temp_dir.path(),
+            "main.rs",
+            r#"fn main() { std::env::var("API_KEY"); }"#,
+        )?;
+
+        let config = Config {
+            stale: Some(StaleMode::Prune),
+            keep_unused: Some(vec!["LOCAL_*".to_string()]),
+            ..Default::default()
+        };
+        let scanner = EnvScanner::with_config(config)?;
+        let plan = scanner.plan_directory(temp_dir.path())?;
+        assert_eq!(
+            scanner.stale_variables(&plan, &output)?,
+            vec!["REMOVED_VAR"]
+        );
+
+        scanner.generate_annotated_env_file(&plan.variables, &plan.sections, &output)?;
+        assert_eq!(fs::read_to_string(&output)?, "API_KEY=1\nLOCAL_DEBUG=3\n");
+        assert!(scanner.stale_variables(&plan, &output)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run_records_writes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output = temp_dir.path().join(".env");
+        fs::write(&output, "API_KEY=secret\n")?;
+        create_test_file(
+            temp_dir.path(),
+            "main.rs",
+            r#"fn main() { std::env::var("API_KEY"); std::env::var("PORT"); }"#,
+        )?;
+
+        let scanner = EnvScanner::new()?.dry_run();
+        let variables = scanner.scan_directory(temp_dir.path())?;
+        scanner.generate_env_file(&variables, &output)?;
+        scanner.generate_env_file(&variables, temp_dir.path().join(".env.example"))?;
+
+        assert_eq!(fs::read_to_string(&output)?, "API_KEY=secret\n");
+        assert!(!temp_dir.path().join(".env.example").exists());
+
+        let planned = scanner.planned_writes();
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[0].old.as_deref(), Some("API_KEY=secret\n"));
+        assert_eq!(planned[0].new, "API_KEY=secret\nPORT=\n");
+        assert_eq!(planned[1].old, None);
+        assert!(planned.iter().all(PlannedWrite::is_change));
+
+        Ok(())
+    }
+
     #[test]
     fn test_parallel_scanning() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -515,4 +1589,495 @@ fn main() {
 
         Ok(())
     }
+
+    #[test]
+    fn test_detection_line_numbers() -> Result<()> {
+        let content = r#"
+fn main() {
+    let a = env::var("SINGLE_LINE").unwrap();
+    let b = std::env::var(
+        "MULTI_LINE"
+    ).unwrap();
+}
+"#;
+
+        let scanner = EnvScanner::new()?;
+        let detections = scanner.detect(content);
+
+        assert_eq!(
+            detections,
+            vec![
+                Detection {
+                    name: "SINGLE_LINE".to_string(),
+                    line: 3,
+                    required: Some(true),
+                    ..Default::default()
+                },
+                Detection {
+                    name: "MULTI_LINE".to_string(),
+                    line: 4,
+                    required: Some(true),
+                    ..Default::default()
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_scan_with_cache() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_file(temp_dir.path(), "Cargo.toml", "[package]\nname = \"app\"\n")?;
+        create_test_file(
+            temp_dir.path(),
+            "src/main.rs",
+            r#"fn main() { std::env::var("FIRST").unwrap(); }"#,
+        )?;
+        create_test_file(
+            temp_dir.path(),
+            "src/lib.rs",
+            r#"pub fn f() { std::env::var("UNCHANGED").unwrap(); }"#,
+        )?;
+
+        let scanner = EnvScanner::new()?;
+        let variables = scanner.scan_directory(temp_dir.path())?;
+        assert_eq!(variables.len(), 2);
+        assert!(temp_dir.path().join(DEFAULT_CACHE_PATH).exists());
+
+        // Changing a file must be picked up even though a cache exists
+        create_test_file(
+            temp_dir.path(),
+            "src/main.rs",
+            r#"fn main() { std::env::var("SECOND").unwrap(); }"#,
+        )?;
+        let variables = scanner.scan_directory(temp_dir.path())?;
+        assert_eq!(variables.len(), 2);
+        assert!(variables.contains("SECOND"));
+        assert!(variables.contains("UNCHANGED"));
+        assert!(!variables.contains("FIRST"));
+
+        // The ignore list is applied on top of cached results
+        let config = Config {
+            ignore: Some(vec!["UNCHANGED".to_string()]),
+            ..Default::default()
+        };
+        let variables = EnvScanner::with_config(config)?.scan_directory(temp_dir.path())?;
+        assert_eq!(variables.len(), 1);
+        assert!(variables.contains("SECOND"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_cache_only_in_cargo_projects() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        create_test_file(root, "main.rs", r#"fn main() { env::var("PLAIN"); }"#)?;
+
+        EnvScanner::new()?.scan_directory(root)?;
+        assert!(!root.join("target").exists());
+
+        // A configured location is used anywhere
+        let config = Config {
+            cache_path: Some(".cache/autoenv".to_string()),
+            ..Default::default()
+        };
+        EnvScanner::with_config(config)?.scan_directory(root)?;
+        assert!(root.join(".cache/autoenv").exists());
+        assert!(!root.join("target").exists());
+
+        fs::create_dir(root.join("target"))?;
+        EnvScanner::new()?.scan_directory(root)?;
+        assert!(root.join(DEFAULT_CACHE_PATH).exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_without_cache() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_file(
+            temp_dir.path(),
+            "main.rs",
+            r#"fn main() { std::env::var("NO_CACHE_VAR").unwrap(); }"#,
+        )?;
+
+        let config = Config {
+            cache: Some(false),
+            ..Default::default()
+        };
+        let variables = EnvScanner::with_config(config)?.scan_directory(temp_dir.path())?;
+
+        assert!(variables.contains("NO_CACHE_VAR"));
+        assert!(!temp_dir.path().join(DEFAULT_CACHE_PATH).exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_directory_detailed() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_file(
+            temp_dir.path(),
+            "src/main.rs",
+            r#"
+fn main() {
+    let db = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let port: u16 = env::var("PORT")
+        .unwrap_or_else(|_| "3000".to_string())
+        .parse::<u16>()
+        .unwrap();
+    let redis = env::var("REDIS_URL").ok();
+}
+"#,
+        )?;
+        create_test_file(
+            temp_dir.path(),
+            "src/lib.rs",
+            r#"pub fn f() { let _ = env::var("REDIS_URL").unwrap(); }"#,
+        )?;
+
+        let scanner = EnvScanner::new()?;
+        let variables = scanner.scan_directory_detailed(temp_dir.path())?;
+
+        assert_eq!(variables.len(), 3);
+        assert_eq!(variables["DATABASE_URL"].required, Some(true));
+
+        let port = &variables["PORT"];
+        assert_eq!(port.required, Some(false));
+        assert_eq!(port.default, Some("3000".to_string()));
+        assert_eq!(port.var_type, Some("u16".to_string()));
+        assert_eq!(
+            port.locations,
+            vec![Location {
+                file: PathBuf::from("src/main.rs"),
+                line: 4,
+                kind: Some(FileKind::Rust),
+            }]
+        );
+
+        // Required in one place makes the variable required overall
+        let redis = &variables["REDIS_URL"];
+        assert_eq!(redis.required, Some(true));
+        assert_eq!(redis.locations.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_with_dependency_variables() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let env_path = temp_dir.path().join(".env");
+        fs::write(&env_path, "API_KEY=secret\n")?;
+
+        let implied = vec![
+            DependencyVariable {
+                name: "RUST_LOG".to_string(),
+                description: Some("Log level filter directives".to_string()),
+                dependency: "env_logger".to_string(),
+            },
+            DependencyVariable {
+                name: "API_KEY".to_string(),
+                description: None,
+                dependency: "some-client".to_string(),
+            },
+        ];
+        let variables: HashSet<String> = ["API_KEY".to_string()].into_iter().collect();
+
+        let scanner = EnvScanner::new()?;
+        scanner.generate_env_file_with_dependencies(&variables, &implied, &env_path)?;
+
+        // Variables the code reads itself are not repeated in the dependency section
+        let content = fs::read_to_string(&env_path)?;
+        assert_eq!(
+            content,
+            "API_KEY=secret\n\n# Read by dependencies\n# Log level filter directives (env_logger)\nRUST_LOG=\n"
+        );
+
+        // Values filled in later stay where they are
+        let edited = content.replace("RUST_LOG=", "RUST_LOG=debug");
+        fs::write(&env_path, &edited)?;
+        scanner.generate_env_file_with_dependencies(&variables, &implied, &env_path)?;
+        assert_eq!(fs::read_to_string(&env_path)?, edited);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_sectioned_env_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let env_path = temp_dir.path().join(".env");
+        let names = |list: &[&str]| list.iter().map(|name| name.to_string()).collect();
+        let sections = vec![
+            ("api".to_string(), names(&["PORT", "DATABASE_URL"])),
+            ("worker".to_string(), names(&["QUEUE_URL"])),
+        ];
+
+        let scanner = EnvScanner::new()?;
+        scanner.generate_sectioned_env_file(&sections, &env_path)?;
+        assert_eq!(
+            fs::read_to_string(&env_path)?,
+            "# Auto-generated environment variables\n# Add your values below\n\n\
+             # [api]\nDATABASE_URL=\nPORT=\n\n# [worker]\nQUEUE_URL=\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_excludes_cargo_provided_variables() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::create_dir_all(temp_dir.path().join(".cargo"))?;
+        fs::write(
+            temp_dir.path().join(".cargo/config.toml"),
+            "[env]\nAPP_MODE = \"dev\"\n",
+        )?;
+        fs::write(
+            temp_dir.path().join("main.rs"),
+            r#"fn main() { env::var("APP_MODE").unwrap(); env::var("API_KEY").unwrap(); }"#,
+        )?;
+
+        let scanner = EnvScanner::new()?;
+        let plan = scanner.plan_directory(temp_dir.path())?;
+        assert_eq!(plan.variables, HashSet::from(["API_KEY".to_string()]));
+        assert!(plan.sections.is_empty());
+
+        let config = Config {
+            cargo_provided: Some(ProvidedMode::Annotate),
+            ..Default::default()
+        };
+        let scanner = EnvScanner::with_config(config)?;
+        let plan = scanner.plan_directory(temp_dir.path())?;
+        assert_eq!(plan.len(), 1);
+
+        let env_path = temp_dir.path().join(".env");
+        scanner.generate_annotated_env_file(&plan.variables, &plan.sections, &env_path)?;
+        let content = fs::read_to_string(&env_path)?;
+        assert!(content.contains(
+            "# Provided by Cargo at build time\n# Set by [env] in .cargo/config.toml\n# APP_MODE=dev\n"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_applies_catalogue() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join("main.rs"),
+            r#"fn main() {
+                env::var("HOME").unwrap();
+                env::var("RUST_BACKTRACE").ok();
+                env::var("API_KEY").unwrap();
+            }"#,
+        )?;
+
+        let scanner = EnvScanner::new()?;
+        let plan = scanner.plan_directory(temp_dir.path())?;
+        assert_eq!(plan.variables, HashSet::from(["API_KEY".to_string()]));
+        assert_eq!(plan.sections.len(), 1);
+        assert_eq!(plan.sections[0].entries[0].name, "RUST_BACKTRACE");
+        assert_eq!(plan.len(), 1);
+
+        let env_path = temp_dir.path().join(".env");
+        scanner.generate_annotated_env_file(&plan.variables, &plan.sections, &env_path)?;
+        let content = fs::read_to_string(&env_path)?;
+        assert!(content.contains("# Print a backtrace on panic (rust)\n# RUST_BACKTRACE=\n"));
+        assert!(!content.contains("HOME"));
+
+        let config = Config {
+            catalogue: Some(BTreeMap::from([
+                (Category::System, CatalogueAction::Keep),
+                (Category::Rust, CatalogueAction::Exclude),
+            ])),
+            ..Default::default()
+        };
+        let scanner = EnvScanner::with_config(config)?;
+        let plan = scanner.plan_directory(temp_dir.path())?;
+        assert_eq!(
+            plan.variables,
+            HashSet::from(["API_KEY".to_string(), "HOME".to_string()])
+        );
+        assert!(plan.sections.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_deployment_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        fs::write(
+            root.join("main.rs"),
+            r#"fn main() { env::var("DATABASE_URL").unwrap(); }"#,
+        )?;
+        fs::write(
+            root.join("entrypoint.sh"),
+            "#!/bin/sh\nexec app --port \"${PORT:-8080}\"\n",
+        )?;
+        fs::write(
+            root.join("Dockerfile"),
+            "FROM scratch\nENV APP_ENV=production\n",
+        )?;
+        fs::write(
+            root.join("docker-compose.yml"),
+            "services:\n  app:\n    environment:\n      - DATABASE_URL\n",
+        )?;
+
+        let scanner = EnvScanner::new()?;
+        let variables = scanner.scan_directory_detailed(root)?;
+        assert_eq!(
+            variables.keys().collect::<Vec<_>>(),
+            vec!["APP_ENV", "DATABASE_URL", "PORT"]
+        );
+        assert_eq!(variables["PORT"].default, Some("8080".to_string()));
+        assert_eq!(
+            variables["PORT"].locations,
+            vec![Location {
+                file: PathBuf::from("entrypoint.sh"),
+                line: 2,
+                kind: Some(FileKind::Shell),
+            }]
+        );
+        let kinds: Vec<Option<FileKind>> = variables["DATABASE_URL"]
+            .locations
+            .iter()
+            .map(|location| location.kind)
+            .collect();
+        assert_eq!(kinds, vec![Some(FileKind::Compose), Some(FileKind::Rust)]);
+
+        let scanner = EnvScanner::with_config(Config {
+            deployment_files: Some(false),
+            ..Default::default()
+        })?;
+        assert_eq!(
+            scanner.scan_directory(root)?,
+            HashSet::from(["DATABASE_URL".to_string()])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_selected_languages() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("worker"))?;
+        fs::create_dir_all(root.join("web/src"))?;
+        fs::create_dir_all(root.join("web/node_modules/pkg"))?;
+        fs::write(
+            root.join("main.rs"),
+            r#"fn main() { env::var("DATABASE_URL").unwrap(); }"#,
+        )?;
+        fs::write(
+            root.join("worker/tasks.py"),
+            "import os\nBROKER = os.environ['BROKER_URL']\n",
+        )?;
+        fs::write(
+            root.join("web/src/api.ts"),
+            "export const base = process.env.API_BASE ?? '/api';\n",
+        )?;
+        fs::write(
+            root.join("web/node_modules/pkg/index.js"),
+            "process.env.NODE_DEBUG;\n",
+        )?;
+
+        let scanner = EnvScanner::new()?;
+        assert_eq!(
+            scanner.scan_directory(root)?,
+            HashSet::from(["DATABASE_URL".to_string()])
+        );
+
+        let scanner = EnvScanner::with_config(Config {
+            languages: Some(vec![FileKind::Rust, FileKind::Python, FileKind::JavaScript]),
+            ..Default::default()
+        })?;
+        let variables = scanner.scan_directory_detailed(root)?;
+        assert_eq!(
+            variables.keys().collect::<Vec<_>>(),
+            vec!["API_BASE", "BROKER_URL", "DATABASE_URL"]
+        );
+        assert_eq!(variables["BROKER_URL"].required, Some(true));
+        assert_eq!(variables["API_BASE"].default, Some("/api".to_string()));
+        assert_eq!(
+            variables["API_BASE"].locations[0].kind,
+            Some(FileKind::JavaScript)
+        );
+
+        Ok(())
+    }
+
+    /// Reports `NAME` for every `require NAME` line
+    pub(crate) struct RequireDetector(pub(crate) Vec<String>);
+
+    impl Detector for RequireDetector {
+        fn name(&self) -> &str {
+            "require"
+        }
+
+        fn patterns(&self) -> &[String] {
+            &self.0
+        }
+
+        fn detect(&self, _path: &Path, content: &str) -> Result<Vec<Detection>> {
+            Ok(content
+                .lines()
+                .enumerate()
+                .filter_map(|(index, line)| {
+                    Some(Detection {
+                        name: line.strip_prefix("require ")?.trim().to_string(),
+                        line: index + 1,
+                        required: Some(true),
+                        metadata: [("keyword".to_string(), serde_json::json!("require"))].into(),
+                        ..Default::default()
+                    })
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_custom_detector() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("conf"))?;
+        fs::write(
+            root.join("main.rs"),
+            r#"fn main() { env::var("DATABASE_URL").unwrap(); }"#,
+        )?;
+        fs::write(root.join("conf/app.dsl"), "listen 80\nrequire API_TOKEN\n")?;
+        fs::write(root.join("conf/notes.txt"), "require NOT_SCANNED\n")?;
+
+        let scanner = EnvScanner::new()?.with_detector(RequireDetector(vec!["*.dsl".to_string()]));
+        let variables = scanner.scan_directory_detailed(root)?;
+        assert_eq!(
+            variables.keys().collect::<Vec<_>>(),
+            vec!["API_TOKEN", "DATABASE_URL"]
+        );
+        assert_eq!(variables["API_TOKEN"].required, Some(true));
+        assert_eq!(
+            variables["API_TOKEN"].locations[0].file,
+            PathBuf::from("conf/app.dsl")
+        );
+        assert_eq!(variables["API_TOKEN"].locations[0].kind, None);
+        assert_eq!(
+            variables["API_TOKEN"].metadata["keyword"],
+            serde_json::json!("require")
+        );
+        assert!(variables["DATABASE_URL"].metadata.is_empty());
+
+        // Metadata survives the scan cache
+        let cached = scanner.scan_directory_detailed(root)?;
+        assert_eq!(
+            cached["API_TOKEN"].metadata,
+            variables["API_TOKEN"].metadata
+        );
+
+        // Cached results are only reused by a scanner with the same detectors
+        let scanner = EnvScanner::new()?;
+        assert!(!scanner.scan_directory(root)?.contains("API_TOKEN"));
+
+        Ok(())
+    }
 }
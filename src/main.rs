@@ -4,9 +4,30 @@
 //! based on detected environment variable usage.
 
 use anyhow::{Context, Result};
-use auto_env_generator::{Config, EnvScanner};
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use auto_env_generator::backup::{self, BackupMode};
+use auto_env_generator::cargo_env::{self, ProvidedMode};
+use auto_env_generator::catalogue::{self, Category};
+use auto_env_generator::changelog;
+use auto_env_generator::check;
+use auto_env_generator::detectors::FileKind;
+use auto_env_generator::dotenv::{self, Dialect, Document};
+use auto_env_generator::git::{self, ChangeBase, ChangeReport, GitRevisionSource};
+use auto_env_generator::interpolate;
+use auto_env_generator::modules::{self, BinaryVariables};
+use auto_env_generator::preview::{KeyChanges, PlannedWrite};
+use auto_env_generator::secrets::{self, Issue};
+use auto_env_generator::stale::StaleMode;
+use auto_env_generator::validate;
+use auto_env_generator::vendor::{self, DependencyScan};
+use auto_env_generator::watch::{self, VariableChanges};
+use auto_env_generator::workspace::{self, Workspace, WorkspaceOutput};
+use auto_env_generator::{Config, EnvScanner, VariableInfo};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "autoenv")]
@@ -21,7 +42,10 @@ Examples:
   autoenv generate                    # Scan current directory
   autoenv generate ./my-project      # Scan specific directory
   autoenv generate -o .env.example   # Generate .env.example file
+  autoenv generate --per-crate       # One .env per workspace crate
+  autoenv generate --per-binary      # One .env.<bin> per binary target
   autoenv scan                       # Just list found variables
+  autoenv watch                      # Regenerate .env whenever sources change
   autoenv config                     # Show current configuration
 ")]
 struct Cli {
@@ -53,6 +77,72 @@ enum Commands {
         #[arg(long, value_name = "VARIABLE")]
         ignore: Vec<String>,
 
+        /// Rescan every file instead of reusing cached results
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Only scan files reachable from crate roots through `mod` declarations
+        #[arg(long)]
+        module_tree: bool,
+
+        /// In a workspace, write a separate file into each member crate
+        #[arg(long, conflicts_with = "sections")]
+        per_crate: bool,
+
+        /// In a workspace, group variables by crate in a single file
+        #[arg(long, conflicts_with = "per_binary")]
+        sections: bool,
+
+        /// Write a separate file for each binary target, e.g. .env.api
+        #[arg(long, conflicts_with = "per_crate")]
+        per_binary: bool,
+
+        /// Don't add variables read by dependencies such as RUST_LOG
+        #[arg(long)]
+        no_dependencies: bool,
+
+        /// Only scan Rust sources, not shell scripts, Dockerfiles or compose files
+        #[arg(long)]
+        no_deployment_files: bool,
+
+        /// Source language to scan (can be used multiple times; default: rust)
+        #[arg(long = "language", value_name = "LANGUAGE", value_parser = parse_language)]
+        languages: Vec<FileKind>,
+
+        /// Syntax of the generated file (default: dotenvy)
+        #[arg(long, value_enum)]
+        dialect: Option<Dialect>,
+
+        /// Remove variables from the existing file that the code no longer reads
+        #[arg(long, conflicts_with = "stale")]
+        prune: bool,
+
+        /// What to do with variables the code no longer reads (default: report)
+        #[arg(long, value_enum, value_name = "MODE")]
+        stale: Option<StaleMode>,
+
+        /// Keep a copy of a file before replacing it (default: off)
+        #[arg(long, value_enum, value_name = "MODE")]
+        backup: Option<BackupMode>,
+
+        /// Add generated files holding values to .gitignore if git would commit them
+        #[arg(long, conflicts_with = "dry_run")]
+        fix_gitignore: bool,
+
+        /// Show what would change instead of writing any file
+        #[arg(long)]
+        dry_run: bool,
+
+        /// How --dry-run shows changes: a unified diff or the keys changed as JSON
+        #[arg(
+            long,
+            value_enum,
+            value_name = "FORMAT",
+            default_value = "unified",
+            requires = "dry_run"
+        )]
+        diff_format: DiffFormat,
+
         /// Verbose output
         #[arg(short, long)]
         verbose: bool,
@@ -72,9 +162,228 @@ enum Commands {
         #[arg(long, value_name = "VARIABLE")]
         ignore: Vec<String>,
 
+        /// Rescan every file instead of reusing cached results
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Only scan files reachable from crate roots through `mod` declarations
+        #[arg(long, conflicts_with_all = ["since", "staged", "rev"])]
+        module_tree: bool,
+
         /// Show file locations where variables were found
         #[arg(long)]
         show_locations: bool,
+
+        /// Group variables by the workspace crate that reads them
+        #[arg(long, conflicts_with_all = ["since", "staged", "rev"])]
+        by_crate: bool,
+
+        /// Don't list variables read by dependencies such as RUST_LOG
+        #[arg(long)]
+        no_dependencies: bool,
+
+        /// Only scan Rust sources, not shell scripts, Dockerfiles or compose files
+        #[arg(long)]
+        no_deployment_files: bool,
+
+        /// Source language to scan (can be used multiple times; default: rust)
+        #[arg(long = "language", value_name = "LANGUAGE", value_parser = parse_language)]
+        languages: Vec<FileKind>,
+
+        /// Also scan the sources of dependencies (vendor directory or local
+        /// registry) and report the variables they read separately
+        #[arg(long, conflicts_with_all = ["since", "staged", "rev"])]
+        third_party: bool,
+
+        /// Show which binary targets need each variable
+        #[arg(long, conflicts_with_all = ["since", "staged", "rev", "by_crate"])]
+        by_target: bool,
+
+        /// Only scan files changed relative to a git revision
+        #[arg(long, value_name = "REV", conflicts_with = "staged")]
+        since: Option<String>,
+
+        /// Only scan files with staged changes, reading them from the git index
+        #[arg(long)]
+        staged: bool,
+
+        /// Scan a git revision (commit, branch or tag) without checking it out
+        #[arg(long, value_name = "REV", conflicts_with_all = ["since", "staged"])]
+        rev: Option<String>,
+    },
+
+    /// Watch the source tree and regenerate the .env file when variables change
+    Watch {
+        /// Directory to watch (default: current directory)
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+
+        /// Output file name (default: .env)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+
+        /// Configuration file path
+        #[arg(short, long, value_name = "CONFIG")]
+        config: Option<PathBuf>,
+
+        /// Don't merge with existing file (overwrite instead)
+        #[arg(long)]
+        no_merge: bool,
+
+        /// Variables to ignore (can be used multiple times)
+        #[arg(long, value_name = "VARIABLE")]
+        ignore: Vec<String>,
+
+        /// Only scan files reachable from crate roots through `mod` declarations
+        #[arg(long)]
+        module_tree: bool,
+
+        /// Syntax of the generated file (default: dotenvy)
+        #[arg(long, value_enum)]
+        dialect: Option<Dialect>,
+
+        /// Quiet period in milliseconds to wait for after a change before rescanning
+        #[arg(long, value_name = "MILLISECONDS", default_value_t = watch::DEFAULT_DEBOUNCE.as_millis() as u64)]
+        debounce: u64,
+    },
+
+    /// Compare environment variable usage between two git revisions
+    Diff {
+        /// Revision range to compare, e.g. v1.0.0..v1.1.0 (the new side defaults to HEAD)
+        #[arg(value_name = "OLD..NEW")]
+        range: String,
+
+        /// Directory inside the repository to scan (default: current directory)
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+
+        /// Configuration file path
+        #[arg(short, long, value_name = "CONFIG")]
+        config: Option<PathBuf>,
+
+        /// Variables to ignore (can be used multiple times)
+        #[arg(long, value_name = "VARIABLE")]
+        ignore: Vec<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+
+    /// Check that the env file lists the variables the code reads, without writing it
+    ///
+    /// Exits with status 1 if variables are missing from the file or the file
+    /// sets variables the code no longer reads.
+    Check {
+        /// Directory to scan (default: current directory)
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+
+        /// File to check (default: .env)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+
+        /// Configuration file path
+        #[arg(short, long, value_name = "CONFIG")]
+        config: Option<PathBuf>,
+
+        /// Variables to ignore (can be used multiple times)
+        #[arg(long, value_name = "VARIABLE")]
+        ignore: Vec<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+
+    /// Validate an env file or the process environment against what the code reads
+    ///
+    /// Exits with status 3 if required variables are missing or empty, 4 if
+    /// values don't parse as their type and 5 if the only problem is keys the
+    /// code doesn't read.
+    Validate {
+        /// Directory to scan (default: current directory)
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+
+        /// Env file to validate
+        #[arg(
+            long,
+            value_name = "FILE",
+            required_unless_present = "from_process_env"
+        )]
+        env_file: Option<PathBuf>,
+
+        /// Validate the environment of this process instead of a file
+        #[arg(long, conflicts_with = "env_file")]
+        from_process_env: bool,
+
+        /// Configuration file path
+        #[arg(short, long, value_name = "CONFIG")]
+        config: Option<PathBuf>,
+
+        /// Variables to ignore (can be used multiple times)
+        #[arg(long, value_name = "VARIABLE")]
+        ignore: Vec<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+
+    /// Print the values of an env file with references to other variables expanded
+    ///
+    /// Exits with status 4 if references can't be resolved.
+    Resolve {
+        /// Env file to expand
+        #[arg(long, value_name = "FILE", default_value = ".env")]
+        env_file: PathBuf,
+
+        /// Configuration file path
+        #[arg(short, long, value_name = "CONFIG")]
+        config: Option<PathBuf>,
+
+        /// Syntax of the file and the output (default: dotenvy)
+        #[arg(long, value_enum)]
+        dialect: Option<Dialect>,
+
+        /// Don't look up variables in the process environment
+        #[arg(long)]
+        no_process_env: bool,
+
+        /// Output format: an env file or a JSON object
+        #[arg(long, value_enum, default_value = "env")]
+        format: ResolveFormat,
+    },
+
+    /// Put back a backup of an env file made by `generate --backup`
+    Restore {
+        /// Directory containing the file (default: current directory)
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+
+        /// File to restore (default: .env)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+
+        /// Configuration file path
+        #[arg(short, long, value_name = "CONFIG")]
+        config: Option<PathBuf>,
+
+        /// Backup to restore (default: the newest one)
+        #[arg(long, value_name = "BACKUP", conflicts_with = "list")]
+        from: Option<PathBuf>,
+
+        /// List the backups, newest first, instead of restoring one
+        #[arg(long)]
+        list: bool,
+    },
+
+    /// List the built-in catalogue of well-known variables
+    Catalogue {
+        /// Only list variables of this category
+        #[arg(long, value_enum)]
+        category: Option<Category>,
     },
 
     /// Show current configuration
@@ -84,52 +393,761 @@ enum Commands {
         config: Option<PathBuf>,
     },
 
-    /// Create a sample configuration file
-    InitConfig {
-        /// Output path for config file (default: autoenv.toml)
-        #[arg(value_name = "FILE")]
-        output: Option<PathBuf>,
-    },
-}
+    /// Create a sample configuration file
+    InitConfig {
+        /// Output path for config file (default: autoenv.toml)
+        #[arg(value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Output format for reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ReportFormat {
+    Text,
+    Json,
+    Markdown,
+}
+
+/// How `resolve` prints the expanded values
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ResolveFormat {
+    Env,
+    Json,
+}
+
+/// How `generate --dry-run` shows the changes it would make
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum DiffFormat {
+    Unified,
+    Json,
+}
+
+/// Output of `generate --dry-run --diff-format json`
+#[derive(Serialize)]
+struct DryRunReport {
+    files: Vec<KeyChanges>,
+}
+
+/// Print the files a dry run would have written, with paths relative to `scan_path`
+fn print_planned_writes(
+    planned: &[PlannedWrite],
+    scan_path: &Path,
+    format: DiffFormat,
+    dialect: Dialect,
+) -> Result<()> {
+    let planned: Vec<PlannedWrite> = planned
+        .iter()
+        .map(|write| PlannedWrite {
+            path: write
+                .path
+                .strip_prefix(scan_path)
+                .unwrap_or(&write.path)
+                .to_path_buf(),
+            ..write.clone()
+        })
+        .collect();
+    match format {
+        DiffFormat::Unified => {
+            let changed: Vec<_> = planned.iter().filter(|write| write.is_change()).collect();
+            if changed.is_empty() {
+                println!("No changes.");
+            }
+            for write in changed {
+                print!("{}", write.unified_diff());
+            }
+        }
+        DiffFormat::Json => {
+            let files: Vec<KeyChanges> = planned
+                .iter()
+                .filter(|write| write.is_change())
+                .map(|write| write.key_changes(dialect))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&DryRunReport { files })?);
+        }
+    }
+    Ok(())
+}
+
+/// Parse a source language given on the command line
+fn parse_language(value: &str) -> Result<FileKind, String> {
+    match FileKind::from_str(value, true) {
+        Ok(kind) if !kind.is_deployment() => Ok(kind),
+        _ => Err("expected one of: rust, python, javascript, go".to_string()),
+    }
+}
+
+/// Split a revision range `OLD..NEW` into its sides; a missing new side means `HEAD`
+fn parse_range(range: &str) -> Result<(String, String)> {
+    let (old, new) = range.split_once("..").unwrap_or((range, ""));
+    if old.is_empty() || new.starts_with('.') {
+        anyhow::bail!("Invalid revision range: {} (expected OLD..NEW)", range);
+    }
+    let new = if new.is_empty() { "HEAD" } else { new };
+    Ok((old.to_string(), new.to_string()))
+}
+
+/// Add the variables given with `--ignore` to those ignored by the configuration
+fn apply_ignore(config: &mut Config, ignore: Vec<String>) {
+    if !ignore.is_empty() {
+        config.ignore.get_or_insert_with(Vec::new).extend(ignore);
+    }
+}
+
+/// Load the configuration file given on the command line, falling back to
+/// `autoenv.toml` in the scanned directory and then to the defaults
+fn load_config(config: Option<PathBuf>, scan_path: &Path, verbose: bool) -> Result<Config> {
+    if let Some(config_path) = config {
+        if verbose {
+            println!("Loading config from: {}", config_path.display());
+        }
+        return EnvScanner::load_config(config_path).context("Failed to load configuration file");
+    }
+
+    let default_config = scan_path.join("autoenv.toml");
+    if default_config.exists() {
+        if verbose {
+            println!("Loading default config: {}", default_config.display());
+        }
+        EnvScanner::load_config(default_config)
+    } else {
+        Ok(Config::default())
+    }
+}
+
+/// Write the env file(s) for `scan_path` in the configured layout
+///
+/// With `report` unset nothing but verbose output is printed, for dry runs
+/// whose planned writes are shown afterwards.
+fn generate_files(
+    scanner: &EnvScanner,
+    config: Config,
+    scan_path: &Path,
+    verbose: bool,
+    report: bool,
+) -> Result<()> {
+    let layout = config.workspace_output.unwrap_or_default();
+    if layout == WorkspaceOutput::PerBinary {
+        let output_file = config.output.unwrap_or_else(|| ".env".to_string());
+        let written = modules::generate_per_binary(scanner, scan_path, &output_file)
+            .context("Failed to generate .env files")?;
+        if report {
+            if written.is_empty() {
+                println!("No binary targets found.");
+            }
+            for (path, count) in written {
+                println!("Generated {} with {} variables", path.display(), count);
+            }
+        }
+        return Ok(());
+    }
+    if layout != WorkspaceOutput::Combined {
+        let workspace = Workspace::discover(scan_path).context("Failed to read workspace")?;
+        if workspace.is_multi_package() {
+            let output_file = config.output.unwrap_or_else(|| ".env".to_string());
+            return generate_for_workspace(
+                scanner,
+                scan_path,
+                &workspace,
+                layout,
+                &output_file,
+                verbose,
+                report,
+            );
+        }
+        if verbose {
+            println!("Not a multi-crate workspace, generating a single file");
+        }
+    }
+
+    let plan = scanner
+        .plan_directory(scan_path)
+        .context("Failed to scan directory")?;
+
+    if plan.is_empty() {
+        if report {
+            println!("No environment variables found.");
+        }
+        return Ok(());
+    }
+
+    if verbose {
+        println!("Found {} environment variables:", plan.variables.len());
+        let mut sorted_vars: Vec<_> = plan.variables.iter().collect();
+        sorted_vars.sort();
+        for var in sorted_vars {
+            println!("  - {}", var);
+        }
+        for section in &plan.sections {
+            println!("{}:", section.title);
+            for entry in &section.entries {
+                println!("  - {}", entry.name);
+            }
+        }
+    }
+
+    // Generate .env file
+    let output_file = config.output.unwrap_or_else(|| ".env".to_string());
+    let output_path = scan_path.join(&output_file);
+
+    let stale = scanner
+        .stale_variables(&plan, &output_path)
+        .context("Failed to read existing .env file")?;
+
+    scanner
+        .generate_annotated_env_file(&plan.variables, &plan.sections, &output_path)
+        .context("Failed to generate .env file")?;
+
+    if report {
+        println!("Generated {} with {} variables", output_file, plan.len());
+        print_stale(&stale, config.stale.unwrap_or_default(), &output_file);
+    }
+    if verbose {
+        println!("Output path: {}", output_path.display());
+    }
+
+    Ok(())
+}
+
+/// Generate env files for a multi-crate workspace in the given layout
+fn generate_for_workspace(
+    scanner: &EnvScanner,
+    scan_path: &Path,
+    workspace: &Workspace,
+    layout: WorkspaceOutput,
+    output_file: &str,
+    verbose: bool,
+    report: bool,
+) -> Result<()> {
+    if verbose {
+        println!(
+            "Scanning {} crates in {}",
+            workspace.packages.len(),
+            scan_path.display()
+        );
+    }
+
+    let written = match layout {
+        WorkspaceOutput::PerCrate => {
+            workspace::generate_per_crate(scanner, scan_path, workspace, output_file)
+                .context("Failed to generate .env files")?
+        }
+        _ => {
+            let output_path = scan_path.join(output_file);
+            let count = workspace::generate_sections(scanner, scan_path, workspace, &output_path)
+                .context("Failed to generate .env file")?;
+            if count == 0 {
+                Vec::new()
+            } else {
+                vec![(output_path, count)]
+            }
+        }
+    };
+
+    if report {
+        if written.is_empty() {
+            println!("No environment variables found.");
+        }
+        for (path, count) in written {
+            println!("Generated {} with {} variables", path.display(), count);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print variables grouped by the workspace crate that reads them
+fn print_by_crate(workspace: &Workspace, variables: &BTreeMap<String, VariableInfo>) {
+    if variables.is_empty() {
+        println!("No environment variables found.");
+        return;
+    }
+
+    println!(
+        "Found {} environment variables in {} crates:",
+        variables.len(),
+        workspace.packages.len()
+    );
+    for group in workspace.attribute(variables) {
+        match &group.package {
+            Some(package) if package.root.as_os_str().is_empty() => {
+                println!("{}:", package.name)
+            }
+            Some(package) => println!("{} ({}):", package.name, package.root.display()),
+            None => println!("(outside any crate):"),
+        }
+        for name in group.variables.keys() {
+            println!("  {}", name);
+        }
+    }
+}
+
+/// Print a matrix of variables (rows) against the binaries needing them (columns)
+fn print_target_matrix(binaries: &[BinaryVariables]) {
+    if binaries.is_empty() {
+        println!("No binary targets found.");
+        return;
+    }
+
+    let names: BTreeSet<&String> = binaries
+        .iter()
+        .flat_map(|binary| binary.variables.keys())
+        .collect();
+    if names.is_empty() {
+        println!("No environment variables found in binary targets.");
+        return;
+    }
+
+    let width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("VARIABLE".len());
+    let mut header = format!("{:<width$}", "VARIABLE");
+    for binary in binaries {
+        header.push_str(&format!("  {}", binary.name));
+    }
+    println!("{}", header.trim_end());
+
+    for name in names {
+        let mut row = format!("{:<width$}", name);
+        for binary in binaries {
+            let mark = if binary.variables.contains_key(name) {
+                "x"
+            } else {
+                "-"
+            };
+            row.push_str(&format!("  {:<w$}", mark, w = binary.name.len()));
+        }
+        println!("{}", row.trim_end());
+    }
+}
+
+/// Print the variables read by third-party crates
+fn print_dependency_scan(scan: &DependencyScan, show_locations: bool) {
+    println!();
+    if scan.crates.is_empty() {
+        println!(
+            "No environment variables read by {} scanned dependencies.",
+            scan.scanned
+        );
+    } else {
+        println!(
+            "Read by third-party crates ({} of {} scanned dependencies):",
+            scan.crates.len(),
+            scan.scanned
+        );
+        for usage in &scan.crates {
+            println!("  {} {}:", usage.name, usage.version);
+            for info in usage.variables.values() {
+                match info.locations.first() {
+                    Some(location) if show_locations => println!(
+                        "    {} ({}:{})",
+                        info.name,
+                        usage.path.join(&location.file).display(),
+                        location.line
+                    ),
+                    _ => println!("    {}", info.name),
+                }
+            }
+        }
+    }
+
+    if !scan.missing.is_empty() {
+        println!(
+            "No local sources for {} dependencies (run `cargo fetch` or `cargo vendor`):",
+            scan.missing.len()
+        );
+        for package in &scan.missing {
+            println!("  {}", package);
+        }
+    }
+
+    if !scan.failed.is_empty() {
+        println!("Failed to scan {} dependencies:", scan.failed.len());
+        for (package, error) in &scan.failed {
+            println!("  {}: {}", package, error);
+        }
+    }
+}
+
+/// Print the result of scanning files changed relative to a git revision
+fn print_change_report(report: &ChangeReport, base: &ChangeBase) {
+    let description = match base {
+        ChangeBase::Revision(rev) => format!("since {}", rev),
+        ChangeBase::Index => "in the index".to_string(),
+    };
+
+    if report.files.is_empty() {
+        println!("No changed files scanned {}.", description);
+        return;
+    }
+
+    println!(
+        "Scanned {} changed files {}, found {} environment variables:",
+        report.files.len(),
+        description,
+        report.variables.len()
+    );
+    let mut sorted_vars: Vec<_> = report.variables.iter().collect();
+    sorted_vars.sort();
+    for var in sorted_vars {
+        println!("  {}", var);
+    }
+
+    if report.added.is_empty() && report.removed.is_empty() {
+        println!("No variables were introduced or removed.");
+        return;
+    }
+
+    if !report.added.is_empty() {
+        println!("Newly introduced:");
+        for var in &report.added {
+            println!("  + {}", var);
+        }
+    }
+    if !report.removed.is_empty() {
+        println!("No longer used:");
+        for var in &report.removed {
+            println!("  - {}", var);
+        }
+    }
+}
+
+/// Warn about generated files whose values could leak, fixing .gitignore if asked
+fn audit_generated_files(files: &[PathBuf], dialect: Dialect, fix_gitignore: bool) -> Result<()> {
+    for path in files {
+        for issue in secrets::audit(path, dialect)? {
+            if issue == Issue::NotIgnored && fix_gitignore {
+                if secrets::fix_gitignore(path)? {
+                    println!(
+                        "Added {} to {}",
+                        path.display(),
+                        path.with_file_name(".gitignore").display()
+                    );
+                }
+                continue;
+            }
+            eprintln!("Warning: {} {}", path.display(), issue);
+        }
+    }
+    Ok(())
+}
+
+/// Tell what happened to variables the code no longer reads
+fn print_stale(stale: &[String], mode: StaleMode, output_file: &str) {
+    if stale.is_empty() {
+        return;
+    }
+    match mode {
+        StaleMode::Report => println!(
+            "{} variables in {} are no longer read by the code:",
+            stale.len(),
+            output_file
+        ),
+        StaleMode::Comment => println!("Commented out {} unused variables:", stale.len()),
+        StaleMode::Prune => println!("Removed {} unused variables:", stale.len()),
+    }
+    for name in stale {
+        println!("  - {}", name);
+    }
+    if mode == StaleMode::Report {
+        println!("Remove them with --prune or comment them out with --stale comment;");
+        println!("list them in keep_unused to keep them without notice.");
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Generate {
+            path,
+            output,
+            config,
+            no_merge,
+            ignore,
+            no_cache,
+            module_tree,
+            per_crate,
+            sections,
+            per_binary,
+            no_dependencies,
+            no_deployment_files,
+            languages,
+            dialect,
+            prune,
+            stale,
+            backup,
+            fix_gitignore,
+            dry_run,
+            diff_format,
+            verbose,
+        } => {
+            let scan_path = path.unwrap_or_else(|| PathBuf::from("."));
+            // Keep the JSON on stdout parseable
+            let verbose = verbose && !(dry_run && diff_format == DiffFormat::Json);
+
+            if verbose {
+                println!("Scanning directory: {}", scan_path.display());
+            }
+
+            // Load configuration
+            let mut config_obj = load_config(config, &scan_path, verbose)?;
+
+            // Override config with command line arguments
+            if let Some(output_file) = output {
+                config_obj.output = Some(output_file);
+            }
+
+            if no_merge {
+                config_obj.merge_existing = Some(false);
+            }
+
+            if no_cache {
+                config_obj.cache = Some(false);
+            }
+
+            if module_tree {
+                config_obj.module_tree = Some(true);
+            }
+
+            if no_dependencies {
+                config_obj.dependency_variables = Some(false);
+            }
+
+            if no_deployment_files {
+                config_obj.deployment_files = Some(false);
+            }
+
+            if !languages.is_empty() {
+                config_obj.languages = Some(languages);
+            }
+
+            if dialect.is_some() {
+                config_obj.dialect = dialect;
+            }
+
+            if prune {
+                config_obj.stale = Some(StaleMode::Prune);
+            } else if stale.is_some() {
+                config_obj.stale = stale;
+            }
+
+            if backup.is_some() {
+                config_obj.backup = backup;
+            }
+
+            if per_crate {
+                config_obj.workspace_output = Some(WorkspaceOutput::PerCrate);
+            } else if sections {
+                config_obj.workspace_output = Some(WorkspaceOutput::Sections);
+            } else if per_binary {
+                config_obj.workspace_output = Some(WorkspaceOutput::PerBinary);
+            }
+
+            apply_ignore(&mut config_obj, ignore);
+
+            // Create scanner and scan directory
+            let mut scanner = EnvScanner::with_config(config_obj.clone())?;
+            if dry_run {
+                scanner = scanner.dry_run();
+            }
+
+            if verbose {
+                println!("Scanning for environment variables...");
+            }
+
+            generate_files(&scanner, config_obj.clone(), &scan_path, verbose, !dry_run)?;
+
+            if dry_run {
+                print_planned_writes(
+                    &scanner.planned_writes(),
+                    &scan_path,
+                    diff_format,
+                    config_obj.dialect.unwrap_or_default(),
+                )?;
+            } else {
+                audit_generated_files(
+                    &scanner.generated_files(),
+                    config_obj.dialect.unwrap_or_default(),
+                    fix_gitignore,
+                )?;
+            }
+
+            Ok(())
+        }
+
+        Commands::Scan {
+            path,
+            config,
+            ignore,
+            no_cache,
+            module_tree,
+            show_locations,
+            by_crate,
+            no_dependencies,
+            no_deployment_files,
+            languages,
+            third_party,
+            by_target,
+            since,
+            staged,
+            rev,
+        } => {
+            let scan_path = path.unwrap_or_else(|| PathBuf::from("."));
+
+            // Load configuration
+            let mut config_obj = load_config(config, &scan_path, false)?;
+
+            apply_ignore(&mut config_obj, ignore);
+
+            if no_cache {
+                config_obj.cache = Some(false);
+            }
+
+            if module_tree {
+                config_obj.module_tree = Some(true);
+            }
+
+            if no_dependencies {
+                config_obj.dependency_variables = Some(false);
+            }
+
+            if no_deployment_files {
+                config_obj.deployment_files = Some(false);
+            }
+
+            if !languages.is_empty() {
+                config_obj.languages = Some(languages);
+            }
+
+            let scanner = EnvScanner::with_config(config_obj.clone())?;
+
+            let base = match since {
+                Some(rev) => Some(ChangeBase::Revision(rev)),
+                None if staged => Some(ChangeBase::Index),
+                None => None,
+            };
+            if let Some(base) = base {
+                let report = git::scan_changes(&scanner, &scan_path, &base)
+                    .context("Failed to scan changed files")?;
+                print_change_report(&report, &base);
+                return Ok(());
+            }
+
+            if by_target {
+                let binaries = modules::binary_variables(&scanner, &scan_path)
+                    .context("Failed to scan binary targets")?;
+                print_target_matrix(&binaries);
+                return Ok(());
+            }
+
+            if by_crate {
+                let workspace =
+                    Workspace::discover(&scan_path).context("Failed to read workspace")?;
+                let variables = scanner.scan_directory_detailed(&scan_path)?;
+                print_by_crate(&workspace, &variables);
+                return Ok(());
+            }
+
+            // Dependencies and Cargo configuration are only known for the working tree
+            let (variables, implied) = match &rev {
+                Some(rev) => {
+                    let source = GitRevisionSource::new(&scan_path, rev)?;
+                    let variables = scanner
+                        .scan_source_detailed(&source)
+                        .with_context(|| format!("Failed to scan revision {}", rev))?;
+                    (variables, Vec::new())
+                }
+                None => scanner
+                    .scan_directory_with_dependencies(&scan_path)
+                    .context("Failed to read dependencies")?,
+            };
+            let provided = match &rev {
+                Some(_) => BTreeMap::new(),
+                None if config_obj.cargo_provided == Some(ProvidedMode::Keep) => BTreeMap::new(),
+                None => cargo_env::provided_variables(&scan_path)
+                    .context("Failed to read Cargo-provided variables")?,
+            };
+
+            if variables.is_empty() && implied.is_empty() {
+                println!("No environment variables found.");
+                return Ok(());
+            }
+
+            match &rev {
+                Some(rev) => println!(
+                    "Found {} environment variables at {}:",
+                    variables.len(),
+                    rev
+                ),
+                None => println!("Found {} environment variables:", variables.len()),
+            }
+            for (var, info) in &variables {
+                let mut note = provided
+                    .get(var)
+                    .map(|variable| format!(" (provided by {})", variable.describe()))
+                    .unwrap_or_default();
+                if let Some(entry) = catalogue::lookup(var) {
+                    note.push_str(&format!(" [{}]", entry.category));
+                }
+                println!("  {}{}", var, note);
+                if show_locations {
+                    for location in &info.locations {
+                        match location.kind {
+                            Some(kind) => println!(
+                                "    {}:{} ({})",
+                                location.file.display(),
+                                location.line,
+                                kind
+                            ),
+                            None => println!("    {}:{}", location.file.display(), location.line),
+                        }
+                    }
+                }
+            }
+
+            let implied: Vec<_> = implied
+                .iter()
+                .filter(|variable| !variables.contains_key(&variable.name))
+                .collect();
+            if !implied.is_empty() {
+                println!("Read by dependencies:");
+                for variable in implied {
+                    match &variable.description {
+                        Some(description) => println!(
+                            "  {} ({}: {})",
+                            variable.name, variable.dependency, description
+                        ),
+                        None => println!("  {} ({})", variable.name, variable.dependency),
+                    }
+                }
+            }
+
+            if third_party {
+                let scan = vendor::scan_dependencies(&scanner, &scan_path)
+                    .context("Failed to scan dependency sources")?;
+                print_dependency_scan(&scan, show_locations);
+            }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+            Ok(())
+        }
 
-    match cli.command {
-        Commands::Generate {
+        Commands::Watch {
             path,
             output,
             config,
             no_merge,
             ignore,
-            verbose,
+            module_tree,
+            dialect,
+            debounce,
         } => {
             let scan_path = path.unwrap_or_else(|| PathBuf::from("."));
+            let mut config_obj = load_config(config, &scan_path, false)?;
 
-            if verbose {
-                println!("Scanning directory: {}", scan_path.display());
-            }
-
-            // Load configuration
-            let mut config_obj = if let Some(config_path) = config {
-                if verbose {
-                    println!("Loading config from: {}", config_path.display());
-                }
-                EnvScanner::load_config(config_path).context("Failed to load configuration file")?
-            } else {
-                // Try to load default config file if it exists
-                let default_config = scan_path.join("autoenv.toml");
-                if default_config.exists() {
-                    if verbose {
-                        println!("Loading default config: {}", default_config.display());
-                    }
-                    EnvScanner::load_config(default_config)?
-                } else {
-                    Config::default()
-                }
-            };
-
-            // Override config with command line arguments
             if let Some(output_file) = output {
                 config_obj.output = Some(output_file);
             }
@@ -138,102 +1156,266 @@ fn main() -> Result<()> {
                 config_obj.merge_existing = Some(false);
             }
 
-            if !ignore.is_empty() {
-                let mut ignore_list = config_obj.ignore.unwrap_or_default();
-                ignore_list.extend(ignore);
-                config_obj.ignore = Some(ignore_list);
-            }
-
-            // Create scanner and scan directory
-            let scanner = EnvScanner::with_config(config_obj.clone())?;
-
-            if verbose {
-                println!("Scanning for environment variables...");
+            if dialect.is_some() {
+                config_obj.dialect = dialect;
             }
 
-            let variables = scanner
-                .scan_directory(&scan_path)
-                .context("Failed to scan directory")?;
+            apply_ignore(&mut config_obj, ignore);
 
-            if variables.is_empty() {
-                println!("No environment variables found in Rust files.");
-                return Ok(());
+            if module_tree {
+                config_obj.module_tree = Some(true);
             }
 
-            if verbose {
-                println!("Found {} environment variables:", variables.len());
-                let mut sorted_vars: Vec<_> = variables.iter().collect();
-                sorted_vars.sort();
-                for var in sorted_vars {
-                    println!("  - {}", var);
+            // Watch keeps a single file up to date; a layout that `generate`
+            // would split over several files is refused rather than ignored.
+            let layout = config_obj.workspace_output.unwrap_or_default();
+            let split = match layout {
+                WorkspaceOutput::Combined => None,
+                WorkspaceOutput::PerBinary => Some("per-binary"),
+                WorkspaceOutput::PerCrate | WorkspaceOutput::Sections => {
+                    let workspace =
+                        Workspace::discover(&scan_path).context("Failed to read workspace")?;
+                    workspace
+                        .is_multi_package()
+                        .then_some(if layout == WorkspaceOutput::PerCrate {
+                            "per-crate"
+                        } else {
+                            "sections"
+                        })
                 }
+            };
+            if let Some(name) = split {
+                anyhow::bail!(
+                    "watch only keeps a single combined file up to date, but workspace_output is \"{}\"; use `autoenv generate` for this layout",
+                    name
+                );
             }
 
-            // Generate .env file
+            let scanner = EnvScanner::with_config(config_obj.clone())?;
             let output_file = config_obj.output.unwrap_or_else(|| ".env".to_string());
             let output_path = scan_path.join(&output_file);
 
-            scanner
-                .generate_env_file(&variables, &output_path)
-                .context("Failed to generate .env file")?;
-
             println!(
-                "Generated {} with {} variables",
-                output_file,
-                variables.len()
+                "Watching {} for changes (press Ctrl+C to stop)",
+                scan_path.display()
             );
-            if verbose {
-                println!("Output path: {}", output_path.display());
+
+            watch::watch_directory(
+                &scanner,
+                &scan_path,
+                &output_path,
+                Duration::from_millis(debounce),
+                |changes: &VariableChanges| {
+                    println!("Updated {}:", output_file);
+                    for var in &changes.added {
+                        println!("  + {}", var);
+                    }
+                    for var in &changes.removed {
+                        println!("  - {}", var);
+                    }
+                    ControlFlow::Continue(())
+                },
+                |err| eprintln!("Failed to update {}: {:#}", output_file, err),
+            )
+            .context("Failed to watch directory")
+        }
+
+        Commands::Diff {
+            range,
+            path,
+            config,
+            ignore,
+            format,
+        } => {
+            let scan_path = path.unwrap_or_else(|| PathBuf::from("."));
+            let (old_rev, new_rev) = parse_range(&range)?;
+            let mut config_obj = load_config(config, &scan_path, false)?;
+
+            apply_ignore(&mut config_obj, ignore);
+
+            let scanner = EnvScanner::with_config(config_obj)?;
+            let changelog = changelog::diff_revisions(&scanner, &scan_path, &old_rev, &new_rev)?;
+
+            match format {
+                ReportFormat::Text => print!("{}", changelog.to_text()),
+                ReportFormat::Json => println!("{}", changelog.to_json()?),
+                ReportFormat::Markdown => print!("{}", changelog.to_markdown()),
             }
 
             Ok(())
         }
 
-        Commands::Scan {
+        Commands::Check {
             path,
+            output,
             config,
             ignore,
-            show_locations,
+            format,
         } => {
             let scan_path = path.unwrap_or_else(|| PathBuf::from("."));
+            let mut config_obj = load_config(config, &scan_path, false)?;
 
-            // Load configuration
-            let mut config_obj = if let Some(config_path) = config {
-                EnvScanner::load_config(config_path)?
-            } else {
-                let default_config = scan_path.join("autoenv.toml");
-                if default_config.exists() {
-                    EnvScanner::load_config(default_config)?
+            if let Some(output_file) = output {
+                config_obj.output = Some(output_file);
+            }
+
+            apply_ignore(&mut config_obj, ignore);
+
+            let output_file = config_obj
+                .output
+                .clone()
+                .unwrap_or_else(|| ".env".to_string());
+            let scanner = EnvScanner::with_config(config_obj)?;
+            let mut report =
+                check::check_directory(&scanner, &scan_path, scan_path.join(&output_file))
+                    .context("Failed to check env file")?;
+            report.path = PathBuf::from(output_file);
+
+            match format {
+                ReportFormat::Text => print!("{}", report.to_text()),
+                ReportFormat::Json => println!("{}", report.to_json()?),
+                ReportFormat::Markdown => print!("{}", report.to_markdown()),
+            }
+
+            if !report.is_up_to_date() {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+
+        Commands::Validate {
+            path,
+            env_file,
+            from_process_env,
+            config,
+            ignore,
+            format,
+        } => {
+            let scan_path = path.unwrap_or_else(|| PathBuf::from("."));
+            let mut config_obj = load_config(config, &scan_path, false)?;
+
+            apply_ignore(&mut config_obj, ignore);
+
+            let scanner = EnvScanner::with_config(config_obj)?;
+            let report = match env_file {
+                Some(env_file) if !from_process_env => {
+                    validate::validate_env_file(&scanner, &scan_path, &env_file)
+                }
+                _ => {
+                    // Everything else in the process environment would be reported as unknown
+                    let values = validate::process_env();
+                    validate::validate_directory(&scanner, &scan_path, &values, false).map(
+                        |report| validate::ValidationReport {
+                            source: "process environment".to_string(),
+                            ..report
+                        },
+                    )
+                }
+            }
+            .context("Failed to validate environment")?;
+
+            match format {
+                ReportFormat::Text => print!("{}", report.to_text()),
+                ReportFormat::Json => println!("{}", report.to_json()?),
+                ReportFormat::Markdown => print!("{}", report.to_markdown()),
+            }
+
+            let code = report.exit_code();
+            if code != 0 {
+                std::process::exit(code);
+            }
+            Ok(())
+        }
+
+        Commands::Resolve {
+            env_file,
+            config,
+            dialect,
+            no_process_env,
+            format,
+        } => {
+            let config_obj = load_config(config, Path::new("."), false)?;
+            let dialect = dialect.or(config_obj.dialect).unwrap_or_default();
+            let document = Document::read_as(&env_file, dialect)?;
+            let entries: Vec<_> = document.entries().cloned().collect();
+
+            let process_env = |name: &str| {
+                if no_process_env {
+                    None
                 } else {
-                    Config::default()
+                    std::env::var(name).ok()
                 }
             };
+            let resolved = interpolate::resolve(&entries, &process_env);
 
-            if !ignore.is_empty() {
-                let mut ignore_list = config_obj.ignore.unwrap_or_default();
-                ignore_list.extend(ignore);
-                config_obj.ignore = Some(ignore_list);
+            match format {
+                ResolveFormat::Env => {
+                    for (key, value) in &resolved.values {
+                        println!("{}={}", key, dotenv::quote(value, dialect)?);
+                    }
+                }
+                ResolveFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&resolved.values)
+                        .context("Failed to serialize values")?
+                ),
             }
 
-            let scanner = EnvScanner::with_config(config_obj)?;
-            let variables = scanner.scan_directory(&scan_path)?;
+            for problem in &resolved.problems {
+                eprintln!("Error: {}", problem);
+            }
+            if !resolved.problems.is_empty() {
+                std::process::exit(validate::EXIT_INVALID);
+            }
+            Ok(())
+        }
+
+        Commands::Restore {
+            path,
+            output,
+            config,
+            from,
+            list,
+        } => {
+            let scan_path = path.unwrap_or_else(|| PathBuf::from("."));
+            let config_obj = load_config(config, &scan_path, false)?;
+            let output_file = output
+                .or(config_obj.output)
+                .unwrap_or_else(|| ".env".to_string());
+            let output_path = scan_path.join(&output_file);
 
-            if variables.is_empty() {
-                println!("No environment variables found in Rust files.");
+            if list {
+                let backups = backup::list_backups(&output_path)?;
+                if backups.is_empty() {
+                    println!("No backups of {} found.", output_file);
+                }
+                for backup in backups {
+                    println!("{}", backup.display());
+                }
                 return Ok(());
             }
 
-            println!("Found {} environment variables:", variables.len());
-            let mut sorted_vars: Vec<_> = variables.iter().collect();
-            sorted_vars.sort();
+            let restored = backup::restore(&output_path, from.as_deref())
+                .with_context(|| format!("Failed to restore {}", output_file))?;
+            println!("Restored {} from {}", output_file, restored.display());
+            Ok(())
+        }
 
-            for var in sorted_vars {
-                if show_locations {
-                    // TODO: Implement location tracking for detailed output
-                    println!("  {}", var);
-                } else {
-                    println!("  {}", var);
+        Commands::Catalogue { category } => {
+            println!(
+                "Well-known variables (catalogue version {}):",
+                catalogue::CATALOGUE_VERSION
+            );
+            for entry in catalogue::entries() {
+                if category.is_some_and(|category| category != entry.category) {
+                    continue;
                 }
+                println!(
+                    "  {:<24} {:<7} {}",
+                    entry.name,
+                    entry.category.to_string(),
+                    entry.description
+                );
             }
 
             Ok(())
@@ -283,12 +1465,9 @@ fn main() -> Result<()> {
             let default_config = Config {
                 output: Some(".env".to_string()),
                 merge_existing: Some(true),
-                ignore: Some(vec![
-                    // Common variables that might not need to be in .env
-                    "HOME".to_string(),
-                    "PATH".to_string(),
-                    "USER".to_string(),
-                ]),
+                // Well-known variables such as HOME are handled by the catalogue
+                ignore: Some(Vec::new()),
+                ..Default::default()
             };
 
             let toml_content = toml::to_string_pretty(&default_config)
@@ -310,6 +1489,7 @@ fn main() -> Result<()> {
             println!("  - output: Name of the generated file");
             println!("  - merge_existing: Whether to preserve existing values");
             println!("  - ignore: List of variables to skip");
+            println!("  - catalogue: What to do with well-known variables per category");
 
             Ok(())
         }
@@ -328,7 +1508,7 @@ mod tests {
 
     #[test]
     fn test_generate_command_parsing() {
-        let cmd = Cli::try_parse_from(&[
+        let cmd = Cli::try_parse_from([
             "autoenv",
             "generate",
             "./test-dir",
@@ -360,9 +1540,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_watch_command_parsing() {
+        let cmd = Cli::try_parse_from(["autoenv", "watch", "./src", "--debounce", "500"]);
+
+        assert!(cmd.is_ok());
+
+        if let Commands::Watch { path, debounce, .. } = cmd.unwrap().command {
+            assert_eq!(path, Some(PathBuf::from("./src")));
+            assert_eq!(debounce, 500);
+        } else {
+            panic!("Expected Watch command");
+        }
+    }
+
+    #[test]
+    fn test_diff_command_parsing() {
+        let cmd =
+            Cli::try_parse_from(["autoenv", "diff", "v1.0.0..v1.1.0", "--format", "markdown"]);
+
+        if let Commands::Diff { range, format, .. } = cmd.unwrap().command {
+            assert_eq!(range, "v1.0.0..v1.1.0");
+            assert_eq!(format, ReportFormat::Markdown);
+        } else {
+            panic!("Expected Diff command");
+        }
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            parse_range("v1..v2").unwrap(),
+            ("v1".to_string(), "v2".to_string())
+        );
+        assert_eq!(
+            parse_range("v1..").unwrap(),
+            ("v1".to_string(), "HEAD".to_string())
+        );
+        assert_eq!(
+            parse_range("main").unwrap(),
+            ("main".to_string(), "HEAD".to_string())
+        );
+        assert!(parse_range("..v2").is_err());
+        assert!(parse_range("v1...v2").is_err());
+    }
+
     #[test]
     fn test_scan_command_parsing() {
-        let cmd = Cli::try_parse_from(&["autoenv", "scan", "--show-locations"]);
+        let cmd = Cli::try_parse_from(["autoenv", "scan", "--show-locations"]);
 
         assert!(cmd.is_ok());
 
@@ -372,4 +1597,253 @@ mod tests {
             panic!("Expected Scan command");
         }
     }
+
+    #[test]
+    fn test_scan_since_conflicts_with_staged() {
+        let cmd = Cli::try_parse_from(["autoenv", "scan", "--since", "main"]);
+        if let Commands::Scan { since, staged, .. } = cmd.unwrap().command {
+            assert_eq!(since, Some("main".to_string()));
+            assert!(!staged);
+        } else {
+            panic!("Expected Scan command");
+        }
+
+        let cmd = Cli::try_parse_from(["autoenv", "scan", "--since", "main", "--staged"]);
+        assert!(cmd.is_err());
+
+        let cmd = Cli::try_parse_from(["autoenv", "scan", "--rev", "v1.2.0", "--staged"]);
+        assert!(cmd.is_err());
+    }
+
+    #[test]
+    fn test_generate_workspace_layout_flags() {
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--per-crate"]);
+        if let Commands::Generate {
+            per_crate,
+            sections,
+            ..
+        } = cmd.unwrap().command
+        {
+            assert!(per_crate);
+            assert!(!sections);
+        } else {
+            panic!("Expected Generate command");
+        }
+
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--per-crate", "--sections"]);
+        assert!(cmd.is_err());
+
+        let cmd = Cli::try_parse_from(["autoenv", "scan", "--by-crate", "--rev", "HEAD"]);
+        assert!(cmd.is_err());
+
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--per-binary", "--sections"]);
+        assert!(cmd.is_err());
+
+        let cmd = Cli::try_parse_from(["autoenv", "scan", "--by-target"]);
+        if let Commands::Scan { by_target, .. } = cmd.unwrap().command {
+            assert!(by_target);
+        } else {
+            panic!("Expected Scan command");
+        }
+    }
+
+    #[test]
+    fn test_catalogue_command_parsing() {
+        let cmd = Cli::try_parse_from(["autoenv", "catalogue", "--category", "ci"]);
+        if let Commands::Catalogue { category } = cmd.unwrap().command {
+            assert_eq!(category, Some(Category::Ci));
+        } else {
+            panic!("Expected Catalogue command");
+        }
+
+        let cmd = Cli::try_parse_from(["autoenv", "catalogue", "--category", "unknown"]);
+        assert!(cmd.is_err());
+    }
+
+    #[test]
+    fn test_language_flags() {
+        let cmd = Cli::try_parse_from([
+            "autoenv",
+            "generate",
+            "--language",
+            "rust",
+            "--language",
+            "javascript",
+        ]);
+        if let Commands::Generate { languages, .. } = cmd.unwrap().command {
+            assert_eq!(languages, vec![FileKind::Rust, FileKind::JavaScript]);
+        } else {
+            panic!("Expected Generate command");
+        }
+
+        let cmd = Cli::try_parse_from(["autoenv", "scan", "--language", "shell"]);
+        assert!(cmd.is_err());
+    }
+
+    #[test]
+    fn test_dialect_flag() {
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--dialect", "docker"]);
+        if let Commands::Generate { dialect, .. } = cmd.unwrap().command {
+            assert_eq!(dialect, Some(Dialect::Docker));
+        } else {
+            panic!("Expected Generate command");
+        }
+
+        let cmd = Cli::try_parse_from(["autoenv", "watch", "--dialect", "yaml"]);
+        assert!(cmd.is_err());
+    }
+
+    #[test]
+    fn test_stale_flags() {
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--stale", "comment"]);
+        if let Commands::Generate { stale, prune, .. } = cmd.unwrap().command {
+            assert_eq!(stale, Some(StaleMode::Comment));
+            assert!(!prune);
+        } else {
+            panic!("Expected Generate command");
+        }
+
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--prune", "--stale", "report"]);
+        assert!(cmd.is_err());
+    }
+
+    #[test]
+    fn test_dry_run_flags() {
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--dry-run"]);
+        if let Commands::Generate {
+            dry_run,
+            diff_format,
+            ..
+        } = cmd.unwrap().command
+        {
+            assert!(dry_run);
+            assert_eq!(diff_format, DiffFormat::Unified);
+        } else {
+            panic!("Expected Generate command");
+        }
+
+        let cmd =
+            Cli::try_parse_from(["autoenv", "generate", "--dry-run", "--diff-format", "json"]);
+        assert!(cmd.is_ok());
+
+        // The format only makes sense for a dry run
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--diff-format", "json"]);
+        assert!(cmd.is_err());
+    }
+
+    #[test]
+    fn test_check_command() {
+        let cmd = Cli::try_parse_from([
+            "autoenv",
+            "check",
+            "-o",
+            ".env.example",
+            "--format",
+            "markdown",
+        ]);
+        if let Commands::Check {
+            path,
+            output,
+            format,
+            ..
+        } = cmd.unwrap().command
+        {
+            assert_eq!(path, None);
+            assert_eq!(output.as_deref(), Some(".env.example"));
+            assert_eq!(format, ReportFormat::Markdown);
+        } else {
+            panic!("Expected Check command");
+        }
+    }
+
+    #[test]
+    fn test_validate_command() {
+        let cmd = Cli::try_parse_from(["autoenv", "validate", "--env-file", ".env.production"]);
+        if let Commands::Validate {
+            env_file,
+            from_process_env,
+            ..
+        } = cmd.unwrap().command
+        {
+            assert_eq!(env_file, Some(PathBuf::from(".env.production")));
+            assert!(!from_process_env);
+        } else {
+            panic!("Expected Validate command");
+        }
+
+        assert!(Cli::try_parse_from(["autoenv", "validate", "--from-process-env"]).is_ok());
+        // One source of values is required, and only one
+        assert!(Cli::try_parse_from(["autoenv", "validate"]).is_err());
+        assert!(
+            Cli::try_parse_from([
+                "autoenv",
+                "validate",
+                "--env-file",
+                ".env",
+                "--from-process-env"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_backup_and_restore_commands() {
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--backup", "timestamped"]);
+        if let Commands::Generate { backup, .. } = cmd.unwrap().command {
+            assert_eq!(backup, Some(BackupMode::Timestamped));
+        } else {
+            panic!("Expected Generate command");
+        }
+
+        let cmd = Cli::try_parse_from(["autoenv", "restore", "--from", ".env.bak"]);
+        if let Commands::Restore { from, list, .. } = cmd.unwrap().command {
+            assert_eq!(from, Some(PathBuf::from(".env.bak")));
+            assert!(!list);
+        } else {
+            panic!("Expected Restore command");
+        }
+        assert!(Cli::try_parse_from(["autoenv", "restore", "--list", "--from", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_fix_gitignore_flag() {
+        let cmd = Cli::try_parse_from(["autoenv", "generate", "--fix-gitignore"]);
+        if let Commands::Generate { fix_gitignore, .. } = cmd.unwrap().command {
+            assert!(fix_gitignore);
+        } else {
+            panic!("Expected Generate command");
+        }
+        assert!(
+            Cli::try_parse_from(["autoenv", "generate", "--fix-gitignore", "--dry-run"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_resolve_command() {
+        let cmd = Cli::try_parse_from(["autoenv", "resolve"]);
+        if let Commands::Resolve {
+            env_file,
+            no_process_env,
+            format,
+            ..
+        } = cmd.unwrap().command
+        {
+            assert_eq!(env_file, PathBuf::from(".env"));
+            assert!(!no_process_env);
+            assert_eq!(format, ResolveFormat::Env);
+        } else {
+            panic!("Expected Resolve command");
+        }
+
+        let cmd = Cli::try_parse_from([
+            "autoenv",
+            "resolve",
+            "--env-file",
+            ".env.production",
+            "--no-process-env",
+            "--format",
+            "json",
+        ]);
+        assert!(cmd.is_ok());
+    }
 }
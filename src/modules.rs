@@ -0,0 +1,881 @@
+//! Module tree discovery from crate roots
+//!
+//! Instead of picking up every `.rs` file on disk, [`ModuleTreeSource`] starts
+//! from the real roots of each target (`src/lib.rs`, `src/main.rs`,
+//! `src/bin/*`, `[[bin]] path`, `build.rs`) and follows `mod` declarations,
+//! `#[path]` attributes and `include!` so that only compiled code is scanned.
+//! Modules behind `#[cfg]` are followed as well, since any of them may be
+//! compiled in some configuration.
+
+use crate::source::Source;
+use crate::workspace::{Package, Workspace};
+use crate::{EnvScanner, FileScan, VariableInfo, dependencies};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Kind of a compilation target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TargetKind {
+    /// The library target
+    Lib,
+    /// A binary target
+    Bin,
+    /// The build script
+    BuildScript,
+}
+
+/// A compilation target of a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    /// Name of the target, e.g. the binary name
+    pub name: String,
+    /// What kind of target this is
+    pub kind: TargetKind,
+    /// Root source file, relative to the scanned directory
+    pub root: PathBuf,
+}
+
+/// A target together with the files compiled into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetFiles {
+    /// The compilation target
+    pub target: Target,
+    /// Files compiled into the target, relative to the scanned directory
+    pub files: Vec<PathBuf>,
+}
+
+/// Variables needed by a single binary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BinaryVariables {
+    /// Name of the binary
+    pub name: String,
+    /// Variables read by code compiled into the binary, with locations limited
+    /// to those files
+    pub variables: BTreeMap<String, VariableInfo>,
+}
+
+/// Token of the simplified Rust lexer used to find module declarations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token {
+    Ident(String),
+    Str(String),
+    Punct(char),
+}
+
+/// Split Rust source into identifiers, string literals and punctuation,
+/// skipping comments, character literals and lifetimes
+pub(crate) fn tokenize(source: &str) -> Vec<Token> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    // Read a string literal whose opening quote is at `start`; returns its content
+    // and the index after the closing quote
+    let read_string = |start: usize, hashes: Option<usize>| -> (String, usize) {
+        let mut content = String::new();
+        let mut j = start + 1;
+        while j < chars.len() {
+            match hashes {
+                Some(count) => {
+                    if chars[j] == '"' && chars[j + 1..].iter().take(count).all(|c| *c == '#') {
+                        return (content, (j + 1 + count).min(chars.len()));
+                    }
+                }
+                None => {
+                    if chars[j] == '\\' {
+                        if let Some(&next) = chars.get(j + 1) {
+                            content.push(match next {
+                                'n' => '\n',
+                                't' => '\t',
+                                other => other,
+                            });
+                        }
+                        j += 2;
+                        continue;
+                    }
+                    if chars[j] == '"' {
+                        return (content, j + 1);
+                    }
+                }
+            }
+            content.push(chars[j]);
+            j += 1;
+        }
+        (content, j)
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            let mut depth = 0;
+            while i < chars.len() {
+                if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        } else if c == '"' {
+            let (content, next) = read_string(i, None);
+            tokens.push(Token::Str(content));
+            i = next;
+        } else if c == '\'' {
+            // Character literal or lifetime
+            if chars.get(i + 1) == Some(&'\\') {
+                i += 2;
+                while i < chars.len() && chars[i] != '\'' {
+                    i += 1;
+                }
+                i += 1;
+            } else if chars.get(i + 2) == Some(&'\'') {
+                i += 3;
+            } else {
+                i += 1;
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let ident: String = chars[start..i].iter().collect();
+
+            // String literal prefixes: raw, byte and C strings
+            if matches!(ident.as_str(), "r" | "br" | "cr")
+                && matches!(chars.get(i), Some('"') | Some('#'))
+            {
+                let hashes = chars[i..].iter().take_while(|c| **c == '#').count();
+                if chars.get(i + hashes) == Some(&'"') {
+                    let (content, next) = read_string(i + hashes, Some(hashes));
+                    tokens.push(Token::Str(content));
+                    i = next;
+                    continue;
+                }
+            }
+            if matches!(ident.as_str(), "b" | "c") && chars.get(i) == Some(&'"') {
+                let (content, next) = read_string(i, None);
+                tokens.push(Token::Str(content));
+                i = next;
+                continue;
+            }
+
+            tokens.push(Token::Ident(ident));
+        } else {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+/// Whether the source refers to an identifier, e.g. a crate name in a path
+fn mentions_ident(source: &str, name: &str) -> bool {
+    tokenize(source)
+        .iter()
+        .any(|token| matches!(token, Token::Ident(ident) if ident == name))
+}
+
+/// Whether a module file owns its directory for nested modules
+fn is_mod_rs(file: &Path) -> bool {
+    file.file_name().is_some_and(|name| name == "mod.rs")
+}
+
+/// Resolve `mod name;` inside `dir` to `dir/name.rs` or `dir/name/mod.rs`
+fn resolve_module(dir: &Path, name: &str) -> Option<PathBuf> {
+    let name = name.strip_prefix("r#").unwrap_or(name);
+    [
+        dir.join(format!("{}.rs", name)),
+        dir.join(name).join("mod.rs"),
+    ]
+    .into_iter()
+    .find(|candidate| candidate.is_file())
+}
+
+/// Files referenced by one source file
+#[derive(Debug, Default, PartialEq, Eq)]
+struct References {
+    /// Module files, each with a flag telling whether it owns its directory
+    modules: Vec<(PathBuf, bool)>,
+    /// Files spliced in with `include!`
+    includes: Vec<PathBuf>,
+}
+
+/// Find the module and include references of `file`
+///
+/// `module_dir` is where the file's own child modules live: its parent
+/// directory for crate roots and `mod.rs` files, or `parent/stem` otherwise.
+fn references(file: &Path, source: &str, module_dir: &Path) -> References {
+    let tokens = tokenize(source);
+    let file_dir = file.parent().unwrap_or(Path::new(""));
+    let mut refs = References::default();
+
+    // Directories of the enclosing inline modules, with the brace depth they opened at
+    let mut inline: Vec<(PathBuf, usize)> = Vec::new();
+    let mut depth = 0;
+    let mut pending_path: Option<String> = None;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let current_dir = inline.last().map_or(module_dir, |(dir, _)| dir.as_path());
+
+        match &tokens[i] {
+            // Outer attribute: remember `#[path = "..."]`, skip the rest
+            Token::Punct('#') if tokens.get(i + 1) == Some(&Token::Punct('[')) => {
+                let mut j = i + 2;
+                let mut brackets = 1;
+                while j < tokens.len() && brackets > 0 {
+                    match tokens[j] {
+                        Token::Punct('[') => brackets += 1,
+                        Token::Punct(']') => brackets -= 1,
+                        _ => {}
+                    }
+                    j += 1;
+                }
+                if let [Token::Ident(name), Token::Punct('='), Token::Str(path), ..] =
+                    &tokens[i + 2..j.min(tokens.len())]
+                    && name == "path"
+                {
+                    pending_path = Some(path.clone());
+                }
+                i = j;
+                continue;
+            }
+            Token::Ident(keyword) if keyword == "mod" => {
+                if let Some(Token::Ident(name)) = tokens.get(i + 1) {
+                    match tokens.get(i + 2) {
+                        Some(Token::Punct(';')) => {
+                            // Modules loaded through `#[path]` own their directory
+                            let resolved = match pending_path.take() {
+                                // Top-level paths are relative to the file's directory
+                                Some(path) if inline.is_empty() => {
+                                    Some((file_dir.join(path), true))
+                                }
+                                Some(path) => Some((current_dir.join(path), true)),
+                                None => resolve_module(current_dir, name)
+                                    .map(|path| (path.clone(), is_mod_rs(&path))),
+                            };
+                            if let Some((path, owns_dir)) = resolved
+                                && path.is_file()
+                            {
+                                refs.modules.push((path, owns_dir));
+                            }
+                            i += 3;
+                            continue;
+                        }
+                        Some(Token::Punct('{')) => {
+                            let dir = match pending_path.take() {
+                                Some(path) => current_dir.join(path),
+                                None => current_dir.join(name),
+                            };
+                            depth += 1;
+                            inline.push((dir, depth));
+                            i += 3;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Token::Ident(name) if name == "include" => {
+                if let (
+                    Some(Token::Punct('!')),
+                    Some(Token::Punct('(' | '[' | '{')),
+                    Some(Token::Str(path)),
+                ) = (tokens.get(i + 1), tokens.get(i + 2), tokens.get(i + 3))
+                {
+                    let path = file_dir.join(path);
+                    if path.is_file() {
+                        refs.includes.push(path);
+                    }
+                    i += 4;
+                    continue;
+                }
+            }
+            Token::Punct('{') => {
+                depth += 1;
+                pending_path = None;
+            }
+            Token::Punct('}') => {
+                if inline.last().is_some_and(|(_, opened)| *opened == depth) {
+                    inline.pop();
+                }
+                depth = depth.saturating_sub(1);
+                pending_path = None;
+            }
+            Token::Punct(';') => pending_path = None,
+            _ => {}
+        }
+
+        i += 1;
+    }
+
+    refs
+}
+
+/// Collect every file compiled into the target rooted at `root`
+///
+/// Paths are returned as given, i.e. relative to the same base as `root`.
+/// Declared modules whose file does not exist (e.g. generated code) are skipped.
+pub fn module_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut visited = BTreeSet::new();
+    let module_dir = root.parent().unwrap_or(Path::new("")).to_path_buf();
+    let mut queue = vec![(root.to_path_buf(), module_dir)];
+
+    while let Some((file, module_dir)) = queue.pop() {
+        if !visited.insert(file.clone()) {
+            continue;
+        }
+        let source = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read file: {:?}", file))?;
+        let refs = references(&file, &source, &module_dir);
+
+        for (module, owns_dir) in refs.modules {
+            let parent = module.parent().unwrap_or(Path::new(""));
+            let child_dir = if owns_dir {
+                parent.to_path_buf()
+            } else {
+                parent.join(module.file_stem().unwrap_or_default())
+            };
+            queue.push((module, child_dir));
+        }
+        // Included code is spliced into the including module
+        for include in refs.includes {
+            queue.push((include, module_dir.clone()));
+        }
+    }
+
+    Ok(visited.into_iter().collect())
+}
+
+/// Read a string value from a TOML table
+fn toml_str<'a>(table: &'a toml::Value, key: &str) -> Option<&'a str> {
+    table.get(key).and_then(|value| value.as_str())
+}
+
+/// Discover the targets of the package in `dir` (relative to `base`), following
+/// Cargo's rules for explicit `[lib]`/`[[bin]]`/`build` entries and auto-discovery
+pub fn package_targets(base: &Path, package: &Package) -> Result<Vec<Target>> {
+    let package_dir = base.join(&package.root);
+    let manifest_path = package_dir.join("Cargo.toml");
+    let content = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read manifest: {:?}", manifest_path))?;
+    let manifest: toml::Value = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse manifest: {:?}", manifest_path))?;
+
+    let package_table = manifest.get("package");
+    let flag = |key: &str| {
+        package_table
+            .and_then(|table| table.get(key))
+            .and_then(|value| value.as_bool())
+            .unwrap_or(true)
+    };
+    let exists = |path: &Path| package_dir.join(path).is_file();
+    let mut targets = Vec::new();
+
+    // Library
+    let lib = manifest.get("lib");
+    let lib_path = lib
+        .and_then(|lib| toml_str(lib, "path"))
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from("src/lib.rs")).filter(|path| exists(path)));
+    if let Some(path) = lib_path {
+        let name = lib
+            .and_then(|lib| toml_str(lib, "name"))
+            .map_or_else(|| package.name.replace('-', "_"), str::to_string);
+        targets.push(Target {
+            name,
+            kind: TargetKind::Lib,
+            root: package.root.join(path),
+        });
+    }
+
+    // Binaries, explicit ones first so they override auto-discovered ones
+    let mut bins: Vec<(String, PathBuf)> = Vec::new();
+    for bin in manifest
+        .get("bin")
+        .and_then(|bins| bins.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let Some(name) = toml_str(bin, "name") else {
+            continue;
+        };
+        let path = toml_str(bin, "path").map(PathBuf::from).or_else(|| {
+            [
+                PathBuf::from(format!("src/bin/{}.rs", name)),
+                PathBuf::from(format!("src/bin/{}/main.rs", name)),
+                PathBuf::from("src/main.rs"),
+            ]
+            .into_iter()
+            .find(|path| exists(path))
+        });
+        if let Some(path) = path {
+            bins.push((name.to_string(), path));
+        }
+    }
+
+    if flag("autobins") {
+        let mut discovered = Vec::new();
+        if exists(Path::new("src/main.rs")) {
+            discovered.push((package.name.clone(), PathBuf::from("src/main.rs")));
+        }
+        let mut entries: Vec<_> = fs::read_dir(package_dir.join("src/bin"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .collect();
+        entries.sort();
+        for path in entries {
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let relative = Path::new("src/bin").join(path.file_name().unwrap_or_default());
+            if path.is_dir() && path.join("main.rs").is_file() {
+                discovered.push((name.to_string(), relative.join("main.rs")));
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                discovered.push((name.to_string(), relative));
+            }
+        }
+        for (name, path) in discovered {
+            if !bins
+                .iter()
+                .any(|(bin_name, bin_path)| *bin_name == name || *bin_path == path)
+            {
+                bins.push((name, path));
+            }
+        }
+    }
+
+    targets.extend(bins.into_iter().map(|(name, path)| Target {
+        name,
+        kind: TargetKind::Bin,
+        root: package.root.join(path),
+    }));
+
+    // Build script
+    let build = package_table.and_then(|table| table.get("build"));
+    let build_path = match build {
+        Some(toml::Value::String(path)) => Some(PathBuf::from(path)),
+        Some(toml::Value::Boolean(false)) => None,
+        _ => Some(PathBuf::from("build.rs")).filter(|path| exists(path)),
+    };
+    if let Some(path) = build_path {
+        targets.push(Target {
+            name: "build-script-build".to_string(),
+            kind: TargetKind::BuildScript,
+            root: package.root.join(path),
+        });
+    }
+
+    Ok(targets)
+}
+
+/// Files of a project reachable from the crate roots of its packages
+///
+/// Every package of the workspace rooted at the directory is considered. Files
+/// that exist on disk but are not part of any module tree are left out.
+#[derive(Debug, Clone)]
+pub struct ModuleTreeSource {
+    root: PathBuf,
+}
+
+impl ModuleTreeSource {
+    /// Create a source for the Cargo package or workspace in `root`
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The packages below the root; fails if there is no Cargo package at all
+    fn packages(&self) -> Result<Vec<Package>> {
+        let workspace = Workspace::discover(&self.root)?;
+        if workspace.packages.is_empty() {
+            anyhow::bail!(
+                "No Cargo package found in {:?}; module tree scanning needs a Cargo.toml",
+                self.root
+            );
+        }
+        Ok(workspace.packages)
+    }
+
+    /// The targets of all packages below the root
+    pub fn targets(&self) -> Result<Vec<Target>> {
+        let mut targets = Vec::new();
+        for package in &self.packages()? {
+            targets.extend(package_targets(&self.root, package)?);
+        }
+        Ok(targets)
+    }
+
+    /// Files compiled into each binary target, relative to the root
+    ///
+    /// A binary that refers to its package's library by name also gets the
+    /// library's files, since the library code is linked into it.
+    pub fn binaries(&self) -> Result<Vec<TargetFiles>> {
+        let mut binaries = Vec::new();
+
+        for package in &self.packages()? {
+            let targets = package_targets(&self.root, package)?;
+            let lib = targets
+                .iter()
+                .find(|target| target.kind == TargetKind::Lib)
+                .map(|lib| Ok::<_, anyhow::Error>((lib.name.clone(), self.target_files(lib)?)))
+                .transpose()?;
+
+            for target in targets.into_iter().filter(|t| t.kind == TargetKind::Bin) {
+                let mut files: BTreeSet<PathBuf> =
+                    self.target_files(&target)?.into_iter().collect();
+
+                if let Some((lib_name, lib_files)) = &lib {
+                    let uses_lib = files.iter().any(|file| {
+                        self.read(file)
+                            .is_ok_and(|source| mentions_ident(&source, lib_name))
+                    });
+                    if uses_lib {
+                        files.extend(lib_files.iter().cloned());
+                    }
+                }
+
+                binaries.push(TargetFiles {
+                    target,
+                    files: files.into_iter().collect(),
+                });
+            }
+        }
+
+        Ok(binaries)
+    }
+
+    /// Files compiled into a single target, relative to the root
+    pub fn target_files(&self, target: &Target) -> Result<Vec<PathBuf>> {
+        let files = module_files(&self.root.join(&target.root))?;
+        Ok(files
+            .into_iter()
+            .filter_map(|file| file.strip_prefix(&self.root).ok().map(Path::to_path_buf))
+            .collect())
+    }
+}
+
+impl Source for ModuleTreeSource {
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = BTreeSet::new();
+        for target in self.targets()? {
+            files.extend(self.target_files(&target)?);
+        }
+        Ok(files.into_iter().collect())
+    }
+
+    fn read(&self, path: &Path) -> Result<String> {
+        let path = self.root.join(path);
+        fs::read_to_string(&path).with_context(|| format!("Failed to read file: {:?}", path))
+    }
+
+    fn local_root(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+}
+
+/// Split detailed scan results by binary target
+///
+/// A variable appears under every binary whose compiled files read it.
+pub fn attribute_binaries(
+    binaries: &[TargetFiles],
+    variables: &BTreeMap<String, VariableInfo>,
+) -> Vec<BinaryVariables> {
+    binaries
+        .iter()
+        .map(|binary| {
+            let files: HashSet<&Path> = binary.files.iter().map(PathBuf::as_path).collect();
+            let variables = variables
+                .values()
+                .filter_map(|info| {
+                    let locations: Vec<_> = info
+                        .locations
+                        .iter()
+                        .filter(|location| files.contains(location.file.as_path()))
+                        .cloned()
+                        .collect();
+                    (!locations.is_empty()).then(|| {
+                        (
+                            info.name.clone(),
+                            VariableInfo {
+                                locations,
+                                ..info.clone()
+                            },
+                        )
+                    })
+                })
+                .collect();
+
+            BinaryVariables {
+                name: binary.target.name.clone(),
+                variables,
+            }
+        })
+        .collect()
+}
+
+/// Scan the package or workspace in `dir` and compute the variables of each binary
+pub fn binary_variables<P: AsRef<Path>>(
+    scanner: &EnvScanner,
+    dir: P,
+) -> Result<Vec<BinaryVariables>> {
+    let source = ModuleTreeSource::new(dir);
+    let binaries = source.binaries()?;
+    let variables = scanner.scan_source_detailed(&source)?;
+    Ok(attribute_binaries(&binaries, &variables))
+}
+
+/// Write one env file per binary into `dir`, named `<output_file>.<binary>`
+///
+/// Each file lists what `generate` would for the binary's module tree alone,
+/// with the dependencies of the binary's package. Returns the written paths
+/// with the number of variables in each.
+pub fn generate_per_binary<P: AsRef<Path>>(
+    scanner: &EnvScanner,
+    dir: P,
+    output_file: &str,
+) -> Result<Vec<(PathBuf, usize)>> {
+    let dir = dir.as_ref();
+    let source = ModuleTreeSource::new(dir);
+    let binaries = source.binaries()?;
+    let scanned: HashMap<PathBuf, FileScan> = scanner
+        .scan_files(&source, source.files()?)?
+        .into_iter()
+        .collect();
+    let workspace = Workspace::discover(dir)?;
+    let mut written = Vec::new();
+
+    for binary in &binaries {
+        let package = workspace.package_for(&binary.target.root);
+        let (package_dir, locked) = match package {
+            Some(package) => (
+                dir.join(&package.root),
+                dependencies::usable_crates(scanner, dir, Some(&package.name))?,
+            ),
+            None => (dir.to_path_buf(), HashSet::new()),
+        };
+        let scans: Vec<&FileScan> = binary
+            .files
+            .iter()
+            .filter_map(|file| scanned.get(file))
+            .collect();
+        let plan = scanner.plan_files(&package_dir, &locked, &scans)?;
+        let output_path = dir.join(format!("{}.{}", output_file, binary.target.name));
+        scanner.generate_annotated_env_file(&plan.variables, &plan.sections, &output_path)?;
+        written.push((output_path, plan.len()));
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_tokenize_skips_comments_and_literals() {
+        let tokens = tokenize(
+            r##"// mod commented;
+/* mod /* nested */ hidden; */
+let s = "mod fake;"; let r = r#"mod raw;"#; let c = '{'; fn f<'a>() {}
+mod real;"##,
+        );
+        let mods: Vec<_> = tokens
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [Token::Ident(keyword), Token::Ident(name)] if keyword == "mod" => {
+                    Some(name.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(mods, vec!["real"]);
+        assert!(tokens.contains(&Token::Str("mod raw;".to_string())));
+        // Only the function body opens a brace; the character literal is skipped
+        let braces = tokens.iter().filter(|t| **t == Token::Punct('{')).count();
+        assert_eq!(braces, 1);
+    }
+
+    #[test]
+    fn test_module_files_follow_declarations() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        write(
+            root,
+            "src/lib.rs",
+            "mod config;\nmod net;\n#[path = \"generated/api.rs\"]\nmod api;\nmod inline {\n    mod nested;\n}\ninclude!(\"extra.rs\");\n",
+        );
+        write(root, "src/config.rs", "mod loader;\n");
+        write(root, "src/config/loader.rs", "");
+        write(root, "src/net/mod.rs", "mod http;\n");
+        write(root, "src/net/http.rs", "");
+        write(root, "src/generated/api.rs", "mod types;\n");
+        write(root, "src/generated/types.rs", "");
+        write(root, "src/inline/nested.rs", "");
+        write(root, "src/extra.rs", "");
+        write(root, "src/orphan.rs", "");
+
+        let files: Vec<PathBuf> = module_files(&root.join("src/lib.rs"))?
+            .into_iter()
+            .map(|file| file.strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+
+        let expected: Vec<PathBuf> = [
+            "src/config/loader.rs",
+            "src/config.rs",
+            "src/extra.rs",
+            "src/generated/api.rs",
+            "src/generated/types.rs",
+            "src/inline/nested.rs",
+            "src/lib.rs",
+            "src/net/http.rs",
+            "src/net/mod.rs",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(files, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_module_tree_source_uses_crate_roots() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[[bin]]\nname = \"tool\"\npath = \"tools/tool.rs\"\n",
+        );
+        write(root, "src/main.rs", "mod server;\nfn main() {}\n");
+        write(root, "src/server.rs", "");
+        write(root, "src/bin/worker.rs", "fn main() {}\n");
+        write(root, "tools/tool.rs", "fn main() {}\n");
+        write(root, "build.rs", "fn main() {}\n");
+        write(root, "src/unused.rs", "");
+        write(root, "tests/fixtures/sample.rs", "");
+
+        let source = ModuleTreeSource::new(root);
+        let targets: Vec<(String, TargetKind)> = source
+            .targets()?
+            .into_iter()
+            .map(|target| (target.name, target.kind))
+            .collect();
+        assert_eq!(
+            targets,
+            vec![
+                ("tool".to_string(), TargetKind::Bin),
+                ("app".to_string(), TargetKind::Bin),
+                ("worker".to_string(), TargetKind::Bin),
+                ("build-script-build".to_string(), TargetKind::BuildScript),
+            ]
+        );
+
+        let files: Vec<PathBuf> = source.files()?;
+        let expected: Vec<PathBuf> = [
+            "build.rs",
+            "src/bin/worker.rs",
+            "src/main.rs",
+            "src/server.rs",
+            "tools/tool.rs",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(files, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_variables() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[package]\nname = \"my-app\"\nversion = \"0.1.0\"\n",
+        );
+        write(
+            root,
+            "src/lib.rs",
+            "pub fn db() { std::env::var(\"DATABASE_URL\").unwrap(); }\n",
+        );
+        write(
+            root,
+            "src/bin/api/main.rs",
+            "mod routes;\nfn main() { my_app::db(); std::env::var(\"PORT\").ok(); }\n",
+        );
+        write(
+            root,
+            "src/bin/api/routes.rs",
+            "fn r() { std::env::var(\"API_KEY\").unwrap(); }\n",
+        );
+        write(
+            root,
+            "src/bin/worker.rs",
+            "fn main() { std::env::var(\"QUEUE_URL\").unwrap(); }\n",
+        );
+
+        let config = crate::Config {
+            cache: Some(false),
+            ..Default::default()
+        };
+        let scanner = EnvScanner::with_config(config)?;
+        let binaries: Vec<(String, Vec<String>)> = binary_variables(&scanner, root)?
+            .into_iter()
+            .map(|binary| (binary.name, binary.variables.into_keys().collect()))
+            .collect();
+
+        assert_eq!(
+            binaries,
+            vec![
+                (
+                    "api".to_string(),
+                    vec![
+                        "API_KEY".to_string(),
+                        "DATABASE_URL".to_string(),
+                        "PORT".to_string()
+                    ]
+                ),
+                ("worker".to_string(), vec!["QUEUE_URL".to_string()]),
+            ]
+        );
+
+        let written = generate_per_binary(&scanner, root, ".env")?;
+        assert_eq!(written.len(), 2);
+        let worker = fs::read_to_string(root.join(".env.worker"))?;
+        assert!(worker.contains("QUEUE_URL="));
+        assert!(!worker.contains("DATABASE_URL="));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,342 @@
+//! External detector plugins
+//!
+//! A plugin is an executable that detects variables in files autoenv does not
+//! understand itself, such as an in-house configuration language. autoenv
+//! starts it on first use and talks to it over stdin and stdout with one JSON
+//! object per line:
+//!
+//! ```text
+//! -> {"path":"conf/app.dsl","content":"listen ${PORT}\n"}
+//! <- {"detections":[{"name":"PORT","line":1,"required":true}]}
+//! ```
+//!
+//! Every request gets exactly one response line, either `{"detections": [...]}`
+//! or `{"error": "message"}`. Detections have a `name` and 1-based `line`, and
+//! optionally `required`, `default`, `type` and a free-form `metadata` object
+//! that is kept with the variable. The plugin should exit when its stdin is
+//! closed. A plugin that doesn't answer within its timeout is killed and
+//! started again for the next file.
+//!
+//! An error answer, a timeout or an invalid response fails the scan, like any
+//! other [`Detector`] error: the file's variables are unknown, and a file
+//! generated without them would look complete. The error names the plugin and
+//! the file, so the file can be fixed or the plugin's `patterns` narrowed.
+
+use crate::Detection;
+use crate::detectors::Detector;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// Default time to wait for a plugin's answer to one file
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Configuration of an external detector, from `[[plugins]]` in `autoenv.toml`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PluginConfig {
+    /// Name used in messages
+    pub name: String,
+    /// Executable to run
+    pub command: String,
+    /// Arguments passed to the executable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// File name globs of the files sent to the plugin, e.g. `*.dsl`
+    pub patterns: Vec<String>,
+    /// Version of the plugin; change it to invalidate cached results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Seconds to wait for the answer to each file (default: 30)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+/// A file sent to a plugin
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    path: &'a Path,
+    content: &'a str,
+}
+
+/// A plugin's answer for one file
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(default)]
+    detections: Vec<Detection>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// A running plugin process
+///
+/// Its output is read line by line on a separate thread, so that waiting for
+/// an answer can time out.
+struct Process {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    lines: Receiver<io::Result<String>>,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        // Closing stdin asks the plugin to exit; don't wait for one that doesn't
+        self.stdin.take();
+        if !matches!(self.child.try_wait(), Ok(Some(_))) {
+            let _ = self.child.kill();
+        }
+        let _ = self.child.wait();
+    }
+}
+
+/// A [`Detector`] backed by an external process
+///
+/// The process is started on first use and kept running for the lifetime of
+/// the detector; requests from parallel scans are sent one at a time.
+pub struct PluginDetector {
+    config: PluginConfig,
+    process: Mutex<Option<Process>>,
+}
+
+impl PluginDetector {
+    /// Create a detector for a configured plugin without starting it
+    pub fn new(config: PluginConfig) -> Self {
+        Self {
+            config,
+            process: Mutex::new(None),
+        }
+    }
+
+    fn spawn(&self) -> Result<Process> {
+        let mut child = Command::new(&self.config.command)
+            .args(&self.config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| {
+                format!(
+                    "Failed to start plugin {}: {}",
+                    self.config.name, self.config.command
+                )
+            })?;
+        let stdin = child.stdin.take();
+        let Some(stdout) = child.stdout.take() else {
+            bail!("Failed to open the output of plugin {}", self.config.name);
+        };
+
+        // Ends once the process exits or is killed and its output is closed
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Process {
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    /// Send one request and wait for the response line
+    fn exchange(process: &mut Process, request: &str, timeout: Duration) -> Result<String> {
+        let stdin = process.stdin.as_mut().context("Plugin input is closed")?;
+        stdin.write_all(request.as_bytes())?;
+        stdin.write_all(b"\n")?;
+        stdin.flush()?;
+
+        match process.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line?),
+            Err(RecvTimeoutError::Timeout) => {
+                bail!(
+                    "Plugin did not answer within {} seconds",
+                    timeout.as_secs_f64()
+                )
+            }
+            Err(RecvTimeoutError::Disconnected) => bail!("Plugin exited without answering"),
+        }
+    }
+}
+
+impl Detector for PluginDetector {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn patterns(&self) -> &[String] {
+        &self.config.patterns
+    }
+
+    fn detect(&self, path: &Path, content: &str) -> Result<Vec<Detection>> {
+        let request = serde_json::to_string(&Request { path, content })?;
+        let mut process = self
+            .process
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Plugin {} is unusable", self.config.name))?;
+
+        if process.is_none() {
+            *process = Some(self.spawn()?);
+        }
+        let timeout = self
+            .config
+            .timeout
+            .map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+        let answer = process
+            .as_mut()
+            .map(|running| Self::exchange(running, &request, timeout))
+            .unwrap_or_else(|| bail!("Plugin is not running"));
+        let line = match answer {
+            Ok(line) => line,
+            Err(err) => {
+                // Kill a plugin that hangs, and start afresh on the next request
+                *process = None;
+                return Err(err).with_context(|| {
+                    format!("Plugin {} failed on {}", self.config.name, path.display())
+                });
+            }
+        };
+
+        let response: Response = serde_json::from_str(&line).with_context(|| {
+            format!(
+                "Plugin {} sent an invalid response for {}",
+                self.config.name,
+                path.display()
+            )
+        })?;
+        if let Some(error) = response.error {
+            bail!(
+                "Plugin {} failed on {}: {}",
+                self.config.name,
+                path.display(),
+                error
+            );
+        }
+        Ok(response.detections)
+    }
+
+    fn fingerprint(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.config.name,
+            self.config.command,
+            self.config.args.join(" "),
+            self.config.version.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A plugin reporting `${NAME}` references, written as a shell script
+    fn write_plugin(dir: &Path) -> String {
+        let script = dir.join("plugin.sh");
+        fs::write(
+            &script,
+            r#"#!/bin/sh
+while IFS= read -r request; do
+  case "$request" in
+    *broken*) echo '{"error":"cannot parse"}' ;;
+    *'${PORT}'*) echo '{"detections":[{"name":"PORT","line":2,"required":true,"type":"u16","metadata":{"section":"server"}}]}' ;;
+    *) echo '{"detections":[]}' ;;
+  esac
+done
+"#,
+        )
+        .unwrap();
+        script.display().to_string()
+    }
+
+    #[test]
+    fn test_plugin_detector() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let detector = PluginDetector::new(PluginConfig {
+            name: "dsl".to_string(),
+            command: "sh".to_string(),
+            args: vec![write_plugin(temp_dir.path())],
+            patterns: vec!["*.dsl".to_string()],
+            version: None,
+            timeout: None,
+        });
+
+        assert!(detector.handles(Path::new("conf/app.dsl")));
+        assert!(!detector.handles(Path::new("conf/app.toml")));
+
+        let detections = detector.detect(Path::new("app.dsl"), "server\nlisten ${PORT}\n")?;
+        assert_eq!(
+            detections,
+            vec![Detection {
+                name: "PORT".to_string(),
+                line: 2,
+                required: Some(true),
+                var_type: Some("u16".to_string()),
+                metadata: [("section".to_string(), serde_json::json!("server"))].into(),
+                ..Default::default()
+            }]
+        );
+        assert!(detector.detect(Path::new("empty.dsl"), "")?.is_empty());
+
+        let err = detector.detect(Path::new("bad.dsl"), "broken").unwrap_err();
+        assert!(err.to_string().contains("cannot parse"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hung_plugin_times_out() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let script = temp_dir.path().join("plugin.sh");
+        fs::write(
+            &script,
+            r#"#!/bin/sh
+while IFS= read -r request; do
+  case "$request" in
+    *hang*) sleep 60 ;;
+    *) echo '{"detections":[{"name":"PORT","line":1}]}' ;;
+  esac
+done
+"#,
+        )?;
+        let detector = PluginDetector::new(PluginConfig {
+            name: "slow".to_string(),
+            command: "sh".to_string(),
+            args: vec![script.display().to_string()],
+            patterns: vec!["*.dsl".to_string()],
+            version: None,
+            timeout: Some(1),
+        });
+
+        let err = detector.detect(Path::new("app.dsl"), "hang").unwrap_err();
+        assert!(format!("{:#}", err).contains("did not answer within 1 seconds"));
+        // The hung process is replaced for the next file
+        assert_eq!(detector.detect(Path::new("app.dsl"), "${PORT}")?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_plugin() {
+        let detector = PluginDetector::new(PluginConfig {
+            name: "missing".to_string(),
+            command: "/nonexistent/autoenv-plugin".to_string(),
+            args: Vec::new(),
+            patterns: vec!["*.dsl".to_string()],
+            version: None,
+            timeout: None,
+        });
+        let err = detector.detect(Path::new("app.dsl"), "").unwrap_err();
+        assert!(err.to_string().contains("Failed to start plugin missing"));
+    }
+}
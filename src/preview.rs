@@ -0,0 +1,314 @@
+//! Previewing generated files without writing them
+//!
+//! In dry-run mode the scanner records what it would write as
+//! [`PlannedWrite`]s. This module renders them as a unified diff against the
+//! current files, or summarizes them as the keys added, removed and changed.
+
+use crate::dotenv::{Dialect, Document};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Number of unchanged lines shown around each change in a unified diff
+pub const CONTEXT_LINES: usize = 3;
+
+/// A file generating would write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedWrite {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Current contents, `None` if the file doesn't exist yet
+    pub old: Option<String>,
+    /// Contents that would be written
+    pub new: String,
+}
+
+impl PlannedWrite {
+    /// Whether writing would change the file
+    pub fn is_change(&self) -> bool {
+        self.old.as_deref() != Some(self.new.as_str())
+    }
+
+    /// Unified diff from the current to the new contents, empty if unchanged
+    ///
+    /// Relative paths get the `a/` and `b/` prefixes git uses.
+    pub fn unified_diff(&self) -> String {
+        let name = |prefix: &str| {
+            if self.path.is_absolute() {
+                self.path.display().to_string()
+            } else {
+                format!("{}{}", prefix, self.path.display())
+            }
+        };
+        let old_name = match self.old {
+            Some(_) => name("a/"),
+            None => "/dev/null".to_string(),
+        };
+        unified_diff(
+            self.old.as_deref().unwrap_or_default(),
+            &self.new,
+            &old_name,
+            &name("b/"),
+        )
+    }
+
+    /// Keys the write adds, removes or gives a different value
+    ///
+    /// Contents that don't parse in `dialect` count as having no keys.
+    pub fn key_changes(&self, dialect: Dialect) -> KeyChanges {
+        let values = |content: &str| -> BTreeMap<String, String> {
+            Document::parse_as(content, dialect)
+                .map(|document| {
+                    document
+                        .entries()
+                        .map(|entry| (entry.key.clone(), entry.value.clone()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let old = values(self.old.as_deref().unwrap_or_default());
+        let new = values(&self.new);
+
+        KeyChanges {
+            path: self.path.clone(),
+            created: self.old.is_none(),
+            added: new
+                .keys()
+                .filter(|key| !old.contains_key(*key))
+                .cloned()
+                .collect(),
+            removed: old
+                .keys()
+                .filter(|key| !new.contains_key(*key))
+                .cloned()
+                .collect(),
+            changed: new
+                .iter()
+                .filter(|(key, value)| old.get(*key).is_some_and(|old| old != *value))
+                .map(|(key, _)| key.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Keys that differ between the current and the new contents of a file
+///
+/// Values are deliberately left out, as env files often hold secrets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct KeyChanges {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Whether the file would be created
+    pub created: bool,
+    /// Keys only in the new contents, sorted
+    pub added: Vec<String>,
+    /// Keys only in the current contents, sorted
+    pub removed: Vec<String>,
+    /// Keys in both whose value differs, sorted
+    pub changed: Vec<String>,
+}
+
+/// One step turning the old lines into the new ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// Shortest edit script between two line sequences (Myers' algorithm)
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let offset = n + m + 1;
+    let index = |k: isize| (k + offset) as usize;
+
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = Vec::new();
+    'search: for d in 0..=(n + m) {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk back from the end through the furthest points of each round
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == prev_x {
+                Edit::Insert
+            } else {
+                Edit::Delete
+            });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.reverse();
+    edits
+}
+
+/// Write one diff line, noting a missing line break at the end of the file
+fn push_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Hunk range in the `start,count` form of unified diffs
+fn range(start: usize, count: usize) -> String {
+    // An empty range names the line before it
+    let start = if count == 0 { start } else { start + 1 };
+    if count == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, count)
+    }
+}
+
+/// Unified diff of two texts, empty if they are equal
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = diff_lines(&old_lines, &new_lines);
+
+    // Line positions in both texts before each edit
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for edit in &edits {
+        positions.push((i, j));
+        match edit {
+            Edit::Keep => (i, j) = (i + 1, j + 1),
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+    positions.push((i, j));
+
+    // Changes whose contexts would touch or overlap share a hunk
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&e| edits[e] != Edit::Keep)
+        .collect();
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &change in &changes {
+        match hunks.last_mut() {
+            Some((_, end)) if change <= *end + 2 * CONTEXT_LINES + 1 => *end = change,
+            _ => hunks.push((change, change)),
+        }
+    }
+
+    let mut out = String::new();
+    if hunks.is_empty() {
+        return out;
+    }
+    let _ = writeln!(out, "--- {}", old_name);
+    let _ = writeln!(out, "+++ {}", new_name);
+    for (first, last) in hunks {
+        let start = first.saturating_sub(CONTEXT_LINES);
+        let end = (last + 1 + CONTEXT_LINES).min(edits.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let _ = writeln!(
+            out,
+            "@@ -{} +{} @@",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        );
+        for e in start..end {
+            let (i, j) = positions[e];
+            match edits[e] {
+                Edit::Keep => push_line(&mut out, ' ', old_lines[i]),
+                Edit::Delete => push_line(&mut out, '-', old_lines[i]),
+                Edit::Insert => push_line(&mut out, '+', new_lines[j]),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        assert_eq!(unified_diff("A=1\n", "A=1\n", "a", "b"), "");
+
+        let old = "# header\nA=1\nB=2\nC=3\nD=4\nE=5\nF=6\nG=7\nH=8\nI=9\n";
+        let new = "# header\nA=1\nB=two\nC=3\nD=4\nE=5\nF=6\nG=7\nH=8\nI=9\nJ=\n";
+        assert_eq!(
+            unified_diff(old, new, "a/.env", "b/.env"),
+            "--- a/.env\n+++ b/.env\n\
+             @@ -1,6 +1,6 @@\n # header\n A=1\n-B=2\n+B=two\n C=3\n D=4\n E=5\n\
+             @@ -8,3 +8,4 @@\n G=7\n H=8\n I=9\n+J=\n"
+        );
+
+        assert_eq!(
+            unified_diff("", "A=\n", "/dev/null", "b/.env"),
+            "--- /dev/null\n+++ b/.env\n@@ -0,0 +1 @@\n+A=\n"
+        );
+        assert_eq!(
+            unified_diff("A=1", "A=1\nB=\n", "a", "b"),
+            "--- a\n+++ b\n@@ -1 +1,2 @@\n-A=1\n\\ No newline at end of file\n+A=1\n+B=\n"
+        );
+    }
+
+    #[test]
+    fn test_key_changes() {
+        let write = PlannedWrite {
+            path: PathBuf::from(".env"),
+            old: Some("A=1\nB=2\nC=3\n".to_string()),
+            new: "A=1\nB=\"22\"\nD=\n".to_string(),
+        };
+        assert!(write.is_change());
+        let changes = write.key_changes(Dialect::Dotenvy);
+        assert_eq!(changes.added, vec!["D"]);
+        assert_eq!(changes.removed, vec!["C"]);
+        assert_eq!(changes.changed, vec!["B"]);
+        assert!(!changes.created);
+
+        let write = PlannedWrite {
+            path: PathBuf::from(".env"),
+            old: None,
+            new: "A=\n".to_string(),
+        };
+        assert!(write.key_changes(Dialect::Dotenvy).created);
+        assert!(
+            write
+                .unified_diff()
+                .starts_with("--- /dev/null\n+++ b/.env\n")
+        );
+    }
+}
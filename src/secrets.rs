@@ -0,0 +1,172 @@
+//! Safeguards for env files that hold real values
+//!
+//! A template such as `.env.example` only lists names, but once values are
+//! merged in, a `.env` file usually holds credentials. Such files are written
+//! readable by their owner only, and [`audit`] points out ones that other
+//! users can read or that git would commit.
+
+use crate::dotenv::{Dialect, Document};
+use crate::git::{self, FileStatus};
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Whether env file contents set any variable to a non-empty value
+///
+/// Contents that don't parse count as holding values, to err on the safe side.
+pub fn has_values(content: &str, dialect: Dialect) -> bool {
+    Document::parse_as(content, dialect)
+        .map(|document| document.entries().any(|entry| !entry.value.is_empty()))
+        .unwrap_or(true)
+}
+
+/// A way the values in an env file could leak
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Issue {
+    /// Other users can read the file; holds the Unix mode
+    ReadableByOthers(u32),
+    /// Git would pick the file up, as no `.gitignore` pattern matches it
+    NotIgnored,
+    /// The file is in the git index, so its values are or will be committed
+    Tracked,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::ReadableByOthers(mode) => write!(
+                f,
+                "holds values but is readable by other users (mode {:o}); restrict it with `chmod 600`",
+                mode
+            ),
+            Issue::NotIgnored => write!(
+                f,
+                "holds values but is not covered by .gitignore; add it with --fix-gitignore"
+            ),
+            Issue::Tracked => write!(
+                f,
+                "holds values but is tracked by git; stop tracking it with `git rm --cached`"
+            ),
+        }
+    }
+}
+
+/// Ways the values in the env file at `path` could leak
+///
+/// Files that only list names, such as templates, have no issues.
+pub fn audit(path: &Path, dialect: Dialect) -> Result<Vec<Issue>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read file: {:?}", path))?;
+    if !has_values(&content, dialect) {
+        return Ok(Vec::new());
+    }
+
+    let mut issues = Vec::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)
+            .with_context(|| format!("Failed to read file: {:?}", path))?
+            .permissions()
+            .mode()
+            & 0o777;
+        if mode & 0o044 != 0 {
+            issues.push(Issue::ReadableByOthers(mode));
+        }
+    }
+    match git::file_status(path) {
+        Some(FileStatus::Tracked) => issues.push(Issue::Tracked),
+        Some(FileStatus::Untracked) => issues.push(Issue::NotIgnored),
+        Some(FileStatus::Ignored) | None => {}
+    }
+    Ok(issues)
+}
+
+/// Add the file at `path` to the `.gitignore` next to it
+///
+/// The pattern is anchored, e.g. `/.env`, so it doesn't match files of the
+/// same name in subdirectories. Returns whether `.gitignore` changed.
+pub fn fix_gitignore(path: &Path) -> Result<bool> {
+    let name = path
+        .file_name()
+        .with_context(|| format!("Not a file path: {:?}", path))?
+        .to_string_lossy();
+    let pattern = format!("/{}", name);
+    let gitignore = path.with_file_name(".gitignore");
+
+    let mut content = match fs::read_to_string(&gitignore) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read file: {:?}", gitignore));
+        }
+    };
+    if content.lines().any(|line| line.trim() == pattern) {
+        return Ok(false);
+    }
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&pattern);
+    content.push('\n');
+    crate::backup::write_atomic(&gitignore, content.as_bytes(), false)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_has_values() {
+        assert!(!has_values(
+            "# Template\nAPI_KEY=\nPORT=\"\"\n",
+            Dialect::Dotenvy
+        ));
+        assert!(has_values("API_KEY=\nPORT=8080\n", Dialect::Dotenvy));
+        assert!(has_values("BROKEN=\"unterminated\n", Dialect::Dotenvy));
+    }
+
+    #[test]
+    fn test_audit_and_fix_gitignore() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["init", "-q"])
+            .status()?;
+        assert!(status.success());
+
+        let template = root.join(".env.example");
+        fs::write(&template, "API_KEY=\n")?;
+        assert!(audit(&template, Dialect::Dotenvy)?.is_empty());
+
+        let env = root.join(".env");
+        crate::backup::write_atomic(&env, b"API_KEY=secret\n", true)?;
+        assert_eq!(audit(&env, Dialect::Dotenvy)?, vec![Issue::NotIgnored]);
+
+        fs::write(root.join(".gitignore"), "target")?;
+        assert!(fix_gitignore(&env)?);
+        assert!(!fix_gitignore(&env)?);
+        assert_eq!(
+            fs::read_to_string(root.join(".gitignore"))?,
+            "target\n/.env\n"
+        );
+        assert!(audit(&env, Dialect::Dotenvy)?.is_empty());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&env, fs::Permissions::from_mode(0o644))?;
+            assert_eq!(
+                audit(&env, Dialect::Dotenvy)?,
+                vec![Issue::ReadableByOthers(0o644)]
+            );
+        }
+        Ok(())
+    }
+}
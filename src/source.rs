@@ -0,0 +1,157 @@
+//! Sources of files to scan
+//!
+//! A [`Source`] lists the files that make up a project and reads their
+//! contents. The scanner works on any source, so the same detection runs on a
+//! directory on disk or on a revision stored in a git repository.
+
+use crate::detectors::FileKind;
+use crate::workspace::glob_match;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A collection of source files the scanner can read
+pub trait Source: Sync {
+    /// List the files to scan, relative to the root of the source
+    fn files(&self) -> Result<Vec<PathBuf>>;
+
+    /// Read the content of a file returned by [`Source::files`]
+    fn read(&self, path: &Path) -> Result<String>;
+
+    /// Directory on disk the files live in, if any
+    ///
+    /// Sources backed by the local file system can use the on-disk scan cache.
+    fn local_root(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Files in a directory on the local file system
+#[derive(Debug, Clone)]
+pub struct FsSource {
+    root: PathBuf,
+    patterns: Vec<String>,
+    excluded: Vec<PathBuf>,
+}
+
+impl FsSource {
+    /// Create a source for all source and deployment files below `root`
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            patterns: Vec::new(),
+            excluded: Vec::new(),
+        }
+    }
+
+    /// Also list files whose name matches one of the globs, e.g. `*.dsl`
+    pub fn with_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    /// Leave out everything below the given directories, e.g. vendored sources
+    pub fn with_excluded(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.excluded = dirs;
+        self
+    }
+}
+
+impl Source for FsSource {
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        walk_dir(&self.root, &self.patterns, &self.excluded, &mut files)?;
+        Ok(files
+            .into_iter()
+            .filter_map(|file| file.strip_prefix(&self.root).ok().map(Path::to_path_buf))
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> Result<String> {
+        let path = self.root.join(path);
+        fs::read_to_string(&path).with_context(|| format!("Failed to read file: {:?}", path))
+    }
+
+    fn local_root(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+}
+
+/// Directories never scanned: build output and installed dependencies
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "__pycache__", "venv"];
+
+/// Whether a directory is left out of scans, i.e. hidden or in [`SKIPPED_DIRS`]
+pub(crate) fn is_skipped_dir(name: &str) -> bool {
+    name.starts_with('.') || SKIPPED_DIRS.contains(&name)
+}
+
+/// Recursively collect scannable files, skipping build output, dependency,
+/// hidden and `excluded` directories
+fn walk_dir(
+    dir: &Path,
+    patterns: &[String],
+    excluded: &[PathBuf],
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if path.is_dir() {
+            if let Some(name) = path.file_name()
+                && let Some(name_str) = name.to_str()
+                && is_skipped_dir(name_str)
+            {
+                continue;
+            }
+            if excluded.contains(&path) {
+                continue;
+            }
+            walk_dir(&path, patterns, excluded, files)?;
+        } else if FileKind::of(&path).is_some() || matches_any(&path, patterns) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether a file name matches one of the globs
+fn matches_any(path: &Path, patterns: &[String]) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| patterns.iter().any(|pattern| glob_match(pattern, name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fs_source_lists_relative_paths() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::create_dir_all(temp_dir.path().join("src"))?;
+        fs::create_dir_all(temp_dir.path().join("target"))?;
+        fs::create_dir_all(temp_dir.path().join("web/node_modules/lib"))?;
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}")?;
+        fs::write(temp_dir.path().join("target/gen.rs"), "fn gen() {}")?;
+        fs::write(temp_dir.path().join("README.md"), "# readme")?;
+        fs::write(temp_dir.path().join("web/app.js"), "process.env.PORT")?;
+        fs::write(
+            temp_dir.path().join("web/node_modules/lib/index.js"),
+            "process.env.NODE_DEBUG",
+        )?;
+
+        let source = FsSource::new(temp_dir.path());
+        let mut files = source.files()?;
+        files.sort();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("src/main.rs"), PathBuf::from("web/app.js")]
+        );
+        assert_eq!(source.read(Path::new("src/main.rs"))?, "fn main() {}");
+        assert_eq!(source.local_root(), Some(temp_dir.path()));
+
+        Ok(())
+    }
+}
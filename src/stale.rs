@@ -0,0 +1,154 @@
+//! Variables left behind in an env file after the code stopped reading them
+//!
+//! Merging keeps every variable already in the output file, so one that was
+//! removed from the code would stay in `.env.example` forever. This module
+//! finds such stale variables and reports, comments out or removes them.
+
+use crate::dotenv::Document;
+use crate::workspace::glob_match;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Start of the note written above a commented-out stale variable
+pub const UNUSED_NOTE: &str = "unused since";
+
+/// What generating does with variables the code no longer reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum StaleMode {
+    /// Keep them and only report them (the default)
+    #[default]
+    Report,
+    /// Comment them out below a `# unused since <date>` note
+    Comment,
+    /// Remove them
+    Prune,
+}
+
+/// Variables assigned in `document` that are not in `used`, in file order
+///
+/// Variables matching one of the `keep` patterns (`*` matches any run of
+/// characters) are never stale.
+pub fn find_stale(document: &Document, used: &HashSet<&str>, keep: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    document
+        .entries()
+        .map(|entry| entry.key.as_str())
+        .filter(|key| !used.contains(key) && seen.insert(*key))
+        .filter(|key| !keep.iter().any(|pattern| glob_match(pattern, key)))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Bring `document` in line with the variables in use
+///
+/// Variables in `used` that an earlier run commented out as unused are
+/// restored. Stale variables are then commented out with a note dated `date`
+/// or removed, as `mode` asks; with [`StaleMode::Report`] they are left alone.
+/// Returns the stale variables.
+pub fn apply(
+    document: &mut Document,
+    used: &HashSet<&str>,
+    keep: &[String],
+    mode: StaleMode,
+    date: &str,
+) -> Vec<String> {
+    for key in used {
+        document.uncomment(key, UNUSED_NOTE);
+    }
+
+    let stale = find_stale(document, used, keep);
+    for key in &stale {
+        match mode {
+            StaleMode::Report => {}
+            StaleMode::Comment => {
+                document.comment_out(key, &format!("{} {}", UNUSED_NOTE, date));
+            }
+            StaleMode::Prune => {
+                document.remove(key);
+            }
+        }
+    }
+    stale
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400);
+    civil_date(days as i64)
+}
+
+/// Format a number of days since 1970-01-01 as a proleptic Gregorian date
+pub(crate) fn civil_date(days: i64) -> String {
+    // Howard Hinnant's days-to-civil algorithm, with eras of 400 years
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(11_016), "2000-02-29");
+        assert_eq!(civil_date(20_744), "2026-10-18");
+        assert_eq!(today().len(), 10);
+    }
+
+    #[test]
+    fn test_apply_modes() {
+        let input = "# Database\nDB_URL=x\nOLD_TOKEN=\"multi\nline\"\nLEGACY_FLAG=1\nKEEP_ME=1\n";
+        let used = HashSet::from(["DB_URL"]);
+        let keep = vec!["KEEP_*".to_string()];
+
+        let mut document = Document::parse(input).unwrap();
+        let stale = apply(&mut document, &used, &keep, StaleMode::Report, "2026-10-18");
+        assert_eq!(stale, vec!["OLD_TOKEN", "LEGACY_FLAG"]);
+        assert_eq!(document.to_string(), input);
+
+        let mut document = Document::parse(input).unwrap();
+        apply(&mut document, &used, &keep, StaleMode::Prune, "2026-10-18");
+        assert_eq!(document.to_string(), "# Database\nDB_URL=x\nKEEP_ME=1\n");
+
+        let mut document = Document::parse(input).unwrap();
+        apply(
+            &mut document,
+            &used,
+            &keep,
+            StaleMode::Comment,
+            "2026-10-18",
+        );
+        let commented = "# Database\nDB_URL=x\n# unused since 2026-10-18\n# OLD_TOKEN=\"multi\n# line\"\n# unused since 2026-10-18\n# LEGACY_FLAG=1\nKEEP_ME=1\n";
+        assert_eq!(document.to_string(), commented);
+
+        // Commenting out again changes nothing, and a variable used again comes back
+        let mut document = Document::parse(commented).unwrap();
+        let used = HashSet::from(["DB_URL", "OLD_TOKEN"]);
+        let stale = apply(
+            &mut document,
+            &used,
+            &keep,
+            StaleMode::Comment,
+            "2026-11-01",
+        );
+        assert!(stale.is_empty());
+        assert_eq!(
+            document.to_string(),
+            "# Database\nDB_URL=x\nOLD_TOKEN=\"multi\nline\"\n# unused since 2026-10-18\n# LEGACY_FLAG=1\nKEEP_ME=1\n"
+        );
+        assert_eq!(document.get("OLD_TOKEN"), Some("multi\nline"));
+    }
+}
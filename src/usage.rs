@@ -0,0 +1,157 @@
+//! Inference of how a variable is used from the code around its lookup
+//!
+//! Looking at the method chain following `env::var("NAME")` tells whether the
+//! variable is required (`.unwrap()`, `.expect(..)`, `?`), optional (`.ok()`,
+//! `.unwrap_or(..)`, `if let Ok(..)`), which default it falls back to, and which
+//! type it is parsed into (`.parse::<u16>()`).
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Method call at the start of a chain, e.g. `.parse::<u16>(`
+static METHOD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*\.\s*([A-Za-z_][A-Za-z0-9_]*)\s*(?:::\s*<\s*([^>]+?)\s*>)?\s*\(").unwrap()
+});
+
+/// First string literal in a piece of code
+static STRING_LITERAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""((?:[^"\\]|\\.)*)""#).unwrap());
+
+/// Lookup used as the scrutinee of an `if let`/`while let` or `match`
+static CONDITIONAL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:(?:if|while)\s+let\s+(?:Ok|Some)\s*\([^()]*\)\s*=|match)\s*$").unwrap()
+});
+
+/// What the code around a lookup reveals about the variable
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Usage {
+    pub required: Option<bool>,
+    pub default: Option<String>,
+    pub var_type: Option<String>,
+}
+
+/// Return the arguments of a call whose opening parenthesis was just consumed,
+/// and the rest of the text after the closing parenthesis
+fn split_call_args(text: &str) -> Option<(&str, &str)> {
+    let mut depth = 1;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (index, ch) in text.char_indices() {
+        if in_string {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match ch {
+            '"' => in_string = true,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&text[..index], &text[index + 1..]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Infer usage from the code before the lookup call and the code after it
+pub(crate) fn infer(prefix: &str, tail: &str) -> Usage {
+    let mut usage = Usage::default();
+
+    if CONDITIONAL.is_match(prefix) {
+        usage.required = Some(false);
+    }
+
+    let mut rest = tail;
+    loop {
+        if rest.trim_start().starts_with('?') {
+            usage.required.get_or_insert(true);
+            rest = &rest.trim_start()[1..];
+            continue;
+        }
+
+        let Some(cap) = METHOD.captures(rest) else {
+            break;
+        };
+        let method = cap.get(1).map_or("", |m| m.as_str());
+        let Some((args, after)) = split_call_args(&rest[cap.get(0).unwrap().end()..]) else {
+            break;
+        };
+
+        match method {
+            "unwrap" | "expect" => {
+                usage.required.get_or_insert(true);
+            }
+            "ok" | "is_ok" | "is_err" | "unwrap_or_default" | "unwrap_or" | "unwrap_or_else"
+            | "or" | "or_else" => {
+                usage.required.get_or_insert(false);
+                if usage.default.is_none() && matches!(method, "unwrap_or" | "unwrap_or_else") {
+                    usage.default = STRING_LITERAL
+                        .captures(args)
+                        .and_then(|cap| cap.get(1))
+                        .map(|m| m.as_str().to_string());
+                }
+            }
+            "parse" => {
+                if let Some(ty) = cap.get(2) {
+                    usage
+                        .var_type
+                        .get_or_insert_with(|| ty.as_str().to_string());
+                }
+            }
+            _ => {}
+        }
+
+        rest = after;
+    }
+
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_required_and_optional() {
+        assert_eq!(infer("let a = ", ".unwrap();").required, Some(true));
+        assert_eq!(
+            infer("let a = ", r#".expect("set A");"#).required,
+            Some(true)
+        );
+        assert_eq!(infer("let a = ", "?;").required, Some(true));
+        assert_eq!(infer("let a = ", ".ok();").required, Some(false));
+        assert_eq!(infer("if let Ok(value) = ", " {").required, Some(false));
+        assert_eq!(infer("match ", " {").required, Some(false));
+        assert_eq!(infer("let a = ", ";").required, None);
+    }
+
+    #[test]
+    fn test_infer_default_and_type() {
+        let usage = infer(
+            "let port: u16 = ",
+            r#".unwrap_or_else(|_| "3000".to_string()).parse::<u16>()?;"#,
+        );
+        assert_eq!(
+            usage,
+            Usage {
+                required: Some(false),
+                default: Some("3000".to_string()),
+                var_type: Some("u16".to_string()),
+            }
+        );
+
+        let usage = infer("let host = ", r#".unwrap_or("local(host)".into());"#);
+        assert_eq!(usage.default, Some("local(host)".to_string()));
+    }
+}
@@ -0,0 +1,393 @@
+//! Validating a runtime environment against what the code reads
+//!
+//! [`validate_directory`] scans a directory and checks a set of values, read
+//! from an env file or taken from the process environment, against the
+//! variables found: every required variable must be set to a non-empty value,
+//! values must parse as the type the code parses them into, references to
+//! other variables must resolve, and keys the code never reads are reported
+//! as unknown.
+
+use crate::EnvScanner;
+use crate::dotenv::Document;
+use crate::interpolate::{self, Problem, Resolved};
+use crate::workspace::glob_match;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+/// Exit status when required variables are missing or empty
+pub const EXIT_MISSING: i32 = 3;
+/// Exit status when values don't parse as their expected type or have
+/// references that can't be resolved
+pub const EXIT_INVALID: i32 = 4;
+/// Exit status when the only problem is keys the code doesn't read
+pub const EXIT_UNKNOWN: i32 = 5;
+
+/// A value that doesn't parse as the type the code expects
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct InvalidValue {
+    /// Name of the variable
+    pub name: String,
+    /// Type the value should parse as, e.g. `u16`
+    pub expected: String,
+}
+
+/// Problems found validating a set of values
+///
+/// Values are deliberately left out, as they often hold secrets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    /// Where the values came from, e.g. the env file path
+    pub source: String,
+    /// Required variables that are unset or empty, sorted
+    pub missing: Vec<String>,
+    /// Variables whose value doesn't parse as their type, sorted by name
+    pub invalid: Vec<InvalidValue>,
+    /// References to undefined variables and to variables only set further
+    /// down the file, in file order
+    pub unresolved: Vec<Problem>,
+    /// Keys set that the code doesn't read, sorted
+    pub unknown: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Whether no problems were found
+    pub fn is_valid(&self) -> bool {
+        self.missing.is_empty()
+            && self.invalid.is_empty()
+            && self.unresolved.is_empty()
+            && self.unknown.is_empty()
+    }
+
+    /// Exit status for the most serious problem found, 0 if there is none
+    ///
+    /// Missing variables outrank invalid values, which outrank unknown keys.
+    pub fn exit_code(&self) -> i32 {
+        if !self.missing.is_empty() {
+            EXIT_MISSING
+        } else if !self.invalid.is_empty() || !self.unresolved.is_empty() {
+            EXIT_INVALID
+        } else if !self.unknown.is_empty() {
+            EXIT_UNKNOWN
+        } else {
+            0
+        }
+    }
+
+    /// Render the report as plain text
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        if self.is_valid() {
+            writeln!(out, "No problems found in {}.", self.source).unwrap();
+            return out;
+        }
+
+        if !self.missing.is_empty() {
+            writeln!(out, "Missing or empty required variables:").unwrap();
+            for name in &self.missing {
+                writeln!(out, "  ! {}", name).unwrap();
+            }
+        }
+        if !self.invalid.is_empty() {
+            writeln!(out, "Invalid values:").unwrap();
+            for invalid in &self.invalid {
+                writeln!(out, "  ~ {}: expected {}", invalid.name, invalid.expected).unwrap();
+            }
+        }
+        if !self.unresolved.is_empty() {
+            writeln!(out, "Unresolvable references:").unwrap();
+            for problem in &self.unresolved {
+                writeln!(out, "  ~ {}", problem).unwrap();
+            }
+        }
+        if !self.unknown.is_empty() {
+            writeln!(out, "Warning: variables not read by the code:").unwrap();
+            for name in &self.unknown {
+                writeln!(out, "  ? {}", name).unwrap();
+            }
+        }
+        out
+    }
+
+    /// Render the report as Markdown
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "## Validation of `{}`", self.source).unwrap();
+        writeln!(out).unwrap();
+        if self.is_valid() {
+            writeln!(out, "No problems found.").unwrap();
+            return out;
+        }
+
+        writeln!(out, "| Variable | Problem |").unwrap();
+        writeln!(out, "| --- | --- |").unwrap();
+        for name in &self.missing {
+            writeln!(out, "| `{}` | missing |", name).unwrap();
+        }
+        for invalid in &self.invalid {
+            writeln!(
+                out,
+                "| `{}` | not a valid `{}` |",
+                invalid.name, invalid.expected
+            )
+            .unwrap();
+        }
+        for problem in &self.unresolved {
+            writeln!(out, "| `{}` | {} |", problem.key(), problem).unwrap();
+        }
+        for name in &self.unknown {
+            writeln!(out, "| `{}` | unknown |", name).unwrap();
+        }
+        out
+    }
+
+    /// Render the report as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize validation report")
+    }
+}
+
+/// Read the values of an env file, the last assignment of a key winning
+///
+/// References to other variables are expanded. They are only looked up in
+/// the file itself, since the environment it will be loaded into is unknown.
+pub fn read_env_file<P: AsRef<Path>>(scanner: &EnvScanner, path: P) -> Result<Resolved> {
+    let document = Document::read_as(path, scanner.dialect())?;
+    let entries: Vec<_> = document.entries().cloned().collect();
+    Ok(interpolate::resolve(&entries, &|_| None))
+}
+
+/// Validate the env file at `path` against the variables the code in `dir` reads
+///
+/// Combines [`read_env_file`] and [`validate_directory`], also reporting the
+/// references that can't be resolved.
+pub fn validate_env_file<P, F>(scanner: &EnvScanner, dir: P, path: F) -> Result<ValidationReport>
+where
+    P: AsRef<Path>,
+    F: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let resolved = read_env_file(scanner, path)?;
+    let mut report = validate_directory(scanner, dir, &resolved.values, true)?;
+    report.source = path.display().to_string();
+    report.unresolved = resolved.problems;
+    Ok(report)
+}
+
+/// Values of the process environment, skipping those that aren't valid UTF-8
+pub fn process_env() -> BTreeMap<String, String> {
+    std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
+}
+
+/// Check `values` against the variables the code in `dir` reads
+///
+/// A variable is required when the code unwraps its lookup. Types come from
+/// the `types` setting, or else from `.parse::<T>()` calls; values of types
+/// not known here aren't checked. Keys the code doesn't read, apart from
+/// those matching `keep_unused`, are reported as unknown if `report_unknown`
+/// is set, which makes little sense for the process environment.
+pub fn validate_directory<P: AsRef<Path>>(
+    scanner: &EnvScanner,
+    dir: P,
+    values: &BTreeMap<String, String>,
+    report_unknown: bool,
+) -> Result<ValidationReport> {
+    let dir = dir.as_ref();
+    let scanned = scanner.scan_tree(dir)?;
+    let plan = scanner.plan_scanned(dir, &scanned)?;
+    let infos = scanner.describe(&scanned);
+    let declared = scanner.declared_types();
+    let value = |name: &str| values.get(name).filter(|value| !value.is_empty());
+
+    let missing = infos
+        .values()
+        .filter(|info| info.required == Some(true) && plan.variables.contains(&info.name))
+        .filter(|info| value(&info.name).is_none())
+        .map(|info| info.name.clone())
+        .collect();
+
+    let mut expected: BTreeMap<&str, &str> = infos
+        .values()
+        .filter_map(|info| Some((info.name.as_str(), info.var_type.as_deref()?)))
+        .collect();
+    expected.extend(
+        declared
+            .iter()
+            .map(|(name, ty)| (name.as_str(), ty.as_str())),
+    );
+    let invalid = expected
+        .into_iter()
+        .filter(|(name, ty)| value(name).is_some_and(|value| parses_as(value, ty) == Some(false)))
+        .map(|(name, ty)| InvalidValue {
+            name: name.to_string(),
+            expected: ty.to_string(),
+        })
+        .collect();
+
+    let known = plan.names();
+    let unknown = if report_unknown {
+        values
+            .keys()
+            .filter(|key| !known.contains(key.as_str()) && !declared.contains_key(*key))
+            .filter(|key| {
+                !scanner
+                    .keep_unused()
+                    .iter()
+                    .any(|pattern| glob_match(pattern, key))
+            })
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    Ok(ValidationReport {
+        source: String::new(),
+        missing,
+        invalid,
+        unresolved: Vec::new(),
+        unknown,
+    })
+}
+
+/// Whether `value` parses as the Rust type `ty` the way `str::parse` would,
+/// `None` for types not known here
+fn parses_as(value: &str, ty: &str) -> Option<bool> {
+    // Paths such as `std::net::SocketAddr` name the same type as the last segment
+    let ty = ty.rsplit("::").next().unwrap_or(ty).trim();
+    let valid = match ty {
+        "u8" => value.parse::<u8>().is_ok(),
+        "u16" => value.parse::<u16>().is_ok(),
+        "u32" => value.parse::<u32>().is_ok(),
+        "u64" => value.parse::<u64>().is_ok(),
+        "u128" => value.parse::<u128>().is_ok(),
+        "usize" => value.parse::<usize>().is_ok(),
+        "i8" => value.parse::<i8>().is_ok(),
+        "i16" => value.parse::<i16>().is_ok(),
+        "i32" => value.parse::<i32>().is_ok(),
+        "i64" => value.parse::<i64>().is_ok(),
+        "i128" => value.parse::<i128>().is_ok(),
+        "isize" => value.parse::<isize>().is_ok(),
+        "f32" => value.parse::<f32>().is_ok(),
+        "f64" => value.parse::<f64>().is_ok(),
+        "bool" => value.parse::<bool>().is_ok(),
+        "char" => value.parse::<char>().is_ok(),
+        "String" | "PathBuf" | "OsString" => true,
+        "IpAddr" => value.parse::<IpAddr>().is_ok(),
+        "Ipv4Addr" => value.parse::<Ipv4Addr>().is_ok(),
+        "Ipv6Addr" => value.parse::<Ipv6Addr>().is_ok(),
+        "SocketAddr" => value.parse::<SocketAddr>().is_ok(),
+        _ => return None,
+    };
+    Some(valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parses_as() {
+        assert_eq!(parses_as("8080", "u16"), Some(true));
+        assert_eq!(parses_as("80800", "u16"), Some(false));
+        assert_eq!(parses_as("yes", "bool"), Some(false));
+        assert_eq!(
+            parses_as("127.0.0.1:80", "std::net::SocketAddr"),
+            Some(true)
+        );
+        assert_eq!(parses_as("localhost", "IpAddr"), Some(false));
+        assert_eq!(parses_as("anything", "Url"), None);
+    }
+
+    #[test]
+    fn test_validate_directory() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join("main.rs"),
+            r#"fn main() {
+                let key = std::env::var("API_KEY").unwrap();
+                let port = std::env::var("PORT").unwrap().parse::<u16>().unwrap();
+                let workers = std::env::var("WORKERS").ok();
+                let debug = std::env::var("DEBUG").unwrap_or_default();
+            }"#,
+        )?;
+        let env_file = temp_dir.path().join(".env.production");
+        fs::write(
+            &env_file,
+            "API_KEY=\nPORT=http\nWORKERS=four\nDEBUG=yes\nLEGACY=1\nLOCAL_X=1\n",
+        )?;
+
+        let config = Config {
+            keep_unused: Some(vec!["LOCAL_*".to_string()]),
+            types: Some(BTreeMap::from([(
+                "WORKERS".to_string(),
+                "usize".to_string(),
+            )])),
+            ..Default::default()
+        };
+        let scanner = EnvScanner::with_config(config)?;
+        let values = read_env_file(&scanner, &env_file)?.values;
+
+        let report = validate_directory(&scanner, temp_dir.path(), &values, true)?;
+        assert_eq!(report.missing, vec!["API_KEY"]);
+        let invalid: Vec<_> = report.invalid.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(invalid, vec!["PORT", "WORKERS"]);
+        assert_eq!(report.unknown, vec!["LEGACY"]);
+        assert_eq!(report.exit_code(), EXIT_MISSING);
+        let text = report.to_text();
+        assert!(text.contains("  ~ PORT: expected u16\n"));
+        assert!(!text.contains("http"), "values must not be printed");
+
+        let values = BTreeMap::from([
+            ("API_KEY".to_string(), "secret".to_string()),
+            ("PORT".to_string(), "8080".to_string()),
+            ("LEGACY".to_string(), "1".to_string()),
+        ]);
+        let report = validate_directory(&scanner, temp_dir.path(), &values, true)?;
+        assert_eq!(report.exit_code(), EXIT_UNKNOWN);
+        let report = validate_directory(&scanner, temp_dir.path(), &values, false)?;
+        assert!(report.is_valid());
+        assert_eq!(report.exit_code(), 0);
+
+        // References are expanded before checking, and unresolvable ones are invalid
+        fs::write(
+            &env_file,
+            "API_KEY=${SECRET}\nBASE_PORT=8080\nPORT=${BASE_PORT}\nDEBUG=$DEBUG_DEFAULT\nDEBUG_DEFAULT=${DEBUG}\n",
+        )?;
+        let report = validate_env_file(&scanner, temp_dir.path(), &env_file)?;
+        assert_eq!(report.missing, vec!["API_KEY"]);
+        assert!(report.invalid.is_empty());
+        assert_eq!(
+            report
+                .unresolved
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "API_KEY (line 1) refers to SECRET, which is not set",
+                "DEBUG (line 4) refers to DEBUG_DEFAULT, which is only set further down (line 5)",
+            ]
+        );
+        assert_eq!(report.unknown, vec!["BASE_PORT", "DEBUG_DEFAULT"]);
+
+        // A cycle through forward references fails like an invalid value
+        fs::write(
+            &env_file,
+            "API_KEY=key\nPORT=80\nWORKERS=$DEBUG\nDEBUG=${WORKERS}\n",
+        )?;
+        let report = validate_env_file(&scanner, temp_dir.path(), &env_file)?;
+        assert!(report.missing.is_empty());
+        assert_eq!(report.unresolved.len(), 1);
+        assert_eq!(report.exit_code(), EXIT_INVALID);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! Environment variable usage inside dependency sources
+//!
+//! Scans the sources of third-party crates listed in `Cargo.lock` to audit
+//! which of them read which variables. Sources are taken from a `cargo vendor`
+//! directory when there is one, and otherwise from the local registry checkout
+//! in `$CARGO_HOME/registry/src`; nothing is downloaded.
+
+use crate::dependencies::{self, LockedPackage};
+use crate::source::FsSource;
+use crate::{Config, EnvScanner, VariableInfo};
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Default directory `cargo vendor` writes to
+const DEFAULT_VENDOR_DIR: &str = "vendor";
+
+/// Variables read by one third-party crate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CrateUsage {
+    /// Crate name
+    pub name: String,
+    /// Crate version from `Cargo.lock`
+    pub version: String,
+    /// Directory the sources were read from
+    pub path: PathBuf,
+    /// Variables read by the crate, with locations relative to `path`
+    pub variables: BTreeMap<String, VariableInfo>,
+}
+
+/// Result of scanning the dependencies of a project
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DependencyScan {
+    /// Crates that read at least one variable, sorted by name and version
+    pub crates: Vec<CrateUsage>,
+    /// Number of crates whose sources were scanned
+    pub scanned: usize,
+    /// Crates without local sources, as `name version`
+    pub missing: Vec<String>,
+    /// Crates whose sources could not be scanned, as `name version` with the error
+    pub failed: Vec<(String, String)>,
+}
+
+/// Cargo configuration files that may set a vendor directory, in lookup order
+const CARGO_CONFIGS: [&str; 2] = [".cargo/config.toml", ".cargo/config"];
+
+/// Read the vendor directory configured in `.cargo/config.toml`, if any
+fn configured_vendor_dir(dir: &Path) -> Option<PathBuf> {
+    vendor_dir_in(|name| fs::read_to_string(dir.join(name)).ok()).map(|path| dir.join(path))
+}
+
+/// Vendor directory set by the Cargo configuration `read` returns, relative
+/// to the project root unless configured as an absolute path
+///
+/// `read` is given each of [`CARGO_CONFIGS`] and returns its content, if any.
+fn vendor_dir_in(read: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    CARGO_CONFIGS
+        .iter()
+        .filter_map(|name| read(name))
+        .filter_map(|content| toml::from_str::<toml::Value>(&content).ok())
+        .find_map(|config| {
+            config
+                .get("source")?
+                .as_table()?
+                .values()
+                .find_map(|source| source.get("directory")?.as_str().map(PathBuf::from))
+        })
+}
+
+/// Directory vendored sources live in, whether or not it exists
+///
+/// First-party scans leave it out, since its crates are audited separately.
+pub(crate) fn vendor_dir(dir: &Path) -> PathBuf {
+    configured_vendor_dir(dir).unwrap_or_else(|| dir.join(DEFAULT_VENDOR_DIR))
+}
+
+/// Like [`vendor_dir`], with the Cargo configuration files read through `read`
+/// and the directory relative to the project root, e.g. for a git revision
+pub(crate) fn relative_vendor_dir(read: impl Fn(&str) -> Option<String>) -> PathBuf {
+    vendor_dir_in(read).unwrap_or_else(|| PathBuf::from(DEFAULT_VENDOR_DIR))
+}
+
+/// Directories holding registry checkouts, e.g. `~/.cargo/registry/src/index.crates.io-*`
+fn registry_dirs() -> Vec<PathBuf> {
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")));
+    let Some(cargo_home) = cargo_home else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<PathBuf> = fs::read_dir(cargo_home.join("registry/src"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Version declared in a crate's `Cargo.toml`
+fn manifest_version(dir: &Path) -> Option<String> {
+    let content = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    let manifest: toml::Value = toml::from_str(&content).ok()?;
+    manifest
+        .get("package")?
+        .get("version")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Find the local source directory of a locked package
+///
+/// `cargo vendor` names directories `name`, or `name-version` when several
+/// versions are vendored; registry checkouts are always `name-version`.
+fn find_sources(
+    package: &LockedPackage,
+    vendor_dir: Option<&Path>,
+    registries: &[PathBuf],
+) -> Option<PathBuf> {
+    let versioned = format!("{}-{}", package.name, package.version);
+
+    if let Some(vendor_dir) = vendor_dir {
+        let candidate = vendor_dir.join(&versioned);
+        if candidate.is_dir() {
+            return Some(candidate);
+        }
+        let candidate = vendor_dir.join(&package.name);
+        if manifest_version(&candidate).as_deref() == Some(package.version.as_str()) {
+            return Some(candidate);
+        }
+    }
+
+    registries
+        .iter()
+        .map(|registry| registry.join(&versioned))
+        .find(|candidate| candidate.is_dir())
+}
+
+/// Scan the sources of all third-party dependencies of the project in `dir`
+///
+/// Only packages with a source in `Cargo.lock` are considered, i.e. not the
+/// workspace's own crates. The scan cache is never used for dependency sources
+/// so that nothing is written into the vendor directory or the registry, and
+/// only Rust sources are scanned. A crate whose sources can't be read is
+/// recorded in `failed` and the others are still scanned.
+pub fn scan_dependencies<P: AsRef<Path>>(scanner: &EnvScanner, dir: P) -> Result<DependencyScan> {
+    scan_dependencies_in(scanner, dir.as_ref(), &registry_dirs())
+}
+
+fn scan_dependencies_in(
+    scanner: &EnvScanner,
+    dir: &Path,
+    registries: &[PathBuf],
+) -> Result<DependencyScan> {
+    let scanner = EnvScanner::with_config(Config {
+        cache: Some(false),
+        dependency_variables: Some(false),
+        deployment_files: Some(false),
+        languages: None,
+        plugins: None,
+        ..scanner.config.clone()
+    })?;
+
+    let vendor_dir = Some(vendor_dir(dir)).filter(|path| path.is_dir());
+
+    let mut packages: Vec<LockedPackage> = dependencies::locked_packages(dir)?
+        .into_iter()
+        .filter(|package| package.source.is_some())
+        .collect();
+    packages.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    let results: Vec<(LockedPackage, Option<Result<CrateUsage>>)> = packages
+        .into_par_iter()
+        .map(|package| {
+            let usage = find_sources(&package, vendor_dir.as_deref(), registries).map(|path| {
+                let variables = scanner.scan_source_detailed(&FsSource::new(&path))?;
+                Ok(CrateUsage {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    path,
+                    variables,
+                })
+            });
+            (package, usage)
+        })
+        .collect();
+
+    let mut scan = DependencyScan::default();
+    for (package, usage) in results {
+        let id = format!("{} {}", package.name, package.version);
+        match usage {
+            Some(Ok(usage)) => {
+                scan.scanned += 1;
+                if !usage.variables.is_empty() {
+                    scan.crates.push(usage);
+                }
+            }
+            Some(Err(err)) => scan.failed.push((id, format!("{:#}", err))),
+            None => scan.missing.push(id),
+        }
+    }
+
+    Ok(scan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_scan_vendored_and_registry_sources() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path().join("project");
+        let registry = temp_dir.path().join("registry/index.crates.io-0000");
+        write(
+            &root,
+            "Cargo.lock",
+            "version = 3\n\n\
+             [[package]]\nname = \"project\"\nversion = \"0.1.0\"\n\n\
+             [[package]]\nname = \"proxy-client\"\nversion = \"1.2.0\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"quiet\"\nversion = \"0.3.0\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"logger\"\nversion = \"2.0.0\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"absent\"\nversion = \"0.1.0\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"broken\"\nversion = \"0.2.0\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        );
+        write(
+            &root,
+            "src/main.rs",
+            r#"fn main() { std::env::var("OWN_VAR").unwrap(); }"#,
+        );
+        write(
+            &root,
+            "vendor/proxy-client/Cargo.toml",
+            "[package]\nname = \"proxy-client\"\nversion = \"1.2.0\"\n",
+        );
+        write(
+            &root,
+            "vendor/proxy-client/src/lib.rs",
+            r#"pub fn proxy() { std::env::var("HTTPS_PROXY").ok(); }"#,
+        );
+        write(&root, "vendor/quiet-0.3.0/src/lib.rs", "pub fn f() {}");
+        fs::create_dir_all(root.join("vendor/broken-0.2.0/src"))?;
+        fs::write(
+            root.join("vendor/broken-0.2.0/src/lib.rs"),
+            [0xff, 0xfe, 0x00],
+        )?;
+        write(
+            &registry,
+            "logger-2.0.0/src/lib.rs",
+            r#"pub fn init() { std::env::var("LOG_LEVEL").ok(); }"#,
+        );
+
+        let scanner = EnvScanner::new()?;
+        assert_eq!(
+            scanner.scan_directory(&root)?,
+            HashSet::from(["OWN_VAR".to_string()])
+        );
+        let scan = scan_dependencies_in(&scanner, &root, &[registry])?;
+
+        let crates: Vec<(&str, Vec<&String>)> = scan
+            .crates
+            .iter()
+            .map(|usage| (usage.name.as_str(), usage.variables.keys().collect()))
+            .collect();
+        assert_eq!(
+            crates,
+            vec![
+                ("logger", vec![&"LOG_LEVEL".to_string()]),
+                ("proxy-client", vec![&"HTTPS_PROXY".to_string()]),
+            ]
+        );
+        assert_eq!(scan.scanned, 3);
+        assert_eq!(scan.missing, vec!["absent 0.1.0".to_string()]);
+        assert_eq!(scan.failed.len(), 1);
+        assert_eq!(scan.failed[0].0, "broken 0.2.0");
+        assert!(!root.join("vendor/proxy-client/target").exists());
+
+        Ok(())
+    }
+}
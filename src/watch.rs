@@ -0,0 +1,311 @@
+//! Watch mode: regenerate the output file whenever the scanned sources change
+//!
+//! File system events are delivered by the platform's native watcher (inotify on
+//! Linux). Bursts of events are debounced into a single rescan, which reuses the
+//! scan cache so only the touched files are read again.
+
+use crate::{EnvPlan, EnvScanner};
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Default quiet period after the last file system event before rescanning
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Variables added to or removed from the detected set between two scans
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VariableChanges {
+    /// Variables that are detected now but were not before, sorted by name
+    pub added: Vec<String>,
+    /// Variables that were detected before but are not anymore, sorted by name
+    pub removed: Vec<String>,
+}
+
+impl VariableChanges {
+    /// Compute the changes going from `old` to `new`
+    pub fn between(old: &HashSet<&str>, new: &HashSet<&str>) -> Self {
+        let mut added: Vec<_> = new.difference(old).map(|name| name.to_string()).collect();
+        let mut removed: Vec<_> = old.difference(new).map(|name| name.to_string()).collect();
+        added.sort();
+        removed.sort();
+        Self { added, removed }
+    }
+
+    /// Whether the variable set is unchanged
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Check whether a path is a Cargo file the plan depends on: manifests and the
+/// lock file for dependency variables, `.cargo/config.toml` for `[env]`
+fn is_cargo_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    match name {
+        "Cargo.toml" | "Cargo.lock" => true,
+        "config.toml" | "config" => path
+            .parent()
+            .and_then(Path::file_name)
+            .is_some_and(|dir| dir == ".cargo"),
+        _ => false,
+    }
+}
+
+/// Check whether an event touches a file that can influence the generated file
+fn is_relevant(scanner: &EnvScanner, event: &Event, root: &Path) -> bool {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+
+    event.paths.iter().any(|path| {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        scanner.scans(relative) || is_cargo_file(relative)
+    })
+}
+
+/// Replan `dir` and rewrite `output_path` if the plan differs from `plan`
+///
+/// Returns the change in the set of variables, or `None` when nothing had to be
+/// written.
+fn sync(
+    scanner: &EnvScanner,
+    dir: &Path,
+    output_path: &Path,
+    plan: &mut Option<EnvPlan>,
+) -> Result<Option<VariableChanges>> {
+    let replanned = scanner.plan_directory(dir)?;
+    if plan.as_ref() == Some(&replanned) {
+        return Ok(None);
+    }
+
+    scanner.generate_annotated_env_file(&replanned.variables, &replanned.sections, output_path)?;
+    let previous = plan.as_ref().map(EnvPlan::names).unwrap_or_default();
+    let changes = VariableChanges::between(&previous, &replanned.names());
+    *plan = Some(replanned);
+    Ok(Some(changes))
+}
+
+/// Watch `dir` and keep `output_path` in sync with the detected variables
+///
+/// The directory is planned and the output file generated once up front, as
+/// `generate` does. After that, every debounced burst of source changes
+/// triggers a rescan; the output file is rewritten only when the plan changed,
+/// and `on_change` is called with the difference when the set of variables did.
+/// Watching stops when `on_change` returns [`ControlFlow::Break`].
+///
+/// Failing to scan or write is passed to `on_error` and watching goes on, so
+/// that a file saved halfway or a briefly locked output doesn't end the session.
+/// Only a failure of the file system watcher itself is returned.
+pub fn watch_directory<P, O, F, E>(
+    scanner: &EnvScanner,
+    dir: P,
+    output_path: O,
+    debounce: Duration,
+    mut on_change: F,
+    mut on_error: E,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    O: AsRef<Path>,
+    F: FnMut(&VariableChanges) -> ControlFlow<()>,
+    E: FnMut(&anyhow::Error),
+{
+    let dir = dir.as_ref();
+    let output_path = output_path.as_ref();
+    // Event paths are absolute, so compare against the canonical root
+    let root = dir
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve directory: {:?}", dir))?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).context("Failed to create file system watcher")?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch directory: {:?}", root))?;
+
+    let mut plan = None;
+    if let Err(err) = sync(scanner, dir, output_path, &mut plan) {
+        on_error(&err);
+    }
+
+    loop {
+        // Block until something relevant happens
+        let event = rx.recv().context("File system watcher stopped")?;
+        if !event.is_ok_and(|event| is_relevant(scanner, &event, &root)) {
+            continue;
+        }
+
+        // Swallow the rest of the burst until the tree has been quiet for a while
+        loop {
+            match rx.recv_timeout(debounce) {
+                Ok(_) => continue,
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    anyhow::bail!("File system watcher stopped")
+                }
+            }
+        }
+
+        match sync(scanner, dir, output_path, &mut plan) {
+            Ok(Some(changes)) if !changes.is_empty() => {
+                if on_change(&changes).is_break() {
+                    return Ok(());
+                }
+            }
+            Ok(_) => {}
+            Err(err) => on_error(&err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use tempfile::TempDir;
+
+    fn set<'a>(names: &[&'a str]) -> HashSet<&'a str> {
+        names.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_variable_changes() {
+        let changes = VariableChanges::between(&set(&["A", "B", "C"]), &set(&["B", "D", "C"]));
+        assert_eq!(changes.added, vec!["D".to_string()]);
+        assert_eq!(changes.removed, vec!["A".to_string()]);
+
+        assert!(VariableChanges::between(&set(&["A"]), &set(&["A"])).is_empty());
+    }
+
+    #[test]
+    fn test_cargo_files_are_relevant() -> Result<()> {
+        let scanner = EnvScanner::new()?;
+        let root = Path::new("/project");
+        let event = |path: &str| Event::new(EventKind::Any).add_path(root.join(path));
+
+        for path in [
+            "Cargo.lock",
+            "crates/api/Cargo.toml",
+            ".cargo/config.toml",
+            "src/main.rs",
+        ] {
+            assert!(is_relevant(&scanner, &event(path), root), "{}", path);
+        }
+        for path in ["README.md", "target/debug/build.log", "config.toml"] {
+            assert!(!is_relevant(&scanner, &event(path), root), "{}", path);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_regenerates_on_change() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path().to_path_buf();
+        fs::write(root.join("main.rs"), r#"fn main() { env::var("BEFORE"); }"#)?;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let writer_done = Arc::clone(&done);
+        let writer_root = root.clone();
+        let writer = thread::spawn(move || {
+            // The watcher may start after the first write, so keep introducing a new
+            // variable until it reports a change
+            for i in 0..50 {
+                if writer_done.load(Ordering::SeqCst) {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(100));
+                let content =
+                    format!(r#"fn main() {{ env::var("BEFORE"); env::var("AFTER_{i}"); }}"#);
+                fs::write(writer_root.join("main.rs"), content).unwrap();
+            }
+        });
+
+        let scanner = EnvScanner::new()?;
+        let mut seen = None;
+        watch_directory(
+            &scanner,
+            &root,
+            root.join(".env"),
+            Duration::from_millis(50),
+            |changes| {
+                seen = Some(changes.clone());
+                ControlFlow::Break(())
+            },
+            |err| panic!("unexpected error: {:#}", err),
+        )?;
+        done.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+
+        let changes = seen.expect("watcher should report a change");
+        assert_eq!(changes.added.len(), 1);
+        assert!(changes.added[0].starts_with("AFTER_"));
+        assert!(!changes.removed.contains(&"BEFORE".to_string()));
+        let output = fs::read_to_string(root.join(".env"))?;
+        assert!(output.contains(&format!("{}=", changes.added[0])));
+        assert!(output.contains("BEFORE="));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_keeps_going_after_errors() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path().to_path_buf();
+        fs::write(root.join("main.rs"), r#"fn main() { env::var("BEFORE"); }"#)?;
+        // Writing fails until the output directory exists
+        let output_path = root.join("out/.env");
+
+        let failed = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicBool::new(false));
+        let (writer_failed, writer_done) = (Arc::clone(&failed), Arc::clone(&done));
+        let writer_root = root.clone();
+        let writer = thread::spawn(move || {
+            for i in 0..50 {
+                if writer_done.load(Ordering::SeqCst) {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(100));
+                if writer_failed.load(Ordering::SeqCst) {
+                    fs::create_dir_all(writer_root.join("out")).unwrap();
+                }
+                let content =
+                    format!(r#"fn main() {{ env::var("BEFORE"); env::var("AFTER_{i}"); }}"#);
+                fs::write(writer_root.join("main.rs"), content).unwrap();
+            }
+        });
+
+        let scanner = EnvScanner::new()?;
+        let mut seen = None;
+        watch_directory(
+            &scanner,
+            &root,
+            &output_path,
+            Duration::from_millis(50),
+            |changes| {
+                seen = Some(changes.clone());
+                ControlFlow::Break(())
+            },
+            |_| failed.store(true, Ordering::SeqCst),
+        )?;
+        done.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+
+        assert!(failed.load(Ordering::SeqCst));
+        let changes = seen.expect("watcher should recover and report a change");
+        assert!(changes.added.contains(&"BEFORE".to_string()));
+        assert!(fs::read_to_string(&output_path)?.contains("BEFORE="));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,617 @@
+//! Cargo workspace awareness
+//!
+//! Discovers the packages of a workspace so that every variable can be
+//! attributed to the crate(s) reading it. Package information comes from
+//! `cargo metadata` when it can run offline, and otherwise from the `members`
+//! of the root `Cargo.toml`.
+
+use crate::{AnnotatedVariable, EnvScanner, EnvSection, FileScan, VariableInfo, dependencies};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// How `generate` lays out variables of a multi-crate workspace or multi-binary package
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkspaceOutput {
+    /// A single file listing every variable (the default)
+    #[default]
+    Combined,
+    /// One file per member crate, written into the crate's directory
+    PerCrate,
+    /// A single root file with one section per member crate
+    Sections,
+    /// One file per binary target, named after the binary (e.g. `.env.api`)
+    PerBinary,
+}
+
+/// A package of the workspace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Package {
+    /// Package name from its manifest
+    pub name: String,
+    /// Directory containing the package's `Cargo.toml`, relative to the workspace root
+    pub root: PathBuf,
+}
+
+/// The packages of a Cargo workspace (or of a single package)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Workspace {
+    /// Packages sorted by name
+    pub packages: Vec<Package>,
+}
+
+/// Variables of one package, or of files outside of every package
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PackageVariables {
+    /// The package, or `None` for files that belong to no package
+    pub package: Option<Package>,
+    /// Variables read by the package's files, with locations limited to those files
+    pub variables: BTreeMap<String, VariableInfo>,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+}
+
+#[derive(Deserialize)]
+struct MetadataPackage {
+    name: String,
+    manifest_path: PathBuf,
+}
+
+/// Match a single path component against a glob with `*` and `?` wildcards
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+/// Expand a workspace member pattern such as `crates/*` into directories
+fn expand_member(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::new()];
+
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if !component.contains(['*', '?']) {
+            candidates.iter_mut().for_each(|dir| dir.push(component));
+            continue;
+        }
+
+        candidates = candidates
+            .into_iter()
+            .flat_map(|dir| {
+                let mut matched: Vec<PathBuf> = fs::read_dir(root.join(&dir))
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter(|entry| entry.path().is_dir())
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .filter(|name| glob_match(component, name))
+                    .map(|name| dir.join(name))
+                    .collect();
+                matched.sort();
+                matched
+            })
+            .collect();
+    }
+
+    candidates
+}
+
+/// Read the package name from a `Cargo.toml`, if it declares a package
+fn package_name(manifest: &Path) -> Option<String> {
+    let content = fs::read_to_string(manifest).ok()?;
+    let value: toml::Value = toml::from_str(&content).ok()?;
+    value
+        .get("package")?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+impl Workspace {
+    /// Discover the packages of the workspace rooted at `dir`
+    ///
+    /// `cargo metadata --offline --no-deps` is tried first; if cargo is not
+    /// available or fails, the root manifest is read directly. A directory
+    /// without a `Cargo.toml` yields an empty workspace.
+    pub fn discover<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        if !dir.join("Cargo.toml").exists() {
+            return Ok(Self::default());
+        }
+
+        match Self::from_cargo_metadata(dir) {
+            Ok(workspace) => Ok(workspace),
+            Err(_) => Self::from_manifest(dir),
+        }
+    }
+
+    /// Discover packages using `cargo metadata`
+    fn from_cargo_metadata(dir: &Path) -> Result<Self> {
+        let output = Command::new("cargo")
+            .args([
+                "metadata",
+                "--offline",
+                "--no-deps",
+                "--format-version",
+                "1",
+            ])
+            .arg("--manifest-path")
+            .arg(dir.join("Cargo.toml"))
+            .output()
+            .context("Failed to run cargo metadata")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "cargo metadata failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let metadata: Metadata =
+            serde_json::from_slice(&output.stdout).context("Failed to parse cargo metadata")?;
+        let root = dir.canonicalize()?;
+
+        let mut packages: Vec<Package> = metadata
+            .packages
+            .into_iter()
+            .filter_map(|package| {
+                let manifest_dir = package.manifest_path.parent()?.canonicalize().ok()?;
+                let relative = manifest_dir.strip_prefix(&root).ok()?.to_path_buf();
+                Some(Package {
+                    name: package.name,
+                    root: relative,
+                })
+            })
+            .collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self { packages })
+    }
+
+    /// Discover packages from the `[workspace]` and `[package]` tables of the root manifest
+    fn from_manifest(dir: &Path) -> Result<Self> {
+        let manifest = dir.join("Cargo.toml");
+        let content = fs::read_to_string(&manifest)
+            .with_context(|| format!("Failed to read manifest: {:?}", manifest))?;
+        let value: toml::Value = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse manifest: {:?}", manifest))?;
+
+        let mut packages = Vec::new();
+        if let Some(name) = package_name(&manifest) {
+            packages.push(Package {
+                name,
+                root: PathBuf::new(),
+            });
+        }
+
+        let workspace = value.get("workspace");
+        let patterns = |key: &str| -> Vec<String> {
+            workspace
+                .and_then(|workspace| workspace.get(key))
+                .and_then(|list| list.as_array())
+                .map(|list| {
+                    list.iter()
+                        .filter_map(|item| item.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let excluded: HashSet<PathBuf> = patterns("exclude")
+            .iter()
+            .flat_map(|pattern| expand_member(dir, pattern))
+            .collect();
+
+        for pattern in patterns("members") {
+            for member in expand_member(dir, &pattern) {
+                if excluded.contains(&member) || member.as_os_str().is_empty() {
+                    continue;
+                }
+                if let Some(name) = package_name(&dir.join(&member).join("Cargo.toml")) {
+                    packages.push(Package { name, root: member });
+                }
+            }
+        }
+
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        packages.dedup();
+        Ok(Self { packages })
+    }
+
+    /// Whether the workspace has more than one package
+    pub fn is_multi_package(&self) -> bool {
+        self.packages.len() > 1
+    }
+
+    /// Find the package a file belongs to: the one with the deepest root containing it
+    pub fn package_for(&self, file: &Path) -> Option<&Package> {
+        self.packages
+            .iter()
+            .filter(|package| file.starts_with(&package.root))
+            .max_by_key(|package| package.root.components().count())
+    }
+
+    /// Split scan results by package, based on the location of every use
+    ///
+    /// A variable read by several packages appears under each of them. Files that
+    /// belong to no package are grouped under `package: None`, listed first.
+    pub fn attribute(&self, variables: &BTreeMap<String, VariableInfo>) -> Vec<PackageVariables> {
+        let mut groups: BTreeMap<Option<&str>, BTreeMap<String, VariableInfo>> = BTreeMap::new();
+
+        for info in variables.values() {
+            for location in &info.locations {
+                let package = self.package_for(&location.file).map(|p| p.name.as_str());
+                let entry = groups
+                    .entry(package)
+                    .or_default()
+                    .entry(info.name.clone())
+                    .or_insert_with(|| VariableInfo {
+                        locations: Vec::new(),
+                        ..info.clone()
+                    });
+                entry.locations.push(location.clone());
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(name, variables)| PackageVariables {
+                package: name.and_then(|name| {
+                    self.packages
+                        .iter()
+                        .find(|package| package.name == name)
+                        .cloned()
+                }),
+                variables,
+            })
+            .collect()
+    }
+}
+
+/// Write one env file per package into each package's directory
+///
+/// Each file lists what `generate` would for the package on its own: the
+/// variables its files read, with well-known and Cargo-provided ones handled
+/// as configured, and those read by the package's dependencies. Variables of
+/// files outside every package go into a file in `dir`. Returns the written
+/// paths with the number of variables in each.
+pub fn generate_per_crate<P: AsRef<Path>>(
+    scanner: &EnvScanner,
+    dir: P,
+    workspace: &Workspace,
+    output_file: &str,
+) -> Result<Vec<(PathBuf, usize)>> {
+    let dir = dir.as_ref();
+    let scanned = scanner.scan_tree(dir)?;
+
+    let mut groups: BTreeMap<Option<&str>, (Option<&Package>, Vec<&FileScan>)> = BTreeMap::new();
+    for (file, scan) in &scanned {
+        let package = workspace.package_for(file);
+        groups
+            .entry(package.map(|p| p.name.as_str()))
+            .or_insert_with(|| (package, Vec::new()))
+            .1
+            .push(scan);
+    }
+
+    let mut written = Vec::new();
+    for (package, scans) in groups.into_values() {
+        let (package_dir, locked) = match package {
+            Some(package) => (
+                dir.join(&package.root),
+                dependencies::usable_crates(scanner, dir, Some(&package.name))?,
+            ),
+            // Such files aren't compiled with any package's dependencies
+            None => (dir.to_path_buf(), HashSet::new()),
+        };
+        let plan = scanner.plan_files(&package_dir, &locked, &scans)?;
+        if plan.is_empty() {
+            continue;
+        }
+        let output_path = package_dir.join(output_file);
+        scanner.generate_annotated_env_file(&plan.variables, &plan.sections, &output_path)?;
+        written.push((output_path, plan.len()));
+    }
+
+    Ok(written)
+}
+
+/// Write a single env file with one section per package
+///
+/// Variables read by several packages are listed once, in a shared section.
+/// Variables read by dependencies, well-known ones and those Cargo provides
+/// follow in their own sections, as `generate` writes them. Returns the number
+/// of variables written, nothing being written if there are none.
+pub fn generate_sections<P: AsRef<Path>, O: AsRef<Path>>(
+    scanner: &EnvScanner,
+    dir: P,
+    workspace: &Workspace,
+    output_path: O,
+) -> Result<usize> {
+    let dir = dir.as_ref();
+    let scanned = scanner.scan_tree(dir)?;
+    let plan = scanner.plan_scanned(dir, &scanned)?;
+    if plan.is_empty() {
+        return Ok(0);
+    }
+
+    let mut variables = scanner.describe(&scanned);
+    variables.retain(|name, _| plan.variables.contains(name));
+    let groups = workspace.attribute(&variables);
+
+    let mut owners: BTreeMap<&str, usize> = BTreeMap::new();
+    for group in &groups {
+        for name in group.variables.keys() {
+            *owners.entry(name.as_str()).or_default() += 1;
+        }
+    }
+    let section = |title: &str, names: Vec<&str>| EnvSection {
+        title: format!("[{}]", title),
+        entries: names
+            .into_iter()
+            .map(|name| AnnotatedVariable {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect(),
+    };
+
+    let mut sections = Vec::new();
+    let shared: Vec<&str> = owners
+        .iter()
+        .filter(|&(_, &count)| count > 1)
+        .map(|(name, _)| *name)
+        .collect();
+    if !shared.is_empty() {
+        sections.push(section("shared", shared));
+    }
+
+    for group in &groups {
+        let title = group
+            .package
+            .as_ref()
+            .map_or("workspace root", |p| p.name.as_str());
+        let own: Vec<&str> = group
+            .variables
+            .keys()
+            .map(String::as_str)
+            .filter(|name| owners[name] == 1)
+            .collect();
+        if !own.is_empty() {
+            sections.push(section(title, own));
+        }
+    }
+    sections.extend(plan.sections.iter().cloned());
+
+    scanner.generate_annotated_env_file(&HashSet::new(), &sections, output_path)?;
+    Ok(plan.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, Location, StaleMode};
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn create_workspace() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"services/*\"]\nexclude = [\"services/legacy\"]\n",
+        );
+        write(
+            root,
+            "services/api/Cargo.toml",
+            "[package]\nname = \"api\"\nversion = \"0.1.0\"\n",
+        );
+        write(
+            root,
+            "services/worker/Cargo.toml",
+            "[package]\nname = \"worker\"\nversion = \"0.1.0\"\n",
+        );
+        write(
+            root,
+            "services/legacy/Cargo.toml",
+            "[package]\nname = \"legacy\"\nversion = \"0.1.0\"\n",
+        );
+        temp_dir
+    }
+
+    fn info(name: &str, files: &[&str]) -> (String, VariableInfo) {
+        let locations = files
+            .iter()
+            .map(|file| Location {
+                file: PathBuf::from(file),
+                line: 1,
+                kind: None,
+            })
+            .collect();
+        (
+            name.to_string(),
+            VariableInfo {
+                name: name.to_string(),
+                locations,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "api"));
+        assert!(glob_match("svc-*", "svc-api"));
+        assert!(glob_match("ap?", "api"));
+        assert!(!glob_match("svc-*", "api"));
+    }
+
+    #[test]
+    fn test_discover_from_manifest() -> Result<()> {
+        let temp_dir = create_workspace();
+        let workspace = Workspace::from_manifest(temp_dir.path())?;
+
+        assert_eq!(
+            workspace.packages,
+            vec![
+                Package {
+                    name: "api".to_string(),
+                    root: PathBuf::from("services/api"),
+                },
+                Package {
+                    name: "worker".to_string(),
+                    root: PathBuf::from("services/worker"),
+                },
+            ]
+        );
+        assert!(workspace.is_multi_package());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_attribute_variables_to_packages() -> Result<()> {
+        let temp_dir = create_workspace();
+        let workspace = Workspace::from_manifest(temp_dir.path())?;
+        let variables: BTreeMap<_, _> = [
+            info("DATABASE_URL", &["services/api/src/main.rs"]),
+            info("QUEUE_URL", &["services/worker/src/main.rs"]),
+            info(
+                "RUST_LOG",
+                &["services/api/src/main.rs", "services/worker/src/main.rs"],
+            ),
+            info("SCRIPT_VAR", &["tools/script.rs"]),
+        ]
+        .into_iter()
+        .collect();
+
+        let groups = workspace.attribute(&variables);
+        let summary: Vec<(Option<String>, Vec<String>)> = groups
+            .iter()
+            .map(|group| {
+                (
+                    group.package.as_ref().map(|p| p.name.clone()),
+                    group.variables.keys().cloned().collect(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (None, vec!["SCRIPT_VAR".to_string()]),
+                (
+                    Some("api".to_string()),
+                    vec!["DATABASE_URL".to_string(), "RUST_LOG".to_string()]
+                ),
+                (
+                    Some("worker".to_string()),
+                    vec!["QUEUE_URL".to_string(), "RUST_LOG".to_string()]
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_sections() -> Result<()> {
+        let temp_dir = create_workspace();
+        let root = temp_dir.path();
+        write(
+            root,
+            "services/api/src/main.rs",
+            r#"fn main() { env::var("DATABASE_URL"); env::var("SHARED_TOKEN"); }"#,
+        );
+        write(
+            root,
+            "services/worker/src/main.rs",
+            r#"fn main() { env::var("QUEUE_URL"); env::var("SHARED_TOKEN"); env::var("HOME"); }"#,
+        );
+        let workspace = Workspace::from_manifest(root)?;
+        let scanner = EnvScanner::new()?;
+
+        // Sections list shared variables once, and the catalogue applies as in `generate`
+        let output = root.join(".env");
+        assert_eq!(generate_sections(&scanner, root, &workspace, &output)?, 3);
+        let content = fs::read_to_string(&output)?;
+        assert_eq!(content.matches("SHARED_TOKEN=").count(), 1);
+        assert!(!content.contains("HOME="));
+        let shared = content.find("# [shared]").unwrap();
+        let api = content.find("# [api]").unwrap();
+        assert!(shared < content.find("SHARED_TOKEN=").unwrap());
+        assert!(api < content.find("DATABASE_URL=").unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_per_crate_with_dependencies() -> Result<()> {
+        let temp_dir = create_workspace();
+        let root = temp_dir.path();
+        write(
+            root,
+            "Cargo.lock",
+            "version = 3\n\n\
+             [[package]]\nname = \"api\"\nversion = \"0.1.0\"\n\
+             dependencies = [\n \"env_logger\",\n]\n\n\
+             [[package]]\nname = \"env_logger\"\nversion = \"0.11.0\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"worker\"\nversion = \"0.1.0\"\n",
+        );
+        write(
+            root,
+            "services/api/src/main.rs",
+            r#"fn main() { env::var("DATABASE_URL"); env::var("HOME"); }"#,
+        );
+        write(
+            root,
+            "services/worker/src/main.rs",
+            r#"fn main() { env::var("QUEUE_URL"); }"#,
+        );
+        let workspace = Workspace::from_manifest(root)?;
+        let scanner = EnvScanner::with_config(Config {
+            stale: Some(StaleMode::Prune),
+            ..Default::default()
+        })?;
+
+        for _ in 0..2 {
+            let written = generate_per_crate(&scanner, root, &workspace, ".env")?;
+            assert_eq!(written.len(), 2);
+        }
+
+        // Dependency variables are kept across runs instead of being pruned as stale
+        let api = fs::read_to_string(root.join("services/api/.env"))?;
+        assert!(api.contains("DATABASE_URL="));
+        assert_eq!(api.matches("RUST_LOG=").count(), 1);
+        assert!(!api.contains("HOME="));
+        let worker = fs::read_to_string(root.join("services/worker/.env"))?;
+        assert!(worker.contains("QUEUE_URL="));
+        assert!(!worker.contains("RUST_LOG"));
+
+        Ok(())
+    }
+}
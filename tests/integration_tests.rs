@@ -236,6 +236,7 @@ fn main() {
         output: Some(".env".to_string()),
         merge_existing: Some(true),
         ignore: None,
+        ..Default::default()
     };
 
     generate_env_file_with_config(temp_dir.path(), config).unwrap();
@@ -288,6 +289,7 @@ fn main() {
         output: Some(".env".to_string()),
         merge_existing: Some(false),
         ignore: None,
+        ..Default::default()
     };
 
     generate_env_file_with_config(temp_dir.path(), config).unwrap();
@@ -325,6 +327,7 @@ fn main() {
         output: Some(".env".to_string()),
         merge_existing: Some(false),
         ignore: Some(vec!["DEBUG_MODE".to_string(), "SECRET_KEY".to_string()]),
+        ..Default::default()
     };
 
     generate_env_file_with_config(temp_dir.path(), config).unwrap();
@@ -360,6 +363,7 @@ fn main() {
         output: Some(".env.example".to_string()),
         merge_existing: Some(false),
         ignore: None,
+        ..Default::default()
     };
 
     generate_env_file_with_config(temp_dir.path(), config).unwrap();